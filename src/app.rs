@@ -1,9 +1,13 @@
-use crate::state::State;
+use crate::{benchmark::BenchmarkConfig, graphics::gpu::GpuRequest, state::State};
 
 pub struct App<'a> {
     #[cfg(target_arch = "wasm32")]
     pub proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     pub state: Option<State<'a>>,
+    /// The benchmark to start once the state is created, if any.
+    pub benchmark: Option<BenchmarkConfig>,
+    /// How to request the GPU when the state is created.
+    pub gpu_request: GpuRequest,
 }
 
 impl<'a> App<'a> {
@@ -14,6 +18,8 @@ impl<'a> App<'a> {
         let proxy = Some(event_loop.create_proxy());
         Self {
             state: None,
+            benchmark: None,
+            gpu_request: GpuRequest::new(),
             #[cfg(target_arch = "wasm32")]
             proxy,
         }
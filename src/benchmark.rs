@@ -0,0 +1,116 @@
+use std::{f32::consts::TAU, io::Write, path::PathBuf};
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation3, Vector3};
+use crate::core::entity::spatial_transform::SpatialTransform;
+
+/// The radius of the camera's orbit around the origin.
+const ORBIT_RADIUS: f32 = 25.0;
+
+/// The height of the camera's orbit.
+const ORBIT_HEIGHT: f32 = 8.0;
+
+/// Configures a benchmark run.
+#[derive(Clone, Debug)]
+pub struct BenchmarkConfig {
+    /// The number of frames to render before exiting.
+    pub frames: u32,
+    /// Where the per-frame timings are written, as CSV.
+    pub output_path: PathBuf,
+}
+
+/// Records frame times over a benchmark run.
+///
+/// The first frame is a warm-up (ie compiling pipelines and uploading resources), so it isn't recorded.
+/// The camera follows a fixed orbit around the origin, advanced per frame rather than by time,
+/// so every run renders the same frames.
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    frame_times: Vec<f32>,
+    warmed_up: bool,
+}
+
+impl Benchmark {
+    /// Start a benchmark.
+    pub fn new(config: BenchmarkConfig) -> Self {
+        let frame_times = Vec::with_capacity(config.frames as usize);
+        Self { config, frame_times, warmed_up: false }
+    }
+
+    /// Record a frame's time (in seconds), unless it's the warm-up frame.
+    pub fn record(&mut self, delta_time: f32) {
+        if !self.warmed_up {
+            self.warmed_up = true;
+        } else if !self.is_finished() {
+            self.frame_times.push(delta_time);
+        }
+    }
+
+    /// Whether all the frames have been recorded.
+    pub fn is_finished(&self) -> bool {
+        self.frame_times.len() >= self.config.frames as usize
+    }
+
+    /// Get the camera's transform for the current frame.
+    pub fn camera_transform(&self) -> SpatialTransform {
+        let angle = self.frame_times.len() as f32 / self.config.frames.max(1) as f32 * TAU;
+        let position = Vector3::new(angle.cos() * ORBIT_RADIUS, ORBIT_HEIGHT, angle.sin() * ORBIT_RADIUS);
+
+        // look at the origin; yaw then pitch, like the freecam
+        let direction = -position.normalize();
+        let yaw = Quaternion::from_angle_y(Rad(direction.x.atan2(direction.z)));
+        let pitch = Quaternion::from_angle_x(Rad((-direction.y).asin()));
+        SpatialTransform {
+            position,
+            rotation: yaw * pitch,
+            ..SpatialTransform::identity()
+        }
+    }
+
+    /// Write the frame times to the output path, and print a summary of them.
+    pub fn finish(self) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&self.config.output_path)?;
+        writeln!(file, "frame,frame_time_ms")?;
+        for (frame, time) in self.frame_times.iter().enumerate() {
+            writeln!(file, "{frame},{}", time * 1000.0)?;
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(f32::total_cmp);
+        let average = sorted.iter().sum::<f32>() / sorted.len().max(1) as f32;
+        // printed rather than logged, so it's shown regardless of the log level
+        println!(
+            "Benchmark of {} frames: average {:.3}ms, p50 {:.3}ms, p95 {:.3}ms, p99 {:.3}ms (written to {})",
+            sorted.len(),
+            average * 1000.0,
+            percentile(&sorted, 0.5) * 1000.0,
+            percentile(&sorted, 0.95) * 1000.0,
+            percentile(&sorted, 0.99) * 1000.0,
+            self.config.output_path.display(),
+        );
+        Ok(())
+    }
+}
+
+/// Get the (nearest-rank) percentile of sorted values.
+fn percentile(sorted: &[f32], percentile: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (percentile * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_warm_up_frame_isnt_recorded() {
+        let mut benchmark = Benchmark::new(BenchmarkConfig { frames: 2, output_path: "unused.csv".into() });
+        benchmark.record(1.0);
+        benchmark.record(0.016);
+        assert!(!benchmark.is_finished());
+        benchmark.record(0.016);
+        assert!(benchmark.is_finished());
+        assert_eq!(benchmark.frame_times, [0.016, 0.016]);
+    }
+}
@@ -0,0 +1,97 @@
+// Assigns point lights to the clusters of clustered forward rendering; one invocation per cluster.
+//
+// Each cluster is a screen tile between two view-space depths, and gets the lights whose range overlaps its bounds.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct PointLight {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+
+@group(1) @binding(0)
+var<storage, read> point_lights: array<PointLight>;
+
+@group(1) @binding(1)
+var<uniform> point_light_count: u32;
+
+struct ClusterParams {
+    dimensions: vec3<u32>,
+    max_lights_per_cluster: u32,
+    screen_size: vec2<f32>,
+    // the view-space depths of the near + far planes
+    near: f32,
+    far: f32,
+    light_range: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> params: ClusterParams;
+
+// per cluster; the light count, then `max_lights_per_cluster` light indices
+@group(2) @binding(1)
+var<storage, read_write> clusters: array<u32>;
+
+// Get the view-space depth where the slice starts; exponential, so near slices are thinner, unless the near plane is at/behind the camera
+fn slice_depth(slice: u32) -> f32 {
+    let t = f32(slice) / f32(params.dimensions.z);
+    if (params.near > 0.0) {
+        return params.near * pow(params.far / params.near, t);
+    }
+    return mix(params.near, params.far, t);
+}
+
+// Unproject a point from NDC to view space
+fn unproject(ndc: vec2<f32>, z: f32) -> vec3<f32> {
+    let point = camera.inv_proj * vec4(ndc, z, 1.0);
+    return point.xyz / point.w;
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn assign_lights(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= params.dimensions)) {
+        return;
+    }
+
+    // the cluster's bounds in view space; the tile's corners are unprojected onto the near + far planes,
+    // then cut at the slice's depths, which works for both perspective + ortho projections
+    let dimensions = vec2<f32>(params.dimensions.xy);
+    let depths = array<f32, 2>(slice_depth(id.z), slice_depth(id.z + 1u));
+    var bounds_min = vec3(1e30);
+    var bounds_max = vec3(-1e30);
+    for (var corner = 0u; corner < 4u; corner++) {
+        let uv = (vec2<f32>(id.xy) + vec2<f32>(vec2(corner & 1u, corner >> 1u))) / dimensions;
+        // tiles go down the screen, like framebuffer coordinates
+        let ndc = vec2(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+        let near_point = unproject(ndc, 0.0);
+        let far_point = unproject(ndc, 1.0);
+        for (var i = 0u; i < 2u; i++) {
+            let t = (-depths[i] - near_point.z) / (far_point.z - near_point.z);
+            let point = mix(near_point, far_point, t);
+            bounds_min = min(bounds_min, point);
+            bounds_max = max(bounds_max, point);
+        }
+    }
+
+    let cluster = id.x + id.y * params.dimensions.x + id.z * params.dimensions.x * params.dimensions.y;
+    let base = cluster * (params.max_lights_per_cluster + 1u);
+    var count = 0u;
+    for (var i = 0u; i < point_light_count && count < params.max_lights_per_cluster; i++) {
+        let position = (camera.view * vec4(point_lights[i].position, 1.0)).xyz;
+        let offset = clamp(position, bounds_min, bounds_max) - position;
+        if (dot(offset, offset) <= params.light_range * params.light_range) {
+            clusters[base + 1u + count] = i;
+            count++;
+        }
+    }
+    clusters[base] = count;
+}
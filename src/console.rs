@@ -0,0 +1,115 @@
+use std::rc::Rc;
+use egui::{Align2, Color32, FontId, Id, LayerId, Order, Pos2, Rect};
+use rustc_hash::FxHashMap;
+use winit::keyboard::KeyCode;
+use crate::{input::state::InputState, state::State};
+
+/// A console command; takes the arguments after the command name, and returns the output to print.
+pub type ConsoleCommand = Rc<dyn Fn(&[&str], &mut State<'_>) -> String>;
+
+/// A drop-down developer console, for running registered commands.
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    output: Vec<String>,
+    commands: FxHashMap<String, ConsoleCommand>,
+}
+
+impl Console {
+    /// The key that toggles the console.
+    pub const TOGGLE_KEY: KeyCode = KeyCode::Backquote;
+
+    /// The max number of output lines kept.
+    const MAX_OUTPUT_LINES: usize = 100;
+
+    /// The number of output lines shown.
+    const VISIBLE_OUTPUT_LINES: usize = 10;
+
+    /// The height of a line, in pixels.
+    const LINE_HEIGHT: f32 = 16.0;
+
+    /// Create a closed console, without any commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a command under the given name, replacing any existing one.
+    pub fn register<F>(&mut self, name: &str, command: F)
+    where
+        F: Fn(&[&str], &mut State<'_>) -> String + 'static,
+    {
+        self.commands.insert(name.to_string(), Rc::new(command));
+    }
+
+    /// Get the command with the given name.
+    pub fn command(&self, name: &str) -> Option<ConsoleCommand> {
+        self.commands.get(name).cloned()
+    }
+
+    /// Whether the console is open.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Open or close the console.
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// Print a line to the console's output.
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+        if self.output.len() > Self::MAX_OUTPUT_LINES {
+            self.output.remove(0);
+        }
+    }
+
+    /// Handle the frame's text input, returning the entered line if enter was pressed.
+    ///
+    /// Does nothing if the console is closed.
+    pub fn handle_input(&mut self, input: &InputState) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        // the toggle key is typed on the frame it opens the console
+        self.input
+            .extend(input.typed_this_frame().chars().filter(|&c| c != '`'));
+        if input.key_pressed(KeyCode::Backspace) {
+            self.input.pop();
+        }
+        if input.key_pressed(KeyCode::Enter) && !self.input.trim().is_empty() {
+            return Some(std::mem::take(&mut self.input));
+        }
+        None
+    }
+
+    /// Paint the console at the top of the screen, over the other UI.
+    pub fn paint(&self, ctx: &egui::Context) {
+        let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("console")));
+        let width = ctx.content_rect().width();
+        let height = (Self::VISIBLE_OUTPUT_LINES + 1) as f32 * Self::LINE_HEIGHT;
+        painter.rect_filled(
+            Rect::from_min_size(Pos2::ZERO, egui::vec2(width, height)),
+            0.0,
+            Color32::from_black_alpha(200),
+        );
+
+        let visible_output = &self.output[self.output.len().saturating_sub(Self::VISIBLE_OUTPUT_LINES)..];
+        let prompt = format!("> {}_", self.input);
+        let lines = visible_output
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(prompt.as_str()))
+            .enumerate();
+        for (i, line) in lines {
+            painter.text(
+                Pos2::new(4.0, i as f32 * Self::LINE_HEIGHT),
+                Align2::LEFT_TOP,
+                line,
+                FontId::monospace(Self::LINE_HEIGHT - 2.0),
+                Color32::WHITE,
+            );
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use cgmath::{ElementWise, InnerSpace, Matrix3, Vector3};
+use crate::core::entity::spatial_transform::SpatialTransform;
+
+/// A collision shape attached to an entity, in its local space (ie centred on the entity and scaled/rotated with it).
+///
+/// These are only for overlap tests (ie trigger volumes); there's no physics response.
+#[derive(Clone, Copy, Debug)]
+pub enum Collider {
+    /// A box with the given half extents along each axis.
+    Aabb { half_extents: Vector3<f32> },
+    /// A sphere with the given radius.
+    Sphere { radius: f32 },
+}
+
+impl Collider {
+    /// Get the collider's shape in world space, given its entity's transform.
+    ///
+    /// Rotated boxes are enlarged to the axis-aligned box containing them, and non-uniformly scaled
+    /// spheres to the sphere containing them, so the bounds are conservative.
+    pub fn to_world(&self, transform: &SpatialTransform) -> WorldCollider {
+        match *self {
+            Collider::Aabb { half_extents } => {
+                // the extents of the rotated box along each world axis
+                let rotation = Matrix3::from(transform.rotation);
+                let scaled = half_extents.mul_element_wise(transform.scale.map(f32::abs));
+                let abs_axis = |axis: Vector3<f32>| axis.map(f32::abs);
+                let extents = abs_axis(rotation.x) * scaled.x
+                    + abs_axis(rotation.y) * scaled.y
+                    + abs_axis(rotation.z) * scaled.z;
+                WorldCollider::Aabb(Aabb {
+                    min: transform.position - extents,
+                    max: transform.position + extents,
+                })
+            }
+            Collider::Sphere { radius } => {
+                let max_scale = transform.scale.x.abs().max(transform.scale.y.abs()).max(transform.scale.z.abs());
+                WorldCollider::Sphere {
+                    centre: transform.position,
+                    radius: radius * max_scale,
+                }
+            }
+        }
+    }
+}
+
+/// A collider in world space.
+#[derive(Clone, Copy, Debug)]
+pub enum WorldCollider {
+    Aabb(Aabb),
+    Sphere { centre: Vector3<f32>, radius: f32 },
+}
+
+impl WorldCollider {
+    /// Whether the colliders overlap. Touching counts as overlapping.
+    pub fn overlaps(&self, other: &WorldCollider) -> bool {
+        match (self, other) {
+            (WorldCollider::Aabb(a), WorldCollider::Aabb(b)) => a.overlaps(b),
+            (WorldCollider::Sphere { centre: a, radius: a_radius }, WorldCollider::Sphere { centre: b, radius: b_radius }) => {
+                (a - b).magnitude2() <= (a_radius + b_radius).powi(2)
+            }
+            (WorldCollider::Aabb(aabb), WorldCollider::Sphere { centre, radius })
+            | (WorldCollider::Sphere { centre, radius }, WorldCollider::Aabb(aabb)) => {
+                (aabb.closest_point(*centre) - centre).magnitude2() <= radius.powi(2)
+            }
+        }
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// Whether the boxes overlap. Touching counts as overlapping.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x
+            && self.min.y <= other.max.y && self.max.y >= other.min.y
+            && self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    /// Get the point in the box closest to the given one.
+    pub fn closest_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            point.x.clamp(self.min.x, self.max.x),
+            point.y.clamp(self.min.y, self.max.y),
+            point.z.clamp(self.min.z, self.max.z),
+        )
+    }
+}
@@ -0,0 +1,69 @@
+use slotmap::SecondaryMap;
+use crate::core::world::WorldEntityId;
+
+/// Data of one type attached to entities, ie for gameplay components like health or velocity.
+///
+/// Components aren't removed along with their entity, so stores should be cleaned up by whoever removes entities.
+pub struct ComponentStore<T> {
+    components: SecondaryMap<WorldEntityId, T>,
+}
+
+impl<T> ComponentStore<T> {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            components: SecondaryMap::new(),
+        }
+    }
+
+    /// Attach the component to the entity, returning the one it replaced (if any).
+    pub fn insert(&mut self, entity: WorldEntityId, component: T) -> Option<T> {
+        self.components.insert(entity, component)
+    }
+
+    /// Get the entity's component.
+    pub fn get(&self, entity: WorldEntityId) -> Option<&T> {
+        self.components.get(entity)
+    }
+
+    /// Get the entity's component mutably.
+    pub fn get_mut(&mut self, entity: WorldEntityId) -> Option<&mut T> {
+        self.components.get_mut(entity)
+    }
+
+    /// Remove the entity's component, returning it if it had one.
+    pub fn remove(&mut self, entity: WorldEntityId) -> Option<T> {
+        self.components.remove(entity)
+    }
+
+    /// Whether the entity has a component.
+    pub fn contains(&self, entity: WorldEntityId) -> bool {
+        self.components.contains_key(entity)
+    }
+
+    /// Iterate over the entities with components, and their components.
+    pub fn iter(&self) -> impl Iterator<Item = (WorldEntityId, &T)> {
+        self.components.iter()
+    }
+
+    /// Iterate over the entities with components, and their components mutably.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (WorldEntityId, &mut T)> {
+        self.components.iter_mut()
+    }
+
+    /// Get the number of components.
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Whether there aren't any components.
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+impl<T> Default for ComponentStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
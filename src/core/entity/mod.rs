@@ -1,3 +1,5 @@
+use std::cell::Cell;
+
 use crate::core::entity::spatial_transform::SpatialTransform;
 use crate::core::world::WorldEntityId;
 use crate::graphics::scene::raw_spatial_transform::RawSpatialTransform;
@@ -10,7 +12,11 @@ pub struct WorldEntity {
     children: Vec<WorldEntityId>,
     parent_transform: SpatialTransform,
     local_transform: SpatialTransform,
-    already_propagated: bool
+    already_propagated: bool,
+    /// Whether the entity is active; see `set_enabled`.
+    enabled: bool,
+    /// The combined raw transform, if it's up-to-date with the parent + local transforms.
+    raw_transform_cache: Cell<Option<RawSpatialTransform>>,
 }
 
 impl WorldEntity {
@@ -26,12 +32,21 @@ impl WorldEntity {
             local_transform,
             parent_transform: SpatialTransform::identity(),
             already_propagated: false,
+            enabled: true,
+            raw_transform_cache: Cell::new(None),
         }
     }
 
     /// Get the raw overall transform for this entity. For use in shader.
+    ///
+    /// This is cached until the local or parent transform changes, so it's cheap for static entities.
     pub fn transform_raw(&self) -> RawSpatialTransform {
-        self.parent_transform.combine_raw(&self.local_transform)
+        if let Some(raw_transform) = self.raw_transform_cache.get() {
+            return raw_transform;
+        }
+        let raw_transform = self.parent_transform.combine_raw(&self.local_transform);
+        self.raw_transform_cache.set(Some(raw_transform));
+        raw_transform
     }
 
     /// Get the overall transform for this entity. For propagation.
@@ -59,6 +74,23 @@ impl WorldEntity {
         self.already_propagated
     }
 
+    /// Whether the entity itself is enabled; see `World::enabled_in_hierarchy` for whether it's active.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable the entity, ie to deactivate an object without removing it.
+    ///
+    /// This applies to its whole subtree: transforms aren't propagated into it, its mesh instances aren't rendered,
+    /// and lights positioned by it are left out of the lighting.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            // the children may have missed updates while it was disabled
+            self.already_propagated = false;
+        }
+        self.enabled = enabled;
+    }
+
     /// Update the node's local transform.
     pub fn update_local_transform<F>(&mut self, mut update: F)
     where
@@ -66,6 +98,7 @@ impl WorldEntity {
     {
         update(&mut self.local_transform);
         self.already_propagated = false;
+        self.raw_transform_cache.set(None);
     }
 
     /// Update the node's global transform.
@@ -75,6 +108,17 @@ impl WorldEntity {
     {
         update(&mut self.parent_transform);
         self.already_propagated = false;
+        self.raw_transform_cache.set(None);
+    }
+
+    /// Add a child.
+    pub(super) fn add_child(&mut self, child: WorldEntityId) {
+        self.children.push(child);
+    }
+
+    /// Remove a child, ie when it's moved to another parent.
+    pub(super) fn remove_child(&mut self, child: WorldEntityId) {
+        self.children.retain(|&id| id != child);
     }
 
     /// Set a new parent.
@@ -1,7 +1,42 @@
-use cgmath::{ElementWise, Matrix, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3, Zero};
+use cgmath::{Deg, ElementWise, VectorSpace, Matrix, Matrix3, Matrix4, Quaternion, Rotation3, SquareMatrix, Vector3, Zero};
 
 use crate::graphics::scene::raw_spatial_transform::RawSpatialTransform;
 
+/// Which axis points up in a world.
+///
+/// Rendering is always Y-up; Z-up worlds are converted through their root entity (see `World::set_up_axis`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    /// Get the up direction.
+    pub fn up(&self) -> Vector3<f32> {
+        match self {
+            UpAxis::Y => Vector3::unit_y(),
+            UpAxis::Z => Vector3::unit_z(),
+        }
+    }
+
+    /// Get the horizontal direction that's rendered as forward (ie +Z in Y-up).
+    pub fn forward(&self) -> Vector3<f32> {
+        match self {
+            UpAxis::Y => Vector3::unit_z(),
+            UpAxis::Z => -Vector3::unit_y(),
+        }
+    }
+
+    /// Get the rotation from this axis' space to Y-up.
+    pub fn to_y_up(&self) -> Quaternion<f32> {
+        match self {
+            UpAxis::Y => Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            UpAxis::Z => Quaternion::from_angle_x(Deg(-90.0)),
+        }
+    }
+}
+
 /// Represents the spacial data for anything.
 #[derive(Clone, Copy)]
 pub struct SpatialTransform {
@@ -24,7 +59,6 @@ impl SpatialTransform {
     pub fn to_raw(&self) -> RawSpatialTransform {
         RawSpatialTransform {
             model: self.model().into(),
-            normal: self.normal().into(),
         }
     }
 
@@ -50,16 +84,44 @@ impl SpatialTransform {
         m3.invert().unwrap_or(Matrix3::identity()).transpose()
     }
 
+    /// Convert a transform from a Z-up space into Y-up, ie for objects imported from Z-up tools (like Blender).
+    ///
+    /// Only top-level transforms need converting, since children are relative to them.
+    /// The object's own axes are rotated too, so its (Z-up) mesh is upright.
+    pub fn z_up_to_y_up(&self) -> SpatialTransform {
+        let conversion = SpatialTransform {
+            rotation: UpAxis::Z.to_y_up(),
+            ..SpatialTransform::identity()
+        };
+        conversion.combine(self)
+    }
+
     /// Get the forward direction of the transform.
+    ///
+    /// This is the transform's local +Z, regardless of the world's up axis.
     pub fn forward(&self) -> Vector3<f32> {
         self.rotation * Vector3::unit_z()
     }
 
     /// Get the up direction of the transform.
+    ///
+    /// This is the transform's local +Y, regardless of the world's up axis. Entities' overall transforms
+    /// include the root's conversion to Y-up, so for them this is the up direction as rendered.
     pub fn up(&self) -> Vector3<f32> {
         self.rotation * Vector3::unit_y()
     }
 
+    /// Interpolate between this and another transform, where `t` is 0 for this one and 1 for the other.
+    ///
+    /// The rotation takes the shortest path between the two.
+    pub fn lerp(&self, other: &SpatialTransform, t: f32) -> SpatialTransform {
+        SpatialTransform {
+            scale: self.scale.lerp(other.scale, t),
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
+
     /// Combines this transform with a child transform.
     /// Returns the resulting overall transform of the child.
     pub fn combine(&self, child: &SpatialTransform) -> SpatialTransform {
@@ -80,15 +142,8 @@ impl SpatialTransform {
     /// Combine this transform with a child transform.
     /// Returns the resulting raw overall transform of the child.
     pub fn combine_raw(&self, b: &SpatialTransform) -> RawSpatialTransform {
-        let combined_model = self.model() * b.model();
-        let m3 = Matrix3::from_cols(
-            combined_model.x.truncate(),
-            combined_model.y.truncate(),
-            combined_model.z.truncate(),
-        );
         RawSpatialTransform {
-            model: combined_model.into(),
-            normal: m3.invert().unwrap_or(Matrix3::identity()).transpose().into(),
+            model: (self.model() * b.model()).into(),
         }
     }
 }
\ No newline at end of file
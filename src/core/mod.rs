@@ -1,2 +1,4 @@
+pub mod collision;
+pub mod components;
 pub mod entity;
 pub mod world;
\ No newline at end of file
@@ -1,6 +1,7 @@
-use slotmap::{SecondaryMap, SlotMap, new_key_type};
+use cgmath::{ElementWise, Quaternion, Rotation, Vector3};
+use slotmap::{SlotMap, new_key_type};
 use std::collections::VecDeque;
-use crate::core::entity::{WorldEntity, spatial_transform::SpatialTransform};
+use crate::core::{collision::{Collider, WorldCollider}, components::ComponentStore, entity::{WorldEntity, spatial_transform::{SpatialTransform, UpAxis}}};
 
 new_key_type! {
     pub struct WorldEntityId;
@@ -10,7 +11,9 @@ new_key_type! {
 /// for other systems to reference.
 pub struct World {
     entities: SlotMap<WorldEntityId, WorldEntity>,
-    root_entity: WorldEntityId
+    root_entity: WorldEntityId,
+    colliders: ComponentStore<Collider>,
+    up_axis: UpAxis,
 }
 
 impl World {
@@ -24,21 +27,87 @@ impl World {
         ));
         Self {
             entities,
-            root_entity
+            root_entity,
+            colliders: ComponentStore::new(),
+            up_axis: UpAxis::Y,
         }
     }
     
     /// Add the given entity and return their ID.
+    /// 
+    /// If no parent is given, the entity is a child of the root entity.
     pub fn add_entity(&mut self, mut parent: Option<WorldEntityId>, children: Vec<WorldEntityId>, local_transform: SpatialTransform) -> WorldEntityId {
         if parent.is_none() {
             parent = Some(self.root_entity);
         }
         let entity = WorldEntity::new(
             parent, 
-            children, 
+            children.clone(), 
             local_transform
         );
-        self.entities.insert(entity)
+        let entity_id = self.entities.insert(entity);
+
+        // link up the graph, so transforms are propagated through it; the parent may already have been propagated,
+        // so its transform is set on the new entity right away, rather than waiting for the parent to change
+        if let Some(parent) = parent.and_then(|parent| self.entities.get_mut(parent)) {
+            parent.add_child(entity_id);
+            let parent_transform = parent.transform();
+            self.entities[entity_id].update_parent_transform(|transform| *transform = parent_transform);
+        }
+        let entity_transform = self.entities[entity_id].transform();
+        for child_id in children {
+            // detach the child from its old parent first, so it only has one
+            let old_parent = self.entities.get(child_id).and_then(|child| *child.parent());
+            if let Some(old_parent) = old_parent.and_then(|old_parent| self.entities.get_mut(old_parent)) {
+                old_parent.remove_child(child_id);
+            }
+            if let Some(child) = self.entities.get_mut(child_id) {
+                child.set_parent(entity_id);
+                child.update_parent_transform(|transform| *transform = entity_transform);
+            }
+        }
+        entity_id
+    }
+
+    /// Start spawning an entity, which is added to the world on `EntitySpawner::build`.
+    /// 
+    /// Unset parts are the same as for `add_entity`, ie no parent means a child of the root,
+    /// and the transform is the identity.
+    pub fn spawn(&mut self) -> EntitySpawner<'_> {
+        EntitySpawner {
+            world: self,
+            parent: None,
+            children: vec![],
+            local_transform: SpatialTransform::identity(),
+        }
+    }
+
+    /// Get the root entity, ie the ancestor of all other entities.
+    /// 
+    /// Its transform can be changed via `entity_mut` like any other entity, ie to offset/scale the whole world;
+    /// the change reaches all its descendants on the next `update_graph`.
+    pub fn root(&self) -> WorldEntityId {
+        self.root_entity
+    }
+
+    /// Get the world's up axis. This is Y by default.
+    pub fn up_axis(&self) -> UpAxis {
+        self.up_axis
+    }
+
+    /// Set the world's up axis, ie to Z for scenes authored in Z-up tools.
+    /// 
+    /// The root entity is rotated to convert the world to Y-up for rendering, so entities' local transforms are in the
+    /// given axis' space, while their overall transforms (`WorldEntity::transform`) are Y-up.
+    /// 
+    /// ## Note
+    /// Entities' own axes are unchanged (ie forward is +Z + up is +Y locally), so in a Z-up world an unrotated camera
+    /// looks straight up; rotate it by `up_axis.to_y_up().invert()` to face the horizon.
+    pub fn set_up_axis(&mut self, up_axis: UpAxis) {
+        let conversion = up_axis.to_y_up() * self.up_axis.to_y_up().invert();
+        self.up_axis = up_axis;
+        self.entities[self.root_entity]
+            .update_local_transform(|transform| transform.rotation = conversion * transform.rotation);
     }
 
     /// Get the given entity.
@@ -51,13 +120,121 @@ impl World {
         self.entities.get_mut(id)
     }
 
+    /// Whether the entity is active, ie it and all its ancestors are enabled; see `WorldEntity::set_enabled`.
+    ///
+    /// Returns `None` if the entity doesn't exist.
+    pub fn enabled_in_hierarchy(&self, id: WorldEntityId) -> Option<bool> {
+        let mut entity = self.entities.get(id)?;
+        loop {
+            if !entity.enabled() {
+                return Some(false);
+            }
+            match entity.parent().and_then(|parent| self.entities.get(parent)) {
+                Some(parent) => entity = parent,
+                None => return Some(true),
+            }
+        }
+    }
+
+    /// Get the entity's transform in world space, by combining the local transforms up its parent chain.
+    ///
+    /// Unlike `WorldEntity::transform`, this is correct even if the transforms haven't been propagated by `update_graph`
+    /// (ie right after spawning it or moving a parent), but walks the chain each time. Returns `None` if the entity
+    /// (or one of its ancestors) doesn't exist.
+    pub fn world_transform(&self, id: WorldEntityId) -> Option<SpatialTransform> {
+        let entity = self.entities.get(id)?;
+        let mut transform = entity.local_transform();
+        let mut parent = *entity.parent();
+        while let Some(parent_id) = parent {
+            let parent_entity = self.entities.get(parent_id)?;
+            transform = parent_entity.local_transform().combine(&transform);
+            parent = *parent_entity.parent();
+        }
+        Some(transform)
+    }
+
+    /// Attach a collider to the entity, replacing any existing one.
+    /// 
+    /// Returns false if the entity doesn't exist.
+    pub fn set_collider(&mut self, id: WorldEntityId, collider: Collider) -> bool {
+        if !self.entities.contains_key(id) {
+            return false;
+        }
+        self.colliders.insert(id, collider);
+        true
+    }
+
+    /// Remove the entity's collider, returning it if it had one.
+    pub fn remove_collider(&mut self, id: WorldEntityId) -> Option<Collider> {
+        self.colliders.remove(id)
+    }
+
+    /// Get the entity's collider in world space, if it has one.
+    pub fn world_collider(&self, id: WorldEntityId) -> Option<WorldCollider> {
+        let collider = self.colliders.get(id)?;
+        let entity = self.entities.get(id)?;
+        Some(collider.to_world(&entity.transform()))
+    }
+
+    /// Whether the entities' colliders overlap.
+    /// 
+    /// False if either doesn't have a collider. This uses the transforms as of the last `update_graph`.
+    pub fn overlaps(&self, a: WorldEntityId, b: WorldEntityId) -> bool {
+        match (self.world_collider(a), self.world_collider(b)) {
+            (Some(a), Some(b)) => a.overlaps(&b),
+            _ => false,
+        }
+    }
+
+    /// Get all other entities whose colliders overlap the entity's.
+    /// 
+    /// This tests against every collider, so it's only meant for a modest number of them.
+    pub fn query_overlaps(&self, id: WorldEntityId) -> Vec<WorldEntityId> {
+        let Some(collider) = self.world_collider(id) else {
+            return vec![];
+        };
+        self.colliders
+            .iter()
+            .map(|(other, _)| other)
+            .filter(|&other| other != id)
+            .filter(|&other| {
+                self.world_collider(other)
+                    .is_some_and(|other| collider.overlaps(&other))
+            })
+            .collect()
+    }
+
+    /// Shift the whole world so that `new_origin` becomes the origin, keeping all relative positions.
+    /// 
+    /// This is for floating-origin, ie calling it when the camera drifts far from the origin so coordinates
+    /// around it stay small, and don't lose precision. The root's children are moved, so the root stays as-is.
+    pub fn rebase(&mut self, new_origin: Vector3<f32>) {
+        let root = &self.entities[self.root_entity];
+        let root_transform = root.transform();
+        let children = root.children().clone();
+
+        // the children's positions are in the root's space
+        let offset = (root_transform.rotation.invert() * new_origin).div_element_wise(root_transform.scale);
+        for child in children {
+            if let Some(child) = self.entities.get_mut(child) {
+                child.update_local_transform(|transform| transform.position -= offset);
+            }
+        }
+    }
+
     /// Walks the entity graph and propagates each entity's transforms to its children's parent transforms.
-    fn update_graph(&mut self) {
+    /// 
+    /// This should be called once per frame, after transforms are updated and before they're used (ie for rendering).
+    pub fn update_graph(&mut self) {
         let mut node_queue = VecDeque::with_capacity(self.entities.len());
         node_queue.push_front(self.root_entity);
         while !node_queue.is_empty() {
             let cur_entity_id = node_queue.pop_back().unwrap();
             let cur_entity = self.entities.get_mut(cur_entity_id).unwrap();
+            // disabled entities' subtrees are skipped until they're enabled again
+            if !cur_entity.enabled() {
+                continue;
+            }
             let children = cur_entity.children().clone();
 
             if !cur_entity.already_propagated() {
@@ -75,4 +252,191 @@ impl World {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// A builder for adding an entity to the world, from `World::spawn`.
+pub struct EntitySpawner<'w> {
+    world: &'w mut World,
+    parent: Option<WorldEntityId>,
+    children: Vec<WorldEntityId>,
+    local_transform: SpatialTransform,
+}
+
+impl EntitySpawner<'_> {
+    /// Set the entity's parent.
+    pub fn parent(mut self, parent: WorldEntityId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Make the entity a child of the given one; the same as `parent`.
+    pub fn child_of(self, parent: WorldEntityId) -> Self {
+        self.parent(parent)
+    }
+
+    /// Make the given entity a child of this one.
+    pub fn child(mut self, child: WorldEntityId) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Set the entity's local position.
+    pub fn position(mut self, position: Vector3<f32>) -> Self {
+        self.local_transform.position = position;
+        self
+    }
+
+    /// Set the entity's local rotation.
+    pub fn rotation(mut self, rotation: Quaternion<f32>) -> Self {
+        self.local_transform.rotation = rotation;
+        self
+    }
+
+    /// Set the entity's local scale.
+    pub fn scale(mut self, scale: Vector3<f32>) -> Self {
+        self.local_transform.scale = scale;
+        self
+    }
+
+    /// Add the entity to the world and return its ID.
+    pub fn build(self) -> WorldEntityId {
+        self.world.add_entity(self.parent, self.children, self.local_transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Rotation3, Vector3, assert_abs_diff_eq};
+    use super::*;
+
+    /// Get the entity's position as of the last `update_graph`.
+    fn position(world: &World, id: WorldEntityId) -> Vector3<f32> {
+        world.entity(id).unwrap().transform().position
+    }
+
+    #[test]
+    fn moving_the_root_moves_all_descendants() {
+        let mut world = World::new();
+        let parent = world.add_entity(None, vec![], SpatialTransform { position: Vector3::new(1.0, 0.0, 0.0), ..SpatialTransform::identity() });
+        let child = world.add_entity(Some(parent), vec![], SpatialTransform { position: Vector3::new(0.0, 2.0, 0.0), ..SpatialTransform::identity() });
+        world.update_graph();
+
+        let offset = Vector3::new(10.0, 20.0, 30.0);
+        let root = world.root();
+        world.entity_mut(root).unwrap().update_local_transform(|transform| transform.position += offset);
+        // spawned after the root + parent were already propagated
+        let late_child = world.add_entity(Some(parent), vec![], SpatialTransform { position: Vector3::new(0.0, 0.0, 3.0), ..SpatialTransform::identity() });
+        world.update_graph();
+
+        assert_abs_diff_eq!(position(&world, parent), Vector3::new(1.0, 0.0, 0.0) + offset);
+        assert_abs_diff_eq!(position(&world, child), Vector3::new(1.0, 2.0, 0.0) + offset);
+        assert_abs_diff_eq!(position(&world, late_child), Vector3::new(1.0, 0.0, 3.0) + offset);
+    }
+
+    #[test]
+    fn entity_spawned_under_a_propagated_parent_gets_its_transform() {
+        let mut world = World::new();
+        let parent = world.add_entity(None, vec![], SpatialTransform { position: Vector3::new(5.0, 0.0, 0.0), ..SpatialTransform::identity() });
+        world.update_graph();
+
+        let child = world.add_entity(Some(parent), vec![], SpatialTransform::identity());
+        assert_abs_diff_eq!(position(&world, child), Vector3::new(5.0, 0.0, 0.0));
+        world.update_graph();
+        assert_abs_diff_eq!(position(&world, child), Vector3::new(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn adopted_child_is_detached_from_its_old_parent() {
+        let mut world = World::new();
+        let old_parent = world.add_entity(None, vec![], SpatialTransform { position: Vector3::new(1.0, 0.0, 0.0), ..SpatialTransform::identity() });
+        let child = world.add_entity(Some(old_parent), vec![], SpatialTransform::identity());
+        world.update_graph();
+
+        let new_parent = world.add_entity(None, vec![child], SpatialTransform { position: Vector3::new(0.0, 7.0, 0.0), ..SpatialTransform::identity() });
+        assert!(!world.entity(old_parent).unwrap().children().contains(&child));
+        assert_eq!(*world.entity(child).unwrap().parent(), Some(new_parent));
+
+        world.update_graph();
+        assert_abs_diff_eq!(position(&world, child), Vector3::new(0.0, 7.0, 0.0));
+    }
+
+    #[test]
+    fn rebase_keeps_relative_positions() {
+        let mut world = World::new();
+        let root = world.root();
+        world.entity_mut(root).unwrap().update_local_transform(|transform| {
+            transform.position = Vector3::new(3.0, -1.0, 2.0);
+            transform.rotation = Quaternion::from_angle_y(Deg(40.0)) * Quaternion::from_angle_x(Deg(25.0));
+            transform.scale = Vector3::new(2.0, 0.5, 1.5);
+        });
+        let a = world.add_entity(None, vec![], SpatialTransform { position: Vector3::new(100.0, 20.0, -50.0), ..SpatialTransform::identity() });
+        let b = world.add_entity(Some(a), vec![], SpatialTransform { position: Vector3::new(1.0, 2.0, 3.0), ..SpatialTransform::identity() });
+        let c = world.add_entity(None, vec![], SpatialTransform { position: Vector3::new(-7.0, 0.0, 4.0), ..SpatialTransform::identity() });
+        world.update_graph();
+        let (a_before, b_before, c_before) = (position(&world, a), position(&world, b), position(&world, c));
+
+        world.rebase(a_before);
+        world.update_graph();
+
+        assert_abs_diff_eq!(position(&world, a), Vector3::new(0.0, 0.0, 0.0), epsilon = 1e-3);
+        assert_abs_diff_eq!(position(&world, b) - position(&world, a), b_before - a_before, epsilon = 1e-3);
+        assert_abs_diff_eq!(position(&world, c) - position(&world, a), c_before - a_before, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn spawner_sets_the_transform_and_parent() {
+        let mut world = World::new();
+        let parent = world.spawn()
+            .position(Vector3::new(0.0, 10.0, 0.0))
+            .rotation(Quaternion::from_angle_y(Deg(90.0)))
+            .build();
+        world.update_graph();
+
+        let child = world.spawn()
+            .child_of(parent)
+            .position(Vector3::new(1.0, 0.0, 0.0))
+            .scale(Vector3::new(2.0, 2.0, 2.0))
+            .build();
+        world.update_graph();
+
+        let entity = world.entity(child).unwrap();
+        assert_eq!(*entity.parent(), Some(parent));
+        assert!(world.entity(parent).unwrap().children().contains(&child));
+        assert_abs_diff_eq!(entity.local_transform().scale, Vector3::new(2.0, 2.0, 2.0));
+        // +x rotated 90 degrees about y is -z
+        assert_abs_diff_eq!(entity.transform().position, Vector3::new(0.0, 10.0, -1.0), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn world_transform_is_correct_without_propagation() {
+        let mut world = World::new();
+        let parent = world.add_entity(None, vec![], SpatialTransform::identity());
+        let child = world.add_entity(Some(parent), vec![], SpatialTransform { position: Vector3::new(1.0, 0.0, 0.0), ..SpatialTransform::identity() });
+        world.update_graph();
+
+        world.entity_mut(parent).unwrap().update_local_transform(|transform| {
+            transform.position = Vector3::new(0.0, 5.0, 0.0);
+            transform.scale = Vector3::new(3.0, 3.0, 3.0);
+        });
+
+        // not propagated yet, so the entity's own transform is stale
+        assert_abs_diff_eq!(position(&world, child), Vector3::new(1.0, 0.0, 0.0));
+        let transform = world.world_transform(child).unwrap();
+        assert_abs_diff_eq!(transform.position, Vector3::new(3.0, 5.0, 0.0));
+        assert_abs_diff_eq!(transform.scale, Vector3::new(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn disabling_a_parent_disables_its_subtree() {
+        let mut world = World::new();
+        let parent = world.add_entity(None, vec![], SpatialTransform::identity());
+        let child = world.add_entity(Some(parent), vec![], SpatialTransform::identity());
+        let grandchild = world.add_entity(Some(child), vec![], SpatialTransform::identity());
+        assert_eq!(world.enabled_in_hierarchy(grandchild), Some(true));
+
+        world.entity_mut(parent).unwrap().set_enabled(false);
+        assert_eq!(world.enabled_in_hierarchy(child), Some(false));
+        assert_eq!(world.enabled_in_hierarchy(grandchild), Some(false));
+        assert_eq!(world.enabled_in_hierarchy(WorldEntityId::default()), None);
+    }
+}
@@ -1,38 +1,62 @@
-use egui::{Area, ClippedPrimitive, Grid, Id, RawInput, Ui, UiBuilder, ViewportId};
+use egui::{Align2, Area, ClippedPrimitive, Color32, FontId, Grid, Id, LayerId, Pos2, RawInput, Ui, UiBuilder, ViewportId};
 use egui_wgpu::{RendererOptions, ScreenDescriptor};
 use wgpu::{Adapter, CommandEncoder, Instance, PresentMode, RenderPass, Surface, TextureFormat, TextureView, rwh::{DisplayHandle, WindowHandle}};
 use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
-use crate::graphics::{gpu::GpuContext, render::hdr::HdrPipeline};
+use crate::{console::Console, graphics::{gpu::GpuContext, render::label::ScreenLabel}};
 
-/// Represents the data of a debug menu.
+/// Represents the data of a debug menu panel.
 pub trait DebugMenuData {
+    /// The title of the panel's window.
+    fn title(&self) -> &str {
+        "Debug Menu"
+    }
+
     /// Render the data, and possibly mutate the data based on interactions.
     fn ui(&mut self, ui: &mut Ui);
 }
 
+/// What the debug menu shows for a frame.
+pub struct DebugMenuFrame<'a, 'p> {
+    /// The panels, each shown in its own window.
+    pub panels: &'a mut [&'p mut dyn DebugMenuData],
+    pub labels: &'a [ScreenLabel],
+    pub console: &'a Console,
+}
+
 /// Represents the debug menu.
 pub struct DebugMenu {
     renderer: egui_wgpu::Renderer,
     state: egui_winit::State,
     screen_descriptor: ScreenDescriptor,
+    /// The primitives from the last `setup_render`, kept until the next one.
+    primitives: Vec<ClippedPrimitive>,
 }
 
 impl DebugMenu {
+    /// The horizontal spacing between the panels' initial positions.
+    const PANEL_SPACING: f32 = 250.0;
+
     /// Instantiate the debug menu.
+    /// 
+    /// The color format, sample count and depth format must match the render pass the menu is rendered in,
+    /// ie `Renderer::target_format()` with no depth for `Renderer::render_with_render_pass(.., None)`.
     pub fn new(
         gpu: &GpuContext, 
         surface: &DisplayHandle, 
-        window_size: PhysicalSize<u32>
+        window_size: PhysicalSize<u32>,
+        color_format: TextureFormat,
+        msaa_samples: u32,
+        depth_stencil_format: Option<TextureFormat>,
     ) -> Self {
         let renderer_options = RendererOptions {
-            msaa_samples: 1,
-            depth_stencil_format: None,
+            msaa_samples,
+            depth_stencil_format,
             dithering: true,
             predictable_texture_filtering: false,
         };
         let renderer = egui_wgpu::Renderer::new(
             gpu.device(), 
-            HdrPipeline::COLOR_FORMAT, 
+            color_format, 
             renderer_options
         );
         let state = egui_winit::State::new(
@@ -50,7 +74,8 @@ impl DebugMenu {
         Self {
             renderer,
             state,
-            screen_descriptor
+            screen_descriptor,
+            primitives: Vec::new(),
         }
     }
 
@@ -67,24 +92,50 @@ impl DebugMenu {
         self.screen_descriptor.size_in_pixels = [width, height];
     }
 
-    /// Setup for the render, returning the primitives required for rendering.
+    /// Setup and render the menu for the frame in one go, ie `setup_render` then `render`.
+    ///
+    /// The render pass needs the format etc the menu was created with (see `new`).
+    pub fn render_frame(&mut self, window: &Window, render_pass: RenderPass<'_>, frame: DebugMenuFrame<'_, '_>, gpu: &GpuContext) {
+        // egui only uses the encoder for paint callbacks, which the menu doesn't have, so it's never submitted
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("debug_menu_encoder"),
+            });
+        self.setup_render(window, &mut encoder, frame, gpu);
+        self.render(render_pass);
+    }
+
+    /// Setup for the render, ie running the UI and uploading its textures + buffers.
     pub fn setup_render(
         &mut self, 
         window: &Window, 
         encoder: &mut CommandEncoder, 
-        data: &mut impl DebugMenuData,
+        frame: DebugMenuFrame<'_, '_>,
         gpu: &GpuContext
-    ) -> Vec<ClippedPrimitive> {
+    ) {
+        let DebugMenuFrame { panels, labels, console } = frame;
         let input = self.state.take_egui_input(window);
         let output = self.state.egui_ctx().run(input, |ctx| {
-            let ui = egui::Window::new("Debug Menu")
-                .current_pos([0.0, 0.0])
-                .default_size([100.0, 100.0])
-                .show(ctx, |ui| {
-                    self.ui(ui, data);
+            Self::paint_labels(ctx, labels);
+            if console.is_open() {
+                console.paint(ctx);
+            }
+            // the first panel is pinned to the top-left; the others start next to it but can be moved
+            for (i, panel) in panels.iter_mut().enumerate() {
+                let window = egui::Window::new(panel.title().to_string())
+                    .id(Id::new(("debug_menu_panel", i)))
+                    .default_size([100.0, 100.0]);
+                let window = match i {
+                    0 => window.current_pos([0.0, 0.0]),
+                    _ => window.default_pos([i as f32 * Self::PANEL_SPACING, 0.0]),
+                };
+                window.show(ctx, |ui| {
+                    self.ui(ui, i, &mut **panel);
                 });
+            }
         });
-        let primitives = self.state
+        self.primitives = self.state
             .egui_ctx()
             .tessellate(output.shapes, output.pixels_per_point);
 
@@ -98,28 +149,40 @@ impl DebugMenu {
             gpu.device(), 
             gpu.queue(), 
             encoder, 
-            &primitives, 
+            &self.primitives, 
             &self.screen_descriptor
         );
-        primitives
     }
 
-    /// Render the menu.
-    /// 
-    /// Make sure you call `setup_render` beforehand.
-    pub fn render(&mut self, primitives: &Vec<ClippedPrimitive>, render_pass: RenderPass<'_>) {
+    /// Render the menu, as of the last `setup_render`.
+    pub fn render(&mut self, render_pass: RenderPass<'_>) {
         let mut render_pass = render_pass.forget_lifetime();
         self.renderer.render(
             &mut render_pass, 
-            &primitives,
+            &self.primitives,
             &self.screen_descriptor 
         );
     }
 
-    /// Build the menu UI.
-    fn ui(&self, ui: &mut Ui, data: &mut impl DebugMenuData) {
+    /// Paint the labels behind the other UI.
+    fn paint_labels(ctx: &egui::Context, labels: &[ScreenLabel]) {
+        let painter = ctx.layer_painter(LayerId::background());
+        for label in labels {
+            let [r, g, b, a] = label.color.map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+            painter.text(
+                Pos2::from(label.position),
+                Align2::CENTER_BOTTOM,
+                &label.text,
+                FontId::default(),
+                Color32::from_rgba_unmultiplied(r, g, b, a),
+            );
+        }
+    }
+
+    /// Build a panel's UI.
+    fn ui(&self, ui: &mut Ui, index: usize, data: &mut dyn DebugMenuData) {
         ui.scope_builder(UiBuilder::new(), |ui| {
-            Grid::new("debug_menu_grid")
+            Grid::new(("debug_menu_grid", index))
                 .num_columns(2)
                 .striped(true)
                 .show(ui, |ui| {
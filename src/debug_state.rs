@@ -1,23 +1,26 @@
 use cgmath::{Vector3, Zero};
 
-use crate::debug_menu::DebugMenuData;
+use crate::{debug_menu::DebugMenuData, graphics::render::renderer::FrameStats};
 
 /// The debug state for our game.
 pub struct DebugState {
-    camera_position: Vector3<f32> 
+    camera_position: Vector3<f32>,
+    frame_stats: FrameStats,
 }
 
 impl DebugState {
     /// Instantiate.
     pub fn new() -> Self {
         Self {
-            camera_position: Vector3::zero()
+            camera_position: Vector3::zero(),
+            frame_stats: FrameStats::zeroed(),
         }
     }
 
     /// Update the debug state.
-    pub fn update(&mut self, camera_position: Vector3<f32>) {
+    pub fn update(&mut self, camera_position: Vector3<f32>, frame_stats: FrameStats) {
         self.camera_position = camera_position;
+        self.frame_stats = frame_stats;
     }
 }
 
@@ -26,5 +29,8 @@ impl DebugMenuData for DebugState {
         ui.label("Camera Position: ");
         ui.label(format!("{:.3}, {:.3}, {:.3}", self.camera_position.x, self.camera_position.y, self.camera_position.z));
         ui.end_row();
+        ui.label("Draw Calls: ");
+        ui.label(format!("{} ({} instances, {} triangles)", self.frame_stats.draw_calls, self.frame_stats.instances, self.frame_stats.triangles));
+        ui.end_row();
     }
 }
\ No newline at end of file
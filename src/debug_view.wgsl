@@ -0,0 +1,100 @@
+// Debug views of the scene's meshes, drawn instead of their own pipelines (see `DebugView`).
+//
+// This uses the same bind groups as `shader.wgsl`, though only the camera is read.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// The view distance shown as white in the depth view
+const DEPTH_RANGE: f32 = 100.0;
+
+// How much each overlapping fragment adds in the overdraw view
+const OVERDRAW_STEP: vec3<f32> = vec3(0.1, 0.05, 0.02);
+
+struct InstanceInput {
+    @location(7) mat_1: vec4<f32>,
+    @location(8) mat_2: vec4<f32>,
+    @location(9) mat_3: vec4<f32>,
+    @location(10) mat_4: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) view_depth: f32,
+}
+
+// The inverse-transpose of the model matrix's upper 3x3, for transforming normals.
+// The cofactor matrix over the determinant is that inverse-transpose.
+fn normal_matrix_of(model: mat4x4<f32>) -> mat3x3<f32> {
+    let c0 = model[0].xyz;
+    let c1 = model[1].xyz;
+    let c2 = model[2].xyz;
+    let cofactor = mat3x3<f32>(cross(c1, c2), cross(c2, c0), cross(c0, c1));
+    let det = dot(c0, cofactor[0]);
+    if (det == 0.0) {
+        return mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    }
+    return cofactor * (1.0 / det);
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.mat_1,
+        instance.mat_2,
+        instance.mat_3,
+        instance.mat_4
+    );
+    let normal_matrix = normal_matrix_of(model_matrix);
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    out.world_normal = normalize(normal_matrix * model.normal);
+    // the view looks down -z
+    out.view_depth = -(camera.view * world_position).z;
+    return out;
+}
+
+// World-space normals, mapped from -1..1 to 0..1
+@fragment
+fn fs_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+}
+
+// UVs as red + green, wrapped so tiling UVs show their repeats
+@fragment
+fn fs_uvs(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(fract(in.tex_coords), 0.0, 1.0);
+}
+
+// Linear view distance as grayscale, from black at the camera to white at `DEPTH_RANGE`
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(vec3(clamp(in.view_depth / DEPTH_RANGE, 0.0, 1.0)), 1.0);
+}
+
+// A constant step, additively blended, so brighter areas are drawn more times
+@fragment
+fn fs_overdraw(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(OVERDRAW_STEP, 1.0);
+}
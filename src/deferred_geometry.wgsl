@@ -0,0 +1,160 @@
+// The geometry pass of deferred rendering; writes each fragment's surface to the G-buffer,
+// for `deferred_lighting.wgsl` to light.
+//
+// This uses the same bind groups as `shader.wgsl`, so meshes can be drawn with either.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct InstanceInput {
+    @location(7) mat_1: vec4<f32>,
+    @location(8) mat_2: vec4<f32>,
+    @location(9) mat_3: vec4<f32>,
+    @location(10) mat_4: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) tex_coords2: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) tex_coords2: vec2<f32>,
+}
+
+// Normalize the vector, leaving zero vectors as-is
+fn safe_normalize(v: vec3<f32>) -> vec3<f32> {
+    let length_squared = dot(v, v);
+    if (length_squared == 0.0) {
+        return v;
+    }
+    return v * inverseSqrt(length_squared);
+}
+
+// The inverse-transpose of the model matrix's upper 3x3, for transforming normals.
+// The cofactor matrix over the determinant is that inverse-transpose.
+fn normal_matrix_of(model: mat4x4<f32>) -> mat3x3<f32> {
+    let c0 = model[0].xyz;
+    let c1 = model[1].xyz;
+    let c2 = model[2].xyz;
+    let cofactor = mat3x3<f32>(cross(c1, c2), cross(c2, c0), cross(c0, c1));
+    let det = dot(c0, cofactor[0]);
+    if (det == 0.0) {
+        return mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    }
+    return cofactor * (1.0 / det);
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.mat_1,
+        instance.mat_2,
+        instance.mat_3,
+        instance.mat_4
+    );
+    let normal_matrix = normal_matrix_of(model_matrix);
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    out.world_position = world_position.xyz;
+    out.world_normal = normalize(normal_matrix * model.normal);
+    out.world_tangent = safe_normalize(normal_matrix * model.tangent);
+    out.world_bitangent = safe_normalize(normal_matrix * model.bitangent);
+    out.color = model.color;
+    out.tex_coords2 = model.tex_coords2;
+    return out;
+}
+
+@group(0) @binding(0)
+var diffuse_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var diffuse_sampler: sampler;
+
+@group(0) @binding(2)
+var normal_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var normal_sampler: sampler;
+
+@group(0) @binding(4)
+var lightmap_texture: texture_2d<f32>;
+
+@group(0) @binding(5)
+var lightmap_sampler: sampler;
+
+struct Material {
+    alpha_cutoff: f32,
+}
+
+@group(0) @binding(6)
+var<uniform> material: Material;
+
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    // the world-space normal
+    @location(1) normal: vec4<f32>,
+    // the world-space position; w is 1 wherever there's geometry, so the lighting pass can skip the background
+    @location(2) position: vec4<f32>,
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> GBufferOutput {
+    // same TBN reconstruction as the forward shader
+    let n_vector = select(-1.0, 1.0, front_facing) * normalize(in.world_normal);
+    var t_vector = safe_normalize(in.world_tangent);
+    var b_vector = safe_normalize(in.world_bitangent);
+    let has_tangents = dot(t_vector, t_vector) > 0.0 && dot(b_vector, b_vector) > 0.0;
+    if (!has_tangents) {
+        let up = select(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), abs(n_vector.y) > 0.99);
+        t_vector = normalize(cross(up, n_vector));
+        b_vector = cross(n_vector, t_vector);
+    }
+
+    let lightmap_color = textureSample(lightmap_texture, lightmap_sampler, in.tex_coords2);
+    let object_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords) * in.color * lightmap_color;
+    let object_normal = textureSample(normal_texture, normal_sampler, in.tex_coords);
+
+    if (object_color.a < material.alpha_cutoff) {
+        discard;
+    }
+
+    // the normal map's normal, taken from tangent space to world space
+    let tangent_normal = select(vec3(0.0, 0.0, 1.0), normalize(object_normal.xyz * 2.0 - 1.0), has_tangents);
+    let world_normal = normalize(mat3x3<f32>(t_vector, b_vector, n_vector) * tangent_normal);
+
+    var out: GBufferOutput;
+    out.albedo = object_color;
+    out.normal = vec4(world_normal, 0.0);
+    out.position = vec4(in.world_position, 1.0);
+    return out;
+}
@@ -0,0 +1,133 @@
+// The lighting pass of deferred rendering; lights each pixel of the G-buffer with every light.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct PointLight {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+
+@group(2) @binding(0)
+var<storage, read> point_lights: array<PointLight>;
+
+@group(2) @binding(1)
+var<uniform> point_light_count: u32;
+
+struct DirectionalLight {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+}
+
+@group(2) @binding(2)
+var<storage, read> directional_lights: array<DirectionalLight>;
+
+@group(2) @binding(3)
+var<uniform> directional_light_count: u32;
+
+struct SpotLight {
+    position: vec3<f32>,
+    inner_cutoff: f32,
+    direction: vec3<f32>,
+    outer_cutoff: f32,
+    color: vec3<f32>,
+}
+
+@group(2) @binding(4)
+var<storage, read> spot_lights: array<SpotLight>;
+
+@group(2) @binding(5)
+var<uniform> spot_light_count: u32;
+
+@group(2) @binding(6)
+var<uniform> ambient_light: vec4<f32>;
+
+@group(0) @binding(0)
+var albedo_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var normal_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var position_texture: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vi: u32,
+) -> VertexOutput {
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(
+        f32((vi << 1u) & 2u),
+        f32(vi & 2u),
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// The diffuse + specular contribution of one light, with all directions in world space
+fn blinn_phong(
+    normal: vec3<f32>,
+    light_dir: vec3<f32>,
+    view_dir: vec3<f32>,
+    light_color: vec3<f32>,
+    object_color: vec3<f32>,
+) -> vec3<f32> {
+    let half_dir = normalize(view_dir + light_dir);
+
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+    let diffuse_color = light_color * diffuse_strength;
+
+    let spec_strength = pow(max(dot(normal, half_dir), 0.0), 64.0);
+    let spec_color = light_color * spec_strength;
+
+    return diffuse_color * object_color + spec_color;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(in.clip_position.xy);
+    let position = textureLoad(position_texture, coords, 0);
+
+    // nothing was drawn here, so leave the background (ie the sky) be
+    if (position.w == 0.0) {
+        discard;
+    }
+    let world_position = position.xyz;
+    let normal = normalize(textureLoad(normal_texture, coords, 0).xyz);
+    let object_color = textureLoad(albedo_texture, coords, 0).rgb;
+    let view_dir = normalize(camera.view_position - world_position);
+
+    var result = ambient_light.rgb * object_color;
+    for (var i = 0u; i < point_light_count; i++) {
+        let light = point_lights[i];
+        let light_dir = normalize(light.position - world_position);
+        result += blinn_phong(normal, light_dir, view_dir, light.color, object_color);
+    }
+
+    for (var i = 0u; i < directional_light_count; i++) {
+        let light = directional_lights[i];
+        result += blinn_phong(normal, normalize(-light.direction), view_dir, light.color, object_color);
+    }
+
+    for (var i = 0u; i < spot_light_count; i++) {
+        let light = spot_lights[i];
+        let light_dir = normalize(light.position - world_position);
+        let theta = dot(light_dir, normalize(-light.direction));
+        let intensity = smoothstep(light.outer_cutoff, light.inner_cutoff, theta);
+        result += intensity * blinn_phong(normal, light_dir, view_dir, light.color, object_color);
+    }
+
+    return vec4<f32>(result, 1.0);
+}
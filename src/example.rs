@@ -1,3 +1,5 @@
+use std::f32::consts::TAU;
+
 use cgmath::InnerSpace;
 use cgmath::Quaternion;
 use cgmath::Rad;
@@ -20,6 +22,8 @@ pub fn generate_one_big_entity(world: &mut World) -> Vec<WorldEntityId> {
 }
 
 /// Just generate some spaced nodes as an example.
+/// 
+/// The nodes are laid out horizontally, according to the world's up axis.
 pub fn generated_spaced_entities(world: &mut World) -> Vec<WorldEntityId> {
     pub const NUM_INSTANCES_PER_ROW: u32 = 10;
     pub const INSTANCE_DISPLACEMENT: Vector3<f32> = Vector3::new(
@@ -33,13 +37,14 @@ pub fn generated_spaced_entities(world: &mut World) -> Vec<WorldEntityId> {
     pub const MAX_VERTICAL_OFFSET: f32 = 0.3;
     const SPACE_BETWEEN: f32 = 3.0;
 
+    let forward = world.up_axis().forward();
     let entities = (0..NUM_INSTANCES_PER_ROW)
         .flat_map(|z| {
             (0..NUM_INSTANCES_PER_ROW).map(|x| {
                 let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
                 let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
 
-                let position = cgmath::Vector3 { x, y: 0.0, z };
+                let position = Vector3::unit_x() * x + forward * z;
                 let rotation = if position.is_zero() {
                     Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
                 } else {
@@ -58,4 +63,56 @@ pub fn generated_spaced_entities(world: &mut World) -> Vec<WorldEntityId> {
         .collect::<Vec<_>>();
 
     entities
-}
\ No newline at end of file
+}
+
+/// Generate entities at pseudo-random positions/rotations, ie for stress-testing and benchmarks.
+///
+/// The positions are within `bounds` (as `(min, max)`), and the same seed always generates the same entities.
+pub fn generate_random_entities(
+    world: &mut World,
+    count: usize,
+    seed: u64,
+    bounds: (Vector3<f32>, Vector3<f32>),
+) -> Vec<WorldEntityId> {
+    let (min, max) = bounds;
+    let mut rng = SplitMix64(seed);
+
+    (0..count)
+        .map(|_| {
+            let position = Vector3::new(
+                min.x + rng.next_f32() * (max.x - min.x),
+                min.y + rng.next_f32() * (max.y - min.y),
+                min.z + rng.next_f32() * (max.z - min.z),
+            );
+            let rotation = Quaternion::from_angle_x(Rad(rng.next_f32() * TAU))
+                * Quaternion::from_angle_y(Rad(rng.next_f32() * TAU))
+                * Quaternion::from_angle_z(Rad(rng.next_f32() * TAU));
+            let transform = SpatialTransform {
+                scale: Vector3::new(1.0, 1.0, 1.0),
+                position,
+                rotation,
+            };
+            world.add_entity(None, vec![], transform)
+        })
+        .collect()
+}
+
+/// A tiny PRNG, so we don't need `rand` just for examples.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Get the next number.
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Get the next number in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        // the top 24 bits fit exactly in an f32's mantissa
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
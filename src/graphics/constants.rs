@@ -1,10 +1,22 @@
 pub const MESH_MATERIAL_BIND_GROUP_SLOT: u32 = 0;
 pub const MESH_CAMERA_BIND_GROUP_SLOT: u32 = 1;
 pub const MESH_LIGHTING_BIND_GROUP_SLOT: u32 = 2;
+/// The light clusters; only bound in clustered forward rendering.
+pub const MESH_CLUSTER_BIND_GROUP_SLOT: u32 = 3;
 
 pub const SKYBOX_CAMERA_BIND_GROUP_SLOT: u32 = 0;
+/// The sky's cubemap, or its sun uniform for procedural skies.
 pub const SKYBOX_CUBEMAP_BIND_GROUP_SLOT: u32 = 1;
 
+pub const GRID_CAMERA_BIND_GROUP_SLOT: u32 = 0;
+pub const GRID_UNIFORM_BIND_GROUP_SLOT: u32 = 1;
+
+pub const REFLECTION_CAMERA_BIND_GROUP_SLOT: u32 = 0;
+/// The reflection texture, for the reflective surface pipeline.
+pub const REFLECTION_TEXTURE_BIND_GROUP_SLOT: u32 = 1;
+
 pub const VERTEX_BUFFER_SLOT: u32 = 0;
 pub const INSTANCE_BUFFER_SLOT: u32 = 1;
+/// The optional secondary instance data (see `SecondaryInstanceData`); only bound for scenes with the stream enabled.
+pub const SECONDARY_INSTANCE_BUFFER_SLOT: u32 = 2;
 pub const INDEX_BUFFER_FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
@@ -3,6 +3,7 @@ pub mod buffer;
 pub mod pipeline;
 pub mod shader;
 pub mod texture;
+pub mod vertex;
 
 /// Abstraction over GPU-related data.
 #[derive(Clone, Debug)]
@@ -24,4 +25,138 @@ impl GpuContext {
     pub fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
+
+    /// The device's limits, ie to check a storage buffer fits before creating it.
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    /// The features enabled on the device, ie to check for timestamp queries before using them.
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+}
+
+/// How to pick the GPU adapter (ie between an integrated + discrete GPU).
+#[derive(Clone, Copy, Debug)]
+pub enum AdapterPreference {
+    /// Let wgpu pick an adapter with the given power preference.
+    Power(wgpu::PowerPreference),
+    /// Use the adapter at this index of `enumerate_adapters`.
+    ///
+    /// Adapters can't be enumerated on the web, so this falls back to the default there.
+    Index(usize),
+}
+
+/// Options for requesting the GPU.
+#[derive(Clone, Copy, Debug)]
+pub struct GpuRequest {
+    pub adapter_preference: AdapterPreference,
+}
+
+impl GpuRequest {
+    /// Request the adapter wgpu picks by default.
+    pub fn new() -> Self {
+        Self {
+            adapter_preference: AdapterPreference::Power(wgpu::PowerPreference::default()),
+        }
+    }
+}
+
+impl Default for GpuRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The backends the engine renders with.
+pub fn backends() -> wgpu::Backends {
+    if cfg!(target_arch = "wasm32") {
+        wgpu::Backends::GL
+    } else {
+        wgpu::Backends::PRIMARY
+    }
+}
+
+/// Create a wgpu instance for the engine's backends.
+pub fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: backends(),
+        ..Default::default()
+    })
+}
+
+/// Get info (ie name, backend, device type) for each adapter available to the engine, ie to pick one for
+/// `AdapterPreference::Index`.
+///
+/// This is always empty on the web, since adapters can't be enumerated there.
+pub fn enumerate_adapters() -> Vec<wgpu::AdapterInfo> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        create_instance()
+            .enumerate_adapters(backends())
+            .iter()
+            .map(wgpu::Adapter::get_info)
+            .collect()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        Vec::new()
+    }
+}
+
+/// Request an adapter compatible with the surface, according to the preference.
+///
+/// Fails if there's no compatible adapter, or if the preferred index is out of range or incompatible.
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    preference: AdapterPreference,
+) -> anyhow::Result<wgpu::Adapter> {
+    let power_preference = match preference {
+        AdapterPreference::Power(power_preference) => power_preference,
+        #[cfg(not(target_arch = "wasm32"))]
+        AdapterPreference::Index(index) => {
+            let adapter = instance
+                .enumerate_adapters(backends())
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| anyhow::anyhow!("There's no adapter at index {index}"))?;
+            anyhow::ensure!(
+                adapter.is_surface_supported(surface),
+                "The adapter at index {index} ({}) can't render to the surface",
+                adapter.get_info().name
+            );
+            return Ok(adapter);
+        }
+        #[cfg(target_arch = "wasm32")]
+        AdapterPreference::Index(_) => wgpu::PowerPreference::default(),
+    };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        })
+        .await?;
+    Ok(adapter)
+}
+
+/// Create a GPU context without a surface, on any adapter with any backend, for tests that need a device.
+///
+/// Returns `None` if there's no adapter (ie on CI without a software renderer), so those tests can be skipped.
+#[cfg(test)]
+pub(crate) fn test_context() -> Option<GpuContext> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: Some("test_device"),
+        required_limits: adapter.limits(),
+        ..Default::default()
+    }))
+    .ok()?;
+    Some(GpuContext::new(device, queue))
 }
@@ -4,57 +4,188 @@ use crate::graphics::gpu::GpuContext;
 #[derive(Clone, Debug)]
 pub struct GpuPipeline {
     pipeline: wgpu::RenderPipeline,
+    /// What the pipeline was created from, to recreate it for another color format; `None` if it was created with `new`.
+    source: Option<PipelineSource>,
+}
+
+/// An owned copy of everything a pipeline was created from.
+#[derive(Clone, Debug)]
+struct PipelineSource {
+    label: String,
+    layout: wgpu::PipelineLayout,
+    vertex_buffers: Vec<(wgpu::BufferAddress, wgpu::VertexStepMode, Vec<wgpu::VertexAttribute>)>,
+    vertex_shader: wgpu::ShaderModule,
+    fragment_shader: wgpu::ShaderModule,
+    vertex_entry_point: Option<String>,
+    fragment_entry_point: Option<String>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    primitive_topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+    color_targets: Vec<wgpu::ColorTargetState>,
+}
+
+/// The optional parts of a render pipeline, for `GpuPipeline::create`.
+///
+/// The default has the shaders' only entry points, no depth, back-face culled triangles, and no color targets.
+#[derive(Clone, Debug)]
+pub struct GpuPipelineDescriptor<'a> {
+    /// The vertex shader's entry point, for shaders with multiple `@vertex` functions.
+    pub vertex_entry_point: Option<&'a str>,
+    /// The fragment shader's entry point, for shaders with multiple `@fragment` functions.
+    pub fragment_entry_point: Option<&'a str>,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub primitive_topology: wgpu::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::Face>,
+    /// The targets the fragment shader writes to, one `@location` per target in order (ie for G-buffers).
+    pub color_targets: Vec<wgpu::ColorTargetState>,
+}
+
+impl Default for GpuPipelineDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            vertex_entry_point: None,
+            fragment_entry_point: None,
+            depth_stencil: None,
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            color_targets: Vec::new(),
+        }
+    }
 }
 
 impl GpuPipeline {
-    /// Creates a render pipeline with mostly default configs.
-    pub fn create_default(
+    /// Create a pipeline from an existing one.
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self { pipeline, source: None }
+    }
+
+    /// Creates a render pipeline from its shaders + layouts, and the rest of its configs.
+    pub fn create(
         label: &str,
         gpu: &GpuContext,
         bind_group_layouts: &[&wgpu::BindGroupLayout],
         vertex_buffer_layouts: &[wgpu::VertexBufferLayout],
         vertex_shader: &wgpu::ShaderModule,
         fragment_shader: &wgpu::ShaderModule,
-        depth_stencil: Option<wgpu::DepthStencilState>,
-        primitive_topology: wgpu::PrimitiveTopology,
-        color_format: wgpu::TextureFormat
+        descriptor: GpuPipelineDescriptor,
     ) -> Self {
-        let device = gpu.device();
-
-        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        if cfg!(debug_assertions) {
+            Self::check_vertex_locations(label, vertex_buffer_layouts);
+        }
+        let layout = gpu.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some(&format!("{label}_layout")),
             bind_group_layouts,
             push_constant_ranges: &[],
         });
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(label),
-            layout: Some(&layout),
+        let source = PipelineSource {
+            label: label.to_string(),
+            layout,
+            vertex_buffers: vertex_buffer_layouts
+                .iter()
+                .map(|layout| (layout.array_stride, layout.step_mode, layout.attributes.to_vec()))
+                .collect(),
+            vertex_shader: vertex_shader.clone(),
+            fragment_shader: fragment_shader.clone(),
+            vertex_entry_point: descriptor.vertex_entry_point.map(str::to_string),
+            fragment_entry_point: descriptor.fragment_entry_point.map(str::to_string),
+            depth_stencil: descriptor.depth_stencil,
+            primitive_topology: descriptor.primitive_topology,
+            cull_mode: descriptor.cull_mode,
+            color_targets: descriptor.color_targets,
+        };
+
+        Self { pipeline: source.build(gpu), source: Some(source) }
+    }
+
+    /// Recreate the pipeline with its color targets of format `from` switched to `to`, ie after the renderer's target format changed.
+    ///
+    /// Returns `None` if none of its targets are `from`, or it was created with `new` and so can't be recreated.
+    pub fn with_color_format(&self, gpu: &GpuContext, from: wgpu::TextureFormat, to: wgpu::TextureFormat) -> Option<Self> {
+        let mut source = self.source.clone()?;
+        let mut changed = false;
+        for target in source.color_targets.iter_mut().filter(|target| target.format == from) {
+            target.format = to;
+            changed = true;
+        }
+        changed.then(|| Self { pipeline: source.build(gpu), source: Some(source) })
+    }
+
+    /// Get the default color target for the given format, ie with blending replacing the existing color.
+    pub fn color_target(format: wgpu::TextureFormat) -> wgpu::ColorTargetState {
+        wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        }
+    }
+
+    /// Get the actual pipeline.
+    pub fn handle(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+
+    /// Panic if any shader location is used by more than one vertex attribute across the buffers,
+    /// since overlapping attributes (ie from widening the instance data) silently read garbage.
+    fn check_vertex_locations(label: &str, vertex_buffer_layouts: &[wgpu::VertexBufferLayout]) {
+        let mut used: Vec<(u32, usize)> = Vec::new();
+        for (buffer, layout) in vertex_buffer_layouts.iter().enumerate() {
+            for attribute in layout.attributes {
+                if let Some((_, other_buffer)) = used.iter().find(|(location, _)| *location == attribute.shader_location) {
+                    panic!(
+                        "Pipeline {label} has vertex buffers {other_buffer} and {buffer} both using shader location {}",
+                        attribute.shader_location
+                    );
+                }
+                used.push((attribute.shader_location, buffer));
+            }
+        }
+    }
+}
+
+impl PipelineSource {
+    /// Create the pipeline.
+    fn build(&self, gpu: &GpuContext) -> wgpu::RenderPipeline {
+        let buffers = self
+            .vertex_buffers
+            .iter()
+            .map(|(array_stride, step_mode, attributes)| wgpu::VertexBufferLayout {
+                array_stride: *array_stride,
+                step_mode: *step_mode,
+                attributes,
+            })
+            .collect::<Vec<_>>();
+        let targets = self
+            .color_targets
+            .iter()
+            .cloned()
+            .map(Some)
+            .collect::<Vec<_>>();
+
+        gpu.device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&self.label),
+            layout: Some(&self.layout),
             vertex: wgpu::VertexState {
-                module: vertex_shader,
-                entry_point: None, 
-                buffers: vertex_buffer_layouts,
+                module: &self.vertex_shader,
+                entry_point: self.vertex_entry_point.as_deref(),
+                buffers: &buffers,
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             primitive: wgpu::PrimitiveState {
-                topology: primitive_topology,
+                topology: self.primitive_topology,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                cull_mode: self.cull_mode,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
             },
             fragment: Some(wgpu::FragmentState {
-                module: fragment_shader,
-                entry_point: None, 
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: color_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                module: &self.fragment_shader,
+                entry_point: self.fragment_entry_point.as_deref(),
+                targets: &targets,
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
-            depth_stencil,
+            depth_stencil: self.depth_stencil.clone(),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -62,13 +193,86 @@ impl GpuPipeline {
             },
             multiview: None,
             cache: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::gpu::test_context;
+    use super::*;
+
+    const SHADER: &str = "
+        @vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(f32(i) - 1.0, f32(i & 1u), 0.0, 1.0);
+        }
+        @fragment fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    /// Draw with the pipeline into a 1x1 texture of the given format, and get any validation error.
+    fn draw_into(gpu: &GpuContext, pipeline: &GpuPipeline, format: wgpu::TextureFormat) -> Option<wgpu::Error> {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("test_target"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         });
+        let view = texture.create_view(&Default::default());
 
-        Self { pipeline }
+        gpu.device().push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut encoder = gpu.device().create_command_encoder(&Default::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("test_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                    depth_slice: None,
+                })],
+                ..Default::default()
+            });
+            render_pass.set_pipeline(pipeline.handle());
+            render_pass.draw(0..3, 0..1);
+        }
+        gpu.queue().submit([encoder.finish()]);
+        pollster::block_on(gpu.device().pop_error_scope())
     }
 
-    /// Get the actual pipeline.
-    pub fn handle(&self) -> &wgpu::RenderPipeline {
-        &self.pipeline
+    #[test]
+    fn recreating_for_a_new_color_format_renders_into_it() {
+        let Some(gpu) = test_context() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let shader = gpu.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("test_shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let (hdr, surface) = (wgpu::TextureFormat::Rgba16Float, wgpu::TextureFormat::Rgba8Unorm);
+        let pipeline = GpuPipeline::create(
+            "test_pipeline",
+            &gpu,
+            &[],
+            &[],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                cull_mode: None,
+                color_targets: vec![GpuPipeline::color_target(hdr)],
+                ..Default::default()
+            },
+        );
+        assert!(pipeline.with_color_format(&gpu, surface, hdr).is_none(), "no target is the surface's format");
+
+        let recreated = pipeline.with_color_format(&gpu, hdr, surface).unwrap();
+        assert!(draw_into(&gpu, &pipeline, surface).is_some(), "the old pipeline shouldn't match the new format");
+        assert!(draw_into(&gpu, &recreated, surface).is_none());
     }
 }
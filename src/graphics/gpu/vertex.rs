@@ -0,0 +1,7 @@
+/// Data laid out in a vertex buffer, ie per vertex or per instance.
+///
+/// This ties a CPU-side struct to its buffer layout, so generic code can take any `V: Vertex`.
+pub trait Vertex: bytemuck::Pod {
+    /// Get the vertex buffer layout.
+    fn desc() -> wgpu::VertexBufferLayout<'static>;
+}
@@ -1,8 +1,11 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
 use slotmap::{SlotMap, new_key_type};
 
 use crate::graphics::{
-    gpu::texture::GpuTexture,
-    render::renderable::model::{Material, Mesh},
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, texture::GpuTexture},
+    render::{renderable::model::{Material, Mesh, MeshData}, renderer::BindGroupId},
+    textures::standard::{ColorSpace, StandardTexture},
 };
 
 new_key_type! {
@@ -15,6 +18,13 @@ pub struct AssetStore {
     meshes: SlotMap<MeshId, Mesh>,
     materials: SlotMap<MaterialId, Material>,
     sprite_textures: SlotMap<SpriteTextureId, GpuTexture>,
+    pending_textures: Vec<PendingMaterialTextures>,
+}
+
+/// A material's diffuse + normal images, being decoded to replace its placeholder textures.
+struct PendingMaterialTextures {
+    material: MaterialId,
+    receiver: Receiver<anyhow::Result<[image::DynamicImage; 2]>>,
 }
 
 impl AssetStore {
@@ -24,6 +34,7 @@ impl AssetStore {
             meshes: SlotMap::with_key(),
             materials: SlotMap::with_key(),
             sprite_textures: SlotMap::with_key(),
+            pending_textures: Vec::new(),
         }
     }
 
@@ -40,6 +51,48 @@ impl AssetStore {
         meshes.into_iter().map(|m| self.meshes.insert(m)).collect()
     }
 
+    /// Add meshes to the store, packing their vertices + indices into one shared vertex + index buffer.
+    ///
+    /// This is an opt-in alternative to `add_meshes` for many small meshes, since drawing them then doesn't need
+    /// separate buffers; each mesh's draw uses its offsets into the shared buffers instead.
+    pub fn add_meshes_packed(&mut self, label: &str, gpu: &GpuContext, meshes: Vec<MeshData>) -> Vec<MeshId> {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut offsets = Vec::with_capacity(meshes.len());
+        for mesh in &meshes {
+            // indices stay relative to the mesh; the draw's base vertex offsets them
+            offsets.push((vertices.len() as i32, indices.len() as u32));
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+        let vertex_buffer = GpuBuffer::create_vertex(
+            &format!("{label}_packed_vertex_buffer"),
+            gpu,
+            bytemuck::cast_slice(&vertices),
+        );
+        let index_buffer = GpuBuffer::create_index(
+            &format!("{label}_packed_index_buffer"),
+            gpu,
+            bytemuck::cast_slice(&indices),
+        );
+
+        let meshes = meshes
+            .into_iter()
+            .zip(offsets)
+            .map(|(mesh, (base_vertex, first_index))| Mesh {
+                // meshes without indices are drawn straight from their vertices
+                index_buffer: (!mesh.indices.is_empty()).then(|| index_buffer.clone()),
+                num_elements: mesh.element_count(),
+                name: mesh.name,
+                vertex_buffer: vertex_buffer.clone(),
+                material: mesh.material,
+                base_vertex,
+                first_index,
+            })
+            .collect();
+        self.add_meshes(meshes)
+    }
+
     /// Add meshes to the store.
     pub fn add_sprite_textures(&mut self, meshes: Vec<GpuTexture>) -> Vec<SpriteTextureId> {
         meshes
@@ -48,6 +101,65 @@ impl AssetStore {
             .collect()
     }
 
+    /// Start decoding the material's diffuse + normal textures from the given (encoded) image bytes,
+    /// ie so it can be used with placeholder textures until they're ready.
+    ///
+    /// The images are decoded on another thread (or immediately on the web, which has none);
+    /// the textures are only created + swapped in by `poll_pending`.
+    pub fn load_material_textures(&mut self, material: MaterialId, diffuse_bytes: Vec<u8>, normal_bytes: Vec<u8>) {
+        let (sender, receiver) = mpsc::channel();
+        let decode = move || {
+            let images = image::load_from_memory(&diffuse_bytes)
+                .and_then(|diffuse| Ok([diffuse, image::load_from_memory(&normal_bytes)?]))
+                .map_err(anyhow::Error::from);
+            // the store may have been dropped, in which case nobody's waiting for it
+            let _ = sender.send(images);
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(decode);
+        #[cfg(target_arch = "wasm32")]
+        decode();
+
+        self.pending_textures.push(PendingMaterialTextures { material, receiver });
+    }
+
+    /// Finish the texture loads that have been decoded, returning each material's new bind group with the ID to
+    /// replace (ie via `Renderer::replace_bind_group`); `Renderer::poll_pending_assets` does both.
+    ///
+    /// This should be called each frame while there are pending loads. If a load fails (or its material was removed),
+    /// it's dropped and the material keeps its placeholder textures.
+    pub fn poll_pending(&mut self, gpu: &GpuContext) -> Vec<(BindGroupId, GpuBindGroup)> {
+        let mut bind_groups = Vec::new();
+        let materials = &mut self.materials;
+        self.pending_textures.retain(|pending| {
+            let [diffuse, normal] = match pending.receiver.try_recv() {
+                Ok(Ok(images)) => images,
+                Ok(Err(err)) => {
+                    log::error!("Unable to decode the textures for material {:?}: {err}", pending.material);
+                    return false;
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            };
+            let Some(material) = materials.get_mut(pending.material) else {
+                return false;
+            };
+            let textures = StandardTexture::from_image(gpu, &diffuse, ColorSpace::Srgb, Some(&material.name))
+                .and_then(|diffuse| Ok((diffuse, StandardTexture::from_image(gpu, &normal, ColorSpace::Linear, Some(&material.name))?)));
+            match textures {
+                Ok((diffuse, normal)) => bind_groups.push((material.bind_group, material.rebuild_bind_group(gpu, diffuse, normal))),
+                Err(err) => log::error!("Unable to create the textures for material {}: {err}", material.name),
+            }
+            false
+        });
+        bind_groups
+    }
+
+    /// Whether any textures are still being loaded by `load_material_textures`.
+    pub fn has_pending(&self) -> bool {
+        !self.pending_textures.is_empty()
+    }
+
     /// Get a material.
     pub fn material(&self, id: MaterialId) -> Option<&Material> {
         self.materials.get(id)
@@ -0,0 +1,331 @@
+use cgmath::{SquareMatrix, Vector4};
+use crate::{graphics::{
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, pipeline::{GpuPipeline, GpuPipelineDescriptor}, vertex::Vertex},
+    render::renderable::model::ModelVertex,
+    scene::{instance_buffer::MeshInstanceData, light::Lighting},
+    textures::{depth::DepthTexture, standard::DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES},
+}, systems::camera::{CAMERA_BIND_GROUP_LAYOUT_ENTRIES, Camera}};
+
+/// The most point lights a single cluster can hold; any more overlapping it are dropped.
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+
+/// The size of the assignment shader's workgroups along each axis.
+const WORKGROUP_SIZE: u32 = 4;
+
+/// Configures clustered forward rendering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterConfig {
+    /// The number of clusters along the screen's width and height, and along the view depth.
+    pub dimensions: [u32; 3],
+    /// How far point lights reach.
+    ///
+    /// Point lights are otherwise unattenuated, so they need a range to be culled by; in clustered rendering,
+    /// they fade out towards it and don't light anything beyond it.
+    pub light_range: f32,
+}
+
+impl ClusterConfig {
+    /// Create a config with 16x9 screen tiles, 24 depth slices, and the given light range.
+    pub fn new(light_range: f32) -> Self {
+        Self {
+            dimensions: [16, 9, 24],
+            light_range,
+        }
+    }
+
+    /// Get the config with at least one cluster along each axis.
+    fn clamped(self) -> Self {
+        Self {
+            dimensions: self.dimensions.map(|d| d.max(1)),
+            ..self
+        }
+    }
+
+    /// Get the total number of clusters.
+    fn cluster_count(&self) -> u32 {
+        self.dimensions.iter().product()
+    }
+}
+
+/// The cluster grid and pipelines for clustered forward rendering.
+///
+/// Each frame, a compute pass assigns the scene's point lights to the clusters (screen tiles split into depth slices)
+/// whose bounds they reach, then meshes are drawn with pipelines that only iterate their fragment's cluster's lights.
+/// Directional + spot lights are still applied to every fragment.
+pub struct ClusteredLighting {
+    config: ClusterConfig,
+    params_buffer: GpuBuffer,
+    cluster_buffer: GpuBuffer,
+    assign_bind_group: GpuBindGroup,
+    shading_bind_group: GpuBindGroup,
+    assign_pipeline: wgpu::ComputePipeline,
+    mesh_pipeline: GpuPipeline,
+    double_sided_mesh_pipeline: GpuPipeline,
+}
+
+impl ClusteredLighting {
+    /// Create the cluster grid + pipelines, with meshes rendering to the given format.
+    pub fn new(gpu: &GpuContext, config: ClusterConfig, output_format: wgpu::TextureFormat) -> Self {
+        let config = config.clamped();
+        let params_buffer = GpuBuffer::create_uniform(
+            "Clustered::params_buffer",
+            gpu,
+            bytemuck::cast_slice(&[ClusterParams::zeroed()])
+        );
+        let cluster_buffer = Self::create_cluster_buffer(gpu, &config);
+        let assign_bind_group = Self::create_bind_group(gpu, "Clustered::assign_bind_group", &params_buffer, &cluster_buffer, false);
+        let shading_bind_group = Self::create_bind_group(gpu, "Clustered::shading_bind_group", &params_buffer, &cluster_buffer, true);
+        let assign_pipeline = Self::create_assign_pipeline(gpu, &assign_bind_group);
+        Self {
+            config,
+            mesh_pipeline: Self::create_mesh_pipeline(gpu, &shading_bind_group, Some(wgpu::Face::Back), output_format),
+            double_sided_mesh_pipeline: Self::create_mesh_pipeline(gpu, &shading_bind_group, None, output_format),
+            params_buffer,
+            cluster_buffer,
+            assign_bind_group,
+            shading_bind_group,
+            assign_pipeline,
+        }
+    }
+
+    /// Get the config.
+    pub fn config(&self) -> ClusterConfig {
+        self.config
+    }
+
+    /// Set the config; the cluster buffer is recreated if the number of clusters changed.
+    pub fn set_config(&mut self, gpu: &GpuContext, config: ClusterConfig) {
+        let config = config.clamped();
+        if config.cluster_count() != self.config.cluster_count() {
+            self.cluster_buffer = Self::create_cluster_buffer(gpu, &config);
+            self.assign_bind_group = Self::create_bind_group(gpu, "Clustered::assign_bind_group", &self.params_buffer, &self.cluster_buffer, false);
+            self.shading_bind_group = Self::create_bind_group(gpu, "Clustered::shading_bind_group", &self.params_buffer, &self.cluster_buffer, true);
+        }
+        self.config = config;
+    }
+
+    /// Set the format meshes render to, ie when toggling HDR.
+    pub fn set_output_format(&mut self, gpu: &GpuContext, output_format: wgpu::TextureFormat) {
+        self.mesh_pipeline = Self::create_mesh_pipeline(gpu, &self.shading_bind_group, Some(wgpu::Face::Back), output_format);
+        self.double_sided_mesh_pipeline = Self::create_mesh_pipeline(gpu, &self.shading_bind_group, None, output_format);
+    }
+
+    /// Get the pipeline for drawing a mesh with clustered lighting.
+    pub fn mesh_pipeline(&self, double_sided: bool) -> &GpuPipeline {
+        match double_sided {
+            true => &self.double_sided_mesh_pipeline,
+            false => &self.mesh_pipeline,
+        }
+    }
+
+    /// Get the bind group the mesh pipelines read the clusters from.
+    pub fn shading_bind_group(&self) -> &GpuBindGroup {
+        &self.shading_bind_group
+    }
+
+    /// Assign the point lights to clusters for the given camera + screen size.
+    ///
+    /// This must be encoded before the meshes are drawn, and the camera + lighting should already be written for the frame.
+    pub fn assign_lights(
+        &self,
+        gpu: &GpuContext,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+        screen_size: (u32, u32),
+        camera_bind_group: &GpuBindGroup,
+        lighting_bind_group: &GpuBindGroup,
+    ) {
+        let params = ClusterParams::new(&self.config, camera, screen_size);
+        gpu.queue().write_buffer(self.params_buffer.handle(), 0, bytemuck::cast_slice(&[params]));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Clustered::assign_pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.assign_pipeline);
+        pass.set_bind_group(0, camera_bind_group.handle(), &[]);
+        pass.set_bind_group(1, lighting_bind_group.handle(), &[]);
+        pass.set_bind_group(2, self.assign_bind_group.handle(), &[]);
+        let [x, y, z] = self.config.dimensions.map(|d| d.div_ceil(WORKGROUP_SIZE));
+        pass.dispatch_workgroups(x, y, z);
+    }
+
+    /// Create the buffer holding each cluster's light count + light indices.
+    fn create_cluster_buffer(gpu: &GpuContext, config: &ClusterConfig) -> GpuBuffer {
+        let size = config.cluster_count() as u64 * (MAX_LIGHTS_PER_CLUSTER as u64 + 1) * size_of::<u32>() as u64;
+        GpuBuffer::create_storage_uninit("Clustered::cluster_buffer", gpu, size)
+    }
+
+    /// Create the bind group for the params + clusters; the assignment pass writes the clusters, and the mesh pipelines read them.
+    fn create_bind_group(
+        gpu: &GpuContext,
+        label: &str,
+        params_buffer: &GpuBuffer,
+        cluster_buffer: &GpuBuffer,
+        read_only: bool,
+    ) -> GpuBindGroup {
+        let visibility = match read_only {
+            true => wgpu::ShaderStages::FRAGMENT,
+            false => wgpu::ShaderStages::COMPUTE,
+        };
+        GpuBindGroup::create_default(
+            label,
+            gpu,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.handle().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cluster_buffer.handle().as_entire_binding(),
+                },
+            ],
+        )
+    }
+
+    /// Create the light assignment pipeline.
+    fn create_assign_pipeline(gpu: &GpuContext, assign_bind_group: &GpuBindGroup) -> wgpu::ComputePipeline {
+        let device = gpu.device();
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clustered::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clustered::lighting_layout"),
+            entries: &Lighting::bind_group_layout_entries(),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Clustered::assign_pipeline_layout"),
+            bind_group_layouts: &[&camera_layout, &lighting_layout, assign_bind_group.layout()],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../cluster_assign.wgsl"));
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Clustered::assign_pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("assign_lights"),
+            cache: None,
+            compilation_options: Default::default(),
+        })
+    }
+
+    /// Create a mesh pipeline with the given cull mode.
+    ///
+    /// This takes the same bind groups as the forward mesh pipelines, plus the clusters after them.
+    fn create_mesh_pipeline(
+        gpu: &GpuContext,
+        shading_bind_group: &GpuBindGroup,
+        cull_mode: Option<wgpu::Face>,
+        output_format: wgpu::TextureFormat,
+    ) -> GpuPipeline {
+        let device = gpu.device();
+        let material_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clustered::material_layout"),
+            entries: &DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clustered::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clustered::lighting_layout"),
+            entries: &Lighting::bind_group_layout_entries(),
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../clustered.wgsl"));
+        GpuPipeline::create(
+            "Clustered::mesh_pipeline",
+            gpu,
+            &[&material_layout, &camera_layout, &lighting_layout, shading_bind_group.layout()],
+            &[ModelVertex::desc(), MeshInstanceData::desc()],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                cull_mode,
+                color_targets: vec![GpuPipeline::color_target(output_format)],
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/// The cluster grid's layout, for both assigning lights and finding a fragment's cluster.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+struct ClusterParams {
+    dimensions: [u32; 3],
+    max_lights_per_cluster: u32,
+    screen_size: [f32; 2],
+    near: f32,
+    far: f32,
+    light_range: f32,
+    _padding: [u32; 3],
+}
+
+impl ClusterParams {
+    /// Create the params for the camera's current projection.
+    ///
+    /// The near + far depths are where the visible depth range (0 to 1 in NDC) ends up in view space.
+    fn new(config: &ClusterConfig, camera: &Camera, screen_size: (u32, u32)) -> Self {
+        let inverse_projection = camera
+            .projection_matrix()
+            .invert()
+            .expect("Camera's projection should be invertible");
+        let view_depth = |ndc_z: f32| {
+            let point = inverse_projection * Vector4::new(0.0, 0.0, ndc_z, 1.0);
+            -point.z / point.w
+        };
+        Self {
+            dimensions: config.dimensions,
+            max_lights_per_cluster: MAX_LIGHTS_PER_CLUSTER,
+            screen_size: [screen_size.0 as f32, screen_size.1 as f32],
+            near: view_depth(0.0),
+            far: view_depth(1.0),
+            light_range: config.light_range,
+            _padding: [0; 3],
+        }
+    }
+
+    /// Create params with everything zeroed, ie before the first frame.
+    fn zeroed() -> Self {
+        Self {
+            dimensions: [0; 3],
+            max_lights_per_cluster: 0,
+            screen_size: [0.0; 2],
+            near: 0.0,
+            far: 0.0,
+            light_range: 0.0,
+            _padding: [0; 3],
+        }
+    }
+}
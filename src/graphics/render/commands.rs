@@ -19,12 +19,32 @@ pub struct MeshRenderCommand<'obj> {
     pub name: &'obj str,
     pub mesh: MeshId,
     pub pipeline: PipelineId,
+    /// Whether the mesh's material is double-sided, ie for picking an unculled pipeline in deferred rendering.
+    pub double_sided: bool,
     pub camera_bind_group: BindGroupId,
     pub lighting_bind_group: BindGroupId,
     pub material_bind_group: BindGroupId,
     pub vertex_buffer: wgpu::BufferSlice<'obj>,
     pub instance_buffer_range: InstanceBufferRange,
-    pub index_buffer: wgpu::BufferSlice<'obj>,
+    /// The range of the instances' secondary data, parallel to `instance_buffer_range`; `None` unless the scene gathers it.
+    pub secondary_instance_buffer_range: Option<InstanceBufferRange>,
+    /// The index buffer; only `None` for non-indexed meshes, which use `DrawCommand::NonIndexed`.
+    pub index_buffer: Option<wgpu::BufferSlice<'obj>>,
+    pub draw: DrawCommand,
+}
+
+/// A command describing how to render arbitrary geometry (ie lines, points, procedural stuff).
+///
+/// This skips the mesh path entirely, so the pipeline, bind groups and buffers are all up to the user.
+pub struct RawRenderCommand<'obj> {
+    pub name: &'obj str,
+    pub pipeline: PipelineId,
+    /// The bind groups, paired with the slot they are set at.
+    pub bind_groups: Vec<(u32, BindGroupId)>,
+    /// The vertex buffers, paired with the slot they are set at.
+    pub vertex_buffers: Vec<(u32, wgpu::BufferSlice<'obj>)>,
+    /// The index buffer; only required for `DrawCommand::Indexed`.
+    pub index_buffer: Option<wgpu::BufferSlice<'obj>>,
     pub draw: DrawCommand,
 }
 
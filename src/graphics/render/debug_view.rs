@@ -0,0 +1,131 @@
+use crate::graphics::{
+    gpu::{GpuContext, pipeline::{GpuPipeline, GpuPipelineDescriptor}, vertex::Vertex},
+    render::renderable::model::ModelVertex,
+    scene::{instance_buffer::MeshInstanceData, light::Lighting},
+    textures::{depth::DepthTexture, standard::DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES},
+};
+use crate::systems::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+
+/// A debug view of the scene's meshes, drawn instead of their own shading; see `Renderer::set_debug_view`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    /// World-space normals as RGB.
+    Normals,
+    /// UVs as red + green.
+    Uvs,
+    /// Linear view distance as grayscale, white at 100 units.
+    Depth,
+    /// How many times each pixel is drawn, as additive brightness.
+    Overdraw,
+}
+
+impl DebugView {
+    /// Get the view's fragment entry point in `debug_view.wgsl`.
+    fn fragment_entry_point(&self) -> &'static str {
+        match self {
+            DebugView::Normals => "fs_normals",
+            DebugView::Uvs => "fs_uvs",
+            DebugView::Depth => "fs_depth",
+            DebugView::Overdraw => "fs_overdraw",
+        }
+    }
+}
+
+/// The pipelines for each `DebugView`.
+///
+/// These take the same vertex buffers + bind groups as the mesh pipelines, so mesh commands can be drawn unchanged.
+/// Nothing is culled, so double-sided meshes show from both sides.
+pub struct DebugViewPipelines {
+    normals: GpuPipeline,
+    uvs: GpuPipeline,
+    depth: GpuPipeline,
+    overdraw: GpuPipeline,
+}
+
+impl DebugViewPipelines {
+    /// Create the pipelines, rendering to the given format (ie the renderer's `target_format`).
+    pub fn new(gpu: &GpuContext, color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            normals: Self::create_pipeline(gpu, DebugView::Normals, color_format),
+            uvs: Self::create_pipeline(gpu, DebugView::Uvs, color_format),
+            depth: Self::create_pipeline(gpu, DebugView::Depth, color_format),
+            overdraw: Self::create_pipeline(gpu, DebugView::Overdraw, color_format),
+        }
+    }
+
+    /// Recreate the pipelines to render to a new color format, ie when HDR is toggled.
+    pub fn set_color_format(&mut self, gpu: &GpuContext, color_format: wgpu::TextureFormat) {
+        *self = Self::new(gpu, color_format);
+    }
+
+    /// Get the pipeline for the view.
+    pub fn pipeline(&self, view: DebugView) -> &GpuPipeline {
+        match view {
+            DebugView::Normals => &self.normals,
+            DebugView::Uvs => &self.uvs,
+            DebugView::Depth => &self.depth,
+            DebugView::Overdraw => &self.overdraw,
+        }
+    }
+
+    /// Create the pipeline for a view.
+    fn create_pipeline(gpu: &GpuContext, view: DebugView, color_format: wgpu::TextureFormat) -> GpuPipeline {
+        let device = gpu.device();
+        let material_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DebugView::material_layout"),
+            entries: &DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DebugView::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DebugView::lighting_layout"),
+            entries: &Lighting::bind_group_layout_entries(),
+        });
+
+        // overdraw counts every layer, so it's blended additively without depth testing
+        let (blend, depth_write_enabled, depth_compare) = match view {
+            DebugView::Overdraw => (
+                Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                false,
+                wgpu::CompareFunction::Always,
+            ),
+            _ => (Some(wgpu::BlendState::REPLACE), true, wgpu::CompareFunction::Less),
+        };
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../debug_view.wgsl"));
+        GpuPipeline::create(
+            &format!("DebugView::{view:?}_pipeline"),
+            gpu,
+            &[&material_layout, &camera_layout, &lighting_layout],
+            &[ModelVertex::desc(), MeshInstanceData::desc()],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                fragment_entry_point: Some(view.fragment_entry_point()),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                cull_mode: None,
+                color_targets: vec![wgpu::ColorTargetState {
+                    format: color_format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+                ..Default::default()
+            },
+        )
+    }
+}
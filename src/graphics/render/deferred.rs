@@ -0,0 +1,229 @@
+use crate::{graphics::{
+    gpu::{GpuContext, bind_group::GpuBindGroup, pipeline::{GpuPipeline, GpuPipelineDescriptor}, vertex::Vertex},
+    render::{fullscreen::FullscreenPass, renderable::model::ModelVertex},
+    scene::{instance_buffer::MeshInstanceData, light::Lighting},
+    textures::{depth::DepthTexture, standard::{DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES, StandardTexture}},
+}, systems::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES};
+
+/// The G-buffer and pipelines for deferred rendering.
+///
+/// The geometry pass writes each pixel's albedo, world normal and world position (with the renderer's depth texture),
+/// then the lighting pass reads them and applies every light in the scene's `Lighting`.
+pub struct DeferredPipeline {
+    albedo: StandardTexture,
+    normal: StandardTexture,
+    position: StandardTexture,
+    gbuffer_bind_group: GpuBindGroup,
+    geometry_pipeline: GpuPipeline,
+    double_sided_geometry_pipeline: GpuPipeline,
+    lighting_pass: FullscreenPass,
+}
+
+impl DeferredPipeline {
+    /// The format of the albedo target; sRGB so that dark colors keep their precision.
+    pub const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    /// The format of the world normal target.
+    pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// The format of the world position target; full floats, since half floats lose precision a few hundred units out.
+    pub const POSITION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+    const GBUFFER_BIND_GROUP_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 3] = [
+        Self::gbuffer_layout_entry(0),
+        Self::gbuffer_layout_entry(1),
+        Self::gbuffer_layout_entry(2),
+    ];
+
+    /// Initialize the G-buffer at the given size, with the lighting pass writing to the given format.
+    pub fn new(gpu: &GpuContext, width: u32, height: u32, output_format: wgpu::TextureFormat) -> Self {
+        let [albedo, normal, position] = Self::create_textures(gpu, width, height);
+        let gbuffer_bind_group = Self::create_gbuffer_bind_group(gpu, &albedo, &normal, &position);
+        let lighting_pass = Self::create_lighting_pass(gpu, &gbuffer_bind_group, output_format);
+        Self {
+            albedo,
+            normal,
+            position,
+            gbuffer_bind_group,
+            geometry_pipeline: Self::create_geometry_pipeline(gpu, Some(wgpu::Face::Back)),
+            double_sided_geometry_pipeline: Self::create_geometry_pipeline(gpu, None),
+            lighting_pass,
+        }
+    }
+
+    /// Resize the G-buffer.
+    pub fn resize(&mut self, gpu: &GpuContext, width: u32, height: u32) {
+        let [albedo, normal, position] = Self::create_textures(gpu, width, height);
+        self.gbuffer_bind_group = Self::create_gbuffer_bind_group(gpu, &albedo, &normal, &position);
+        self.albedo = albedo;
+        self.normal = normal;
+        self.position = position;
+    }
+
+    /// Set the format the lighting pass writes to, ie when toggling HDR.
+    pub fn set_output_format(&mut self, gpu: &GpuContext, output_format: wgpu::TextureFormat) {
+        self.lighting_pass = Self::create_lighting_pass(gpu, &self.gbuffer_bind_group, output_format);
+    }
+
+    /// Get the pipeline for drawing a mesh into the G-buffer.
+    pub fn geometry_pipeline(&self, double_sided: bool) -> &GpuPipeline {
+        match double_sided {
+            true => &self.double_sided_geometry_pipeline,
+            false => &self.geometry_pipeline,
+        }
+    }
+
+    /// Get the G-buffer's color attachments for the geometry pass, cleared so uncovered pixels are skipped when lighting.
+    pub fn color_attachments(&self) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 3] {
+        [&self.albedo, &self.normal, &self.position].map(|texture| {
+            Some(wgpu::RenderPassColorAttachment {
+                view: texture.inner().view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        })
+    }
+
+    /// Light the G-buffer into the output.
+    ///
+    /// Pixels without geometry are left as they are, ie showing the sky drawn before.
+    pub fn light(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        camera_bind_group: &GpuBindGroup,
+        lighting_bind_group: &GpuBindGroup,
+    ) {
+        self.lighting_pass.draw(
+            encoder,
+            output,
+            &[&self.gbuffer_bind_group, camera_bind_group, lighting_bind_group],
+        );
+    }
+
+    /// Get the layout entry for one of the G-buffer's textures; they're read with `textureLoad`, so they needn't be filterable.
+    const fn gbuffer_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }
+    }
+
+    /// Create the albedo, normal and position textures.
+    fn create_textures(gpu: &GpuContext, width: u32, height: u32) -> [StandardTexture; 3] {
+        [
+            (Self::ALBEDO_FORMAT, "Deferred::albedo"),
+            (Self::NORMAL_FORMAT, "Deferred::normal"),
+            (Self::POSITION_FORMAT, "Deferred::position"),
+        ].map(|(format, label)| StandardTexture::new(
+            gpu,
+            width,
+            height,
+            format,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            Some(label)
+        ))
+    }
+
+    /// Create the bind group for reading the G-buffer.
+    fn create_gbuffer_bind_group(
+        gpu: &GpuContext,
+        albedo: &StandardTexture,
+        normal: &StandardTexture,
+        position: &StandardTexture,
+    ) -> GpuBindGroup {
+        let entries = [albedo, normal, position]
+            .iter()
+            .enumerate()
+            .map(|(binding, texture)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::TextureView(texture.inner().view()),
+            })
+            .collect::<Vec<_>>();
+        GpuBindGroup::create_default(
+            "Deferred::gbuffer_bind_group",
+            gpu,
+            &Self::GBUFFER_BIND_GROUP_LAYOUT_ENTRIES,
+            &entries
+        )
+    }
+
+    /// Create a geometry pipeline with the given cull mode.
+    ///
+    /// This takes the same bind groups as the forward mesh pipelines (material, camera, lighting), so mesh commands
+    /// can be drawn unchanged; the lighting group is unused here.
+    fn create_geometry_pipeline(gpu: &GpuContext, cull_mode: Option<wgpu::Face>) -> GpuPipeline {
+        let device = gpu.device();
+        let material_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred::material_layout"),
+            entries: &DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred::lighting_layout"),
+            entries: &Lighting::bind_group_layout_entries(),
+        });
+
+        // the G-buffer isn't blended, and position's full floats can't be anyway
+        let targets = [Self::ALBEDO_FORMAT, Self::NORMAL_FORMAT, Self::POSITION_FORMAT].map(|format| {
+            wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../deferred_geometry.wgsl"));
+        GpuPipeline::create(
+            "Deferred::geometry_pipeline",
+            gpu,
+            &[&material_layout, &camera_layout, &lighting_layout],
+            &[ModelVertex::desc(), MeshInstanceData::desc()],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                cull_mode,
+                color_targets: targets.to_vec(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create the lighting pass, writing to the given format.
+    fn create_lighting_pass(gpu: &GpuContext, gbuffer_bind_group: &GpuBindGroup, output_format: wgpu::TextureFormat) -> FullscreenPass {
+        let device = gpu.device();
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred::lighting_layout"),
+            entries: &Lighting::bind_group_layout_entries(),
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../deferred_lighting.wgsl"));
+        FullscreenPass::new(
+            "Deferred::lighting",
+            gpu,
+            &shader,
+            &[gbuffer_bind_group.layout(), &camera_layout, &lighting_layout],
+            output_format,
+        )
+    }
+}
@@ -0,0 +1,84 @@
+use crate::graphics::gpu::{GpuContext, bind_group::GpuBindGroup, pipeline::{GpuPipeline, GpuPipelineDescriptor}};
+
+/// A pass drawing a single fullscreen triangle into an output, ie for tonemapping + other post-effects.
+///
+/// There are no vertex buffers, so the shader's vertex entry point should generate the triangle
+/// from the vertex index (see `hdr.wgsl`).
+pub struct FullscreenPass {
+    label: String,
+    pipeline: GpuPipeline,
+}
+
+impl FullscreenPass {
+    /// Create the pass' pipeline, taking bind groups with the given layouts (from slot 0).
+    pub fn new(
+        label: &str,
+        gpu: &GpuContext,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self::with_fragment_entry_point(label, gpu, shader, bind_group_layouts, output_format, None)
+    }
+
+    /// Create the pass' pipeline like `new`, using the given fragment entry point of a shader with several.
+    pub fn with_fragment_entry_point(
+        label: &str,
+        gpu: &GpuContext,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        output_format: wgpu::TextureFormat,
+        fragment_entry_point: Option<&str>,
+    ) -> Self {
+        let pipeline = GpuPipeline::create(
+            &format!("{label}::pipeline"),
+            gpu,
+            bind_group_layouts,
+            &[],
+            shader,
+            shader,
+            GpuPipelineDescriptor {
+                fragment_entry_point,
+                color_targets: vec![GpuPipeline::color_target(output_format)],
+                ..Default::default()
+            },
+        );
+        Self {
+            label: label.to_string(),
+            pipeline,
+        }
+    }
+
+    /// Draw the pass into the output, with the given bind groups (from slot 0).
+    ///
+    /// The output's contents are loaded rather than cleared, though the triangle covers all of it.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        bind_groups: &[&GpuBindGroup],
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&format!("{}::render_pass", self.label)),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store
+                    },
+                    depth_slice: None
+                })
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None
+        });
+        pass.set_pipeline(self.pipeline.handle());
+        for (slot, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(slot as u32, bind_group.handle(), &[]);
+        }
+        pass.draw(0..3, 0..1);
+    }
+}
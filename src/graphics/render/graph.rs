@@ -0,0 +1,243 @@
+use thiserror::Error;
+use crate::graphics::{gpu::GpuContext, render::renderer::RenderResult};
+
+/// A resource passes read from + write to, for ordering them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenderResource {
+    /// The color target the scene is rendered to; the HDR texture, or the surface if HDR is disabled.
+    SceneColor,
+    /// The depth texture.
+    SceneDepth,
+    /// The surface, ie the final output.
+    Surface,
+    /// Any other resource, ie a texture owned by a custom pass.
+    Named(&'static str),
+}
+
+/// What's available to a custom pass while it runs.
+pub struct GraphPassContext<'f> {
+    pub gpu: &'f GpuContext,
+    pub encoder: &'f mut wgpu::CommandEncoder,
+    pub scene_color: &'f wgpu::TextureView,
+    pub scene_depth: &'f wgpu::TextureView,
+    pub surface: &'f wgpu::TextureView,
+    /// The format of `scene_color`.
+    pub scene_color_format: wgpu::TextureFormat,
+}
+
+/// A custom pass' callback, which encodes its work.
+pub type CustomPass = Box<dyn FnMut(&mut GraphPassContext<'_>) -> RenderResult<()>>;
+
+/// The passes built into the renderer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BuiltinPass {
+    /// Clears the scene color + depth, and draws the skybox.
+    Skybox,
+    /// Draws the scene's meshes, as per the renderer's `RenderingMode`.
+    Opaque,
+    /// Blends the infinite grid over the scene, if it's enabled.
+    Grid,
+    /// Tonemaps the scene color into the surface, if HDR is enabled.
+    ///
+    /// This runs when the frame ends, so it's the one tonemap for everything drawn in the frame.
+    PostProcess,
+}
+
+/// What a pass runs.
+pub(crate) enum PassKind {
+    Builtin(BuiltinPass),
+    Custom(CustomPass),
+}
+
+/// A pass in the graph.
+pub(crate) struct GraphPass {
+    pub name: String,
+    pub reads: Vec<RenderResource>,
+    pub writes: Vec<RenderResource>,
+    pub kind: PassKind,
+}
+
+/// The passes rendering a scene, ordered by the resources they read + write.
+///
+/// A pass runs after every pass writing a resource it reads. When two passes both read *and* write a resource
+/// (ie both draw over the scene color), they run in the order they were added. Otherwise, passes without
+/// dependencies between them also run in the order they were added.
+///
+/// The renderer starts with the `skybox`, `opaque`, `grid` and `post` passes; see the `*_PASS` constants.
+/// The `post` pass and the custom passes ordered after it run when the frame ends (see `Renderer::end_frame`),
+/// so anything drawn over the scene in the meantime (ie overlays or UI) is post-processed along with it.
+pub struct RenderGraph {
+    passes: Vec<GraphPass>,
+}
+
+impl RenderGraph {
+    /// The pass clearing the scene color + depth and drawing the skybox.
+    pub const SKYBOX_PASS: &'static str = "skybox";
+    /// The pass drawing the scene's meshes.
+    pub const OPAQUE_PASS: &'static str = "opaque";
+    /// The pass drawing the infinite grid.
+    pub const GRID_PASS: &'static str = "grid";
+    /// The pass tonemapping the scene color into the surface, which runs when the frame ends.
+    pub const POST_PROCESS_PASS: &'static str = "post";
+
+    /// Create a graph with the built-in passes.
+    pub(crate) fn new() -> Self {
+        use RenderResource::*;
+        let builtin = |name: &str, reads: Vec<RenderResource>, writes: Vec<RenderResource>, pass: BuiltinPass| GraphPass {
+            name: name.to_string(),
+            reads,
+            writes,
+            kind: PassKind::Builtin(pass),
+        };
+        Self {
+            passes: vec![
+                builtin(Self::SKYBOX_PASS, vec![], vec![SceneColor, SceneDepth], BuiltinPass::Skybox),
+                builtin(Self::OPAQUE_PASS, vec![SceneColor, SceneDepth], vec![SceneColor, SceneDepth], BuiltinPass::Opaque),
+                builtin(Self::GRID_PASS, vec![SceneColor, SceneDepth], vec![SceneColor], BuiltinPass::Grid),
+                builtin(Self::POST_PROCESS_PASS, vec![SceneColor], vec![Surface], BuiltinPass::PostProcess),
+            ],
+        }
+    }
+
+    /// Add a custom pass, which is run each frame the scene is rendered.
+    ///
+    /// Returns an error if there's already a pass with the name.
+    pub fn add_pass(
+        &mut self,
+        name: impl Into<String>,
+        reads: Vec<RenderResource>,
+        writes: Vec<RenderResource>,
+        pass: CustomPass,
+    ) -> Result<(), RenderGraphError> {
+        let name = name.into();
+        if self.passes.iter().any(|p| p.name == name) {
+            return Err(RenderGraphError::DuplicatePass(name));
+        }
+        self.passes.push(GraphPass {
+            name,
+            reads,
+            writes,
+            kind: PassKind::Custom(pass),
+        });
+        Ok(())
+    }
+
+    /// Remove the pass with the given name (including built-in ones), returning whether it existed.
+    pub fn remove_pass(&mut self, name: &str) -> bool {
+        let len = self.passes.len();
+        self.passes.retain(|p| p.name != name);
+        self.passes.len() != len
+    }
+
+    /// Get the pass names in the order they'd run.
+    pub fn pass_order(&self) -> Result<Vec<&str>, RenderGraphError> {
+        Ok(self
+            .execution_order()?
+            .into_iter()
+            .map(|index| self.passes[index].name.as_str())
+            .collect())
+    }
+
+    /// Get the passes mutably, ie for running them.
+    pub(crate) fn passes_mut(&mut self) -> &mut [GraphPass] {
+        &mut self.passes
+    }
+
+    /// Split the passes' execution order into those run with the scene, and those run when the frame ends;
+    /// ie the `post` pass and the custom passes after it.
+    ///
+    /// Built-in passes other than `post` draw the scene, so they're always run with it.
+    pub(crate) fn split_at_post_process(&self, order: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+        let is_post = |index: usize| matches!(self.passes[index].kind, PassKind::Builtin(BuiltinPass::PostProcess));
+        let Some(post) = order.iter().position(|&index| is_post(index)) else {
+            return (order, Vec::new());
+        };
+        let (scene_passes, frame_end_passes): (Vec<_>, Vec<_>) = order
+            .into_iter()
+            .enumerate()
+            .partition(|&(position, index)| {
+                position < post || (!is_post(index) && matches!(self.passes[index].kind, PassKind::Builtin(_)))
+            });
+        let indices = |passes: Vec<(usize, usize)>| passes.into_iter().map(|(_, index)| index).collect();
+        (indices(scene_passes), indices(frame_end_passes))
+    }
+
+    /// Get the indices of the passes in the order they should run.
+    ///
+    /// Returns an error if the passes' dependencies form a cycle.
+    pub(crate) fn execution_order(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let count = self.passes.len();
+        let mut dependents = vec![Vec::new(); count];
+        let mut dependency_counts = vec![0; count];
+        for (reader, reader_pass) in self.passes.iter().enumerate() {
+            for (writer, writer_pass) in self.passes.iter().enumerate() {
+                if reader != writer && Self::depends_on(reader, reader_pass, writer, writer_pass) {
+                    dependents[writer].push(reader);
+                    dependency_counts[reader] += 1;
+                }
+            }
+        }
+
+        // Kahn's algorithm, always taking the earliest added pass that's ready
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&i| !done[i] && dependency_counts[i] == 0) else {
+                let remaining = (0..count)
+                    .filter(|&i| !done[i])
+                    .map(|i| self.passes[i].name.clone())
+                    .collect();
+                return Err(RenderGraphError::Cycle(remaining));
+            };
+            done[next] = true;
+            order.push(next);
+            for &dependent in &dependents[next] {
+                dependency_counts[dependent] -= 1;
+            }
+        }
+        Ok(order)
+    }
+
+    /// Whether the reader has to run after the writer.
+    fn depends_on(reader: usize, reader_pass: &GraphPass, writer: usize, writer_pass: &GraphPass) -> bool {
+        reader_pass.reads.iter().any(|resource| {
+            if !writer_pass.writes.contains(resource) {
+                return false;
+            }
+            // both passes modify the resource, so go by the order they were added
+            let both_modify = reader_pass.writes.contains(resource) && writer_pass.reads.contains(resource);
+            !both_modify || writer < reader
+        })
+    }
+}
+
+/// An error from building the render graph.
+#[derive(Debug, Error)]
+pub enum RenderGraphError {
+    #[error("There's already a render pass named {0}")]
+    DuplicatePass(String),
+    #[error("The render passes {0:?} depend on each other in a cycle")]
+    Cycle(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_pass() -> CustomPass {
+        Box::new(|_| Ok(()))
+    }
+
+    #[test]
+    fn passes_from_post_on_run_when_the_frame_ends() {
+        use RenderResource::*;
+        let mut graph = RenderGraph::new();
+        graph.add_pass("outline", vec![SceneColor], vec![SceneColor], noop_pass()).unwrap();
+        graph.add_pass("crosshair", vec![Surface], vec![Surface], noop_pass()).unwrap();
+
+        let (scene_passes, frame_end_passes) = graph.split_at_post_process(graph.execution_order().unwrap());
+        let names = |passes: Vec<usize>| passes.into_iter().map(|index| graph.passes[index].name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(scene_passes), ["skybox", "opaque", "grid", "outline"]);
+        assert_eq!(names(frame_end_passes), ["post", "crosshair"]);
+    }
+}
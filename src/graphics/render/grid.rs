@@ -0,0 +1,163 @@
+use crate::{graphics::{
+    constants::{GRID_CAMERA_BIND_GROUP_SLOT, GRID_UNIFORM_BIND_GROUP_SLOT},
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, pipeline::GpuPipeline},
+    textures::depth::DepthTexture,
+}, systems::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES};
+
+/// Render pipeline for an infinite grid on the y = 0 plane, ie for orientation in 3D scenes.
+///
+/// The grid is ray-marched in a fullscreen pass and blended over the scene, so it should be drawn after opaque meshes.
+pub struct GridPipeline {
+    pipeline: GpuPipeline,
+    uniform: GridUniform,
+    buffer: GpuBuffer,
+    bind_group: GpuBindGroup,
+    enabled: bool,
+}
+
+impl GridPipeline {
+    /// The distance at which the grid fully fades out.
+    const FADE_DISTANCE: f32 = 100.0;
+
+    /// Initialize the grid pipeline, rendering to the given color format. It is disabled by default.
+    pub fn new(gpu: &GpuContext, color_format: wgpu::TextureFormat) -> Self {
+        let uniform = GridUniform::new(1.0, [0.5, 0.5, 0.5, 1.0], Self::FADE_DISTANCE);
+        let buffer = GpuBuffer::create_uniform("Grid::buffer", gpu, bytemuck::cast_slice(&[uniform]));
+        let bind_group = GpuBindGroup::create_default(
+            "Grid::bind_group",
+            gpu,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.handle().as_entire_binding(),
+                },
+            ],
+        );
+        let pipeline = Self::create_pipeline(gpu, &bind_group, color_format);
+
+        Self {
+            pipeline,
+            uniform,
+            buffer,
+            bind_group,
+            enabled: false,
+        }
+    }
+
+    /// Recreate the pipeline to render to the given color format, ie when the render target changes.
+    pub fn set_color_format(&mut self, gpu: &GpuContext, color_format: wgpu::TextureFormat) {
+        self.pipeline = Self::create_pipeline(gpu, &self.bind_group, color_format);
+    }
+
+    /// Create the render pipeline.
+    fn create_pipeline(gpu: &GpuContext, bind_group: &GpuBindGroup, color_format: wgpu::TextureFormat) -> GpuPipeline {
+        let device = gpu.device();
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+
+        // we need alpha blending + a depth test without depth writes, so the pipeline is built here directly
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../grid.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid::pipeline_layout"),
+            bind_group_layouts: &[&camera_layout, bind_group.layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid::pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: None,
+                buffers: &[], // we generate vertex data directly in the shader
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: None,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        GpuPipeline::new(pipeline)
+    }
+
+    /// Whether the grid is drawn.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Configure the grid, writing its uniform to the GPU.
+    pub fn set(&mut self, gpu: &GpuContext, enabled: bool, spacing: f32, color: [f32; 4]) {
+        self.enabled = enabled;
+        self.uniform = GridUniform::new(spacing, color, Self::FADE_DISTANCE);
+        gpu.queue().write_buffer(
+            self.buffer.handle(),
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    /// Draw the grid to the render pass, using the given camera bind group.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(self.pipeline.handle());
+        render_pass.set_bind_group(GRID_CAMERA_BIND_GROUP_SLOT, camera_bind_group, &[]);
+        render_pass.set_bind_group(GRID_UNIFORM_BIND_GROUP_SLOT, self.bind_group.handle(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// The grid's uniform.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+struct GridUniform {
+    color: [f32; 4],
+    spacing: f32,
+    fade_distance: f32,
+    _padding: [f32; 2],
+}
+
+impl GridUniform {
+    /// Create the uniform.
+    fn new(spacing: f32, color: [f32; 4], fade_distance: f32) -> Self {
+        Self {
+            color,
+            spacing,
+            fade_distance,
+            _padding: [0.0; 2],
+        }
+    }
+}
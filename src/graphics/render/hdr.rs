@@ -1,10 +1,13 @@
-use crate::graphics::{gpu::{GpuContext, bind_group::GpuBindGroup, pipeline::GpuPipeline, texture::GpuTexture}, textures::standard::StandardTexture};
+use crate::graphics::{gpu::{GpuContext, bind_group::GpuBindGroup, texture::GpuTexture}, render::fullscreen::FullscreenPass, textures::standard::StandardTexture};
 
 /// Render pipeline and texture for HDR/tonemapping.
 pub struct HdrPipeline {
-    pipeline: GpuPipeline,
+    pass: FullscreenPass,
     bind_group: GpuBindGroup,
+    lut_bind_group: GpuBindGroup,
     texture: StandardTexture,
+    /// Samples the HDR texture, with the filter used to upscale it to the surface.
+    sampler: wgpu::Sampler,
     width: u32,
     height: u32,
 }
@@ -32,113 +35,251 @@ impl HdrPipeline {
         },
     ];
 
-    /// Initialize the HDR pipeline.
-    pub fn new(gpu: &GpuContext, config: &wgpu::SurfaceConfiguration) -> Self {
-        let width = config.width;
-        let height = config.height;
+    /// The size of the LUT used when none is set, which leaves colors unchanged.
+    const IDENTITY_LUT_SIZE: u32 = 16;
 
-        let texture = StandardTexture::new(
+    /// Initialize the HDR pipeline, with a texture of the given size.
+    ///
+    /// The texture is stretched over the surface, so it can be smaller (or larger) than it; see `set_upscale_filter`.
+    pub fn new(gpu: &GpuContext, width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        let texture = Self::create_texture(gpu, width, height);
+        let sampler = Self::create_sampler(gpu, wgpu::FilterMode::Linear);
+        let bind_group = Self::create_bind_group(gpu, &texture, &sampler);
+
+        let lut_bind_group = Self::create_identity_lut_bind_group(gpu);
+        let pass = Self::create_pass(gpu, &bind_group, &lut_bind_group, format);
+
+        Self {
+            pass,
+            bind_group,
+            lut_bind_group,
+            texture,
+            sampler,
+            width,
+            height,
+        }
+    }
+
+    /// Resize the HDR texture.
+    pub fn resize(&mut self, gpu: &GpuContext, width: u32, height: u32) {
+        self.texture = Self::create_texture(gpu, width, height);
+        self.bind_group = Self::create_bind_group(gpu, &self.texture, &self.sampler);
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Set the filter used when stretching the HDR texture over the surface, ie `Nearest` for pixel art.
+    ///
+    /// The tonemapping pass's layout doesn't change, so it's kept as is.
+    pub fn set_upscale_filter(&mut self, gpu: &GpuContext, filter: wgpu::FilterMode) {
+        self.sampler = Self::create_sampler(gpu, filter);
+        self.bind_group = Self::create_bind_group(gpu, &self.texture, &self.sampler);
+    }
+
+    /// Create the HDR texture.
+    fn create_texture(gpu: &GpuContext, width: u32, height: u32) -> StandardTexture {
+        StandardTexture::new(
             gpu, 
             width, 
             height, 
-            Self::COLOR_FORMAT, 
+            Self::COLOR_FORMAT,
             wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT, 
             Some("Hdr::texture")
-        );
+        )
+    }
 
-        let bind_group = GpuBindGroup::create_default(
+    /// Create the sampler for the HDR texture, filtering with `filter` both ways.
+    fn create_sampler(gpu: &GpuContext, filter: wgpu::FilterMode) -> wgpu::Sampler {
+        gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Hdr::sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        })
+    }
+
+    /// Create the bind group for sampling the HDR texture.
+    fn create_bind_group(gpu: &GpuContext, texture: &StandardTexture, sampler: &wgpu::Sampler) -> GpuBindGroup {
+        GpuBindGroup::create_default(
             "Hdr::bind_group", 
             gpu, 
             &Self::BIND_GROUP_LAYOUT_ENTRIES, 
             &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.inner().view())
+                    resource: wgpu::BindingResource::TextureView(texture.inner().view())
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(texture.inner().sampler())
+                    resource: wgpu::BindingResource::Sampler(sampler)
                 }
             ]
-        );
+        )
+    }
+
+    /// Rebuild the tonemapping pass to output to the given surface format, ie after the surface's format changed.
+    pub fn recreate_for_format(&mut self, gpu: &GpuContext, format: wgpu::TextureFormat) {
+        self.pass = Self::create_pass(gpu, &self.bind_group, &self.lut_bind_group, format);
+    }
+
+    /// Set the color grading LUT applied after tonemapping, or `None` to leave colors unchanged.
+    ///
+    /// The LUT is a 3D table unrolled into a `size * size` by `size` strip (see `StandardTexture::identity_color_lut`),
+    /// indexed by the gamma-encoded color. It should be in an sRGB format (ie loaded with `ColorSpace::Srgb`),
+    /// and is sampled with its own sampler, which should filter linearly.
+    pub fn set_color_lut(&mut self, gpu: &GpuContext, lut: Option<&GpuTexture>) {
+        self.lut_bind_group = match lut {
+            Some(lut) => Self::create_lut_bind_group(gpu, lut),
+            None => Self::create_identity_lut_bind_group(gpu),
+        };
+    }
 
+    /// Create the tonemapping pass, outputting to the surface format.
+    ///
+    /// The pass must target the surface's exact format, since that's what the frame's view has. An sRGB surface
+    /// gamma-encodes the linear output itself; otherwise the shader encodes it, so it's never done twice (or not at all).
+    fn create_pass(
+        gpu: &GpuContext,
+        bind_group: &GpuBindGroup,
+        lut_bind_group: &GpuBindGroup,
+        format: wgpu::TextureFormat,
+    ) -> FullscreenPass {
         let shader = gpu.device().create_shader_module(wgpu::include_wgsl!("../../hdr.wgsl"));
-        let pipeline = GpuPipeline::create_default(
-            "Hdr::pipeline", 
+        let fragment_entry_point = if format.is_srgb() {
+            "fs_main"
+        } else {
+            "fs_main_encode_srgb"
+        };
+        FullscreenPass::with_fragment_entry_point(
+            "Hdr", 
             gpu, 
-            &[bind_group.layout()], 
-            &[], // we generate vertex data directly in the shader 
             &shader, 
-            &shader, 
-            None,
-            wgpu::PrimitiveTopology::TriangleList,
-            config.format.add_srgb_suffix()
-        );
+            &[bind_group.layout(), lut_bind_group.layout()], 
+            format,
+            Some(fragment_entry_point),
+        )
+    }
 
-        Self {
-            pipeline,
-            bind_group,
-            texture,
-            width,
-            height,
-        }
+    /// Renders the HDR texture to the supplied texture view.
+    pub fn process(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        self.pass.draw(encoder, output, &[&self.bind_group, &self.lut_bind_group]);
     }
 
-    /// Resize the HDR texture.
-    pub fn resize(&mut self, gpu: &GpuContext, width: u32, height: u32) {
-        self.texture = StandardTexture::new(
-            gpu, 
-            width, 
-            height, 
-            Self::COLOR_FORMAT,
-            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT, 
-            Some("Hdr::texture")
-        );
-        self.bind_group = GpuBindGroup::create_default(
-            "Hdr::bind_group", 
-            gpu, 
-            &Self::BIND_GROUP_LAYOUT_ENTRIES, 
+    /// Create the bind group for sampling a color grading LUT.
+    fn create_lut_bind_group(gpu: &GpuContext, lut: &GpuTexture) -> GpuBindGroup {
+        GpuBindGroup::create_default(
+            "Hdr::lut_bind_group",
+            gpu,
+            &Self::BIND_GROUP_LAYOUT_ENTRIES,
             &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(self.texture.inner().view())
+                    resource: wgpu::BindingResource::TextureView(lut.view())
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(self.texture.inner().sampler())
+                    resource: wgpu::BindingResource::Sampler(lut.sampler())
                 }
             ]
-        );
-        self.width = width;
-        self.height = height;
+        )
     }
 
-    /// Renders the HDR texture to the supplied texture view.
-    pub fn process(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
-        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor { 
-            label: Some("Hdr::render_pass"), 
-            color_attachments: &[
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &output,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store
-                    },
-                    depth_slice: None
-                })
-            ], 
-            depth_stencil_attachment: None, 
-            timestamp_writes: None, 
-            occlusion_query_set: None 
-        });
-        pass.set_pipeline(self.pipeline.handle());
-        pass.set_bind_group(0, self.bind_group.handle(), &[]);
-        pass.draw(0..3, 0..1);
+    /// Create the bind group for the LUT that leaves colors unchanged.
+    fn create_identity_lut_bind_group(gpu: &GpuContext) -> GpuBindGroup {
+        let lut = StandardTexture::identity_color_lut(gpu, Self::IDENTITY_LUT_SIZE, Some("Hdr::identity_lut"))
+            .expect("The identity LUT is always a valid image");
+        Self::create_lut_bind_group(gpu, lut.inner())
     }
 
     /// Get the inner texture.
     pub fn texture(&self) -> &GpuTexture {
         self.texture.inner()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix3, Vector3};
+    use crate::graphics::gpu::test_context;
+    use super::*;
+
+    /// The CPU version of the shader's `aces_tone_map`.
+    fn aces_tone_map(hdr: Vector3<f32>) -> Vector3<f32> {
+        let m1 = Matrix3::new(0.59719, 0.07600, 0.02840, 0.35458, 0.90834, 0.13383, 0.04823, 0.01566, 0.83777);
+        let m2 = Matrix3::new(1.60475, -0.10208, -0.00327, -0.53108, 1.10813, -0.07276, -0.07367, -0.00605, 1.07602);
+        let v = m1 * hdr;
+        let a = v.map(|v| v * (v + 0.0245786) - 0.000090537);
+        let b = v.map(|v| v * (0.983729 * v + 0.432951) + 0.238081);
+        (m2 * Vector3::new(a.x / b.x, a.y / b.y, a.z / b.z)).map(|v| v.clamp(0.0, 1.0))
+    }
+
+    /// Fill the HDR texture with `color`, tonemap it into a 1x1 texture of the given format, and read the pixel back.
+    fn tonemap_pixel(gpu: &GpuContext, color: wgpu::Color, format: wgpu::TextureFormat) -> [u8; 4] {
+        let hdr = HdrPipeline::new(gpu, 1, 1, format);
+        let output = StandardTexture::new(
+            gpu,
+            1,
+            1,
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            Some("test_output"),
+        );
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("test_readback_buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu.device().create_command_encoder(&Default::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test_clear_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: hdr.texture().view(),
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(color), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+        hdr.process(&mut encoder, output.inner().view());
+        encoder.copy_texture_to_buffer(
+            output.inner().handle().as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        gpu.queue().submit([encoder.finish()]);
+
+        let slice = buffer.slice(..4);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        gpu.device().poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        slice.get_mapped_range()[..4].try_into().unwrap()
+    }
+
+    #[test]
+    fn gamma_is_encoded_once_for_srgb_and_linear_surfaces() {
+        let Some(gpu) = test_context() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let color = wgpu::Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+        let linear = aces_tone_map(Vector3::new(0.5, 0.5, 0.5)).x;
+        let encoded = (1.055 * linear.powf(1.0 / 2.4) - 0.055) * 255.0;
+
+        for format in [wgpu::TextureFormat::Rgba8UnormSrgb, wgpu::TextureFormat::Rgba8Unorm] {
+            let pixel = tonemap_pixel(&gpu, color, format);
+            // the stored bytes are sRGB-encoded either way; encoding twice (or not at all) is far off
+            assert!((pixel[0] as f32 - encoded).abs() <= 2.0, "{format:?}: {pixel:?}, expected ~{encoded}");
+        }
+    }
+}
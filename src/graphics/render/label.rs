@@ -0,0 +1,35 @@
+use cgmath::{Matrix4, Vector3};
+
+use crate::systems::camera::Camera;
+
+/// A text label anchored at a position in the world, queued for drawing this frame.
+pub struct WorldLabel {
+    pub text: String,
+    pub world_position: Vector3<f32>,
+    pub color: [f32; 4],
+}
+
+/// A text label projected to screen space, ready to be drawn by the 2D pass.
+pub struct ScreenLabel {
+    pub text: String,
+    /// The position in pixels, with the origin at the top-left.
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl WorldLabel {
+    /// Project the label to screen space through the camera's view-projection matrix.
+    ///
+    /// Returns `None` if the label is behind the camera or off-screen.
+    pub fn to_screen(&self, view_proj: Matrix4<f32>, width: u32, height: u32) -> Option<ScreenLabel> {
+        let position = Camera::project_to_screen(view_proj, self.world_position, (width, height))?;
+        if !(0.0..=width as f32).contains(&position.x) || !(0.0..=height as f32).contains(&position.y) {
+            return None;
+        }
+        Some(ScreenLabel {
+            text: self.text.clone(),
+            position: position.into(),
+            color: self.color,
+        })
+    }
+}
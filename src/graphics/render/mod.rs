@@ -1,5 +1,13 @@
 pub mod assets;
+pub mod clustered;
 pub mod commands;
+pub mod debug_view;
+pub mod deferred;
+pub mod fullscreen;
+pub mod graph;
+pub mod grid;
+pub mod label;
+pub mod reflection;
 pub mod renderable;
 pub mod renderer;
 pub mod hdr;
@@ -0,0 +1,229 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, SquareMatrix, Vector3, Vector4};
+use crate::{graphics::{
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, pipeline::{GpuPipeline, GpuPipelineDescriptor}, texture::GpuTexture, vertex::Vertex},
+    render::{renderable::model::ModelVertex, renderer::BindGroupId},
+    scene::instance_buffer::MeshInstanceData,
+    textures::{depth::DepthTexture, standard::StandardTexture},
+}, systems::camera::{CAMERA_BIND_GROUP_LAYOUT_ENTRIES, CameraUniform}};
+
+/// A plane the scene is reflected across, ie a water surface or mirror floor.
+#[derive(Clone, Copy, Debug)]
+pub struct ReflectionPlane {
+    /// Any point on the plane.
+    pub point: Vector3<f32>,
+    /// The plane's (normalized) normal, pointing to the side that's reflected.
+    pub normal: Vector3<f32>,
+}
+
+impl ReflectionPlane {
+    /// Create a plane through the point with the given normal, which is normalized.
+    pub fn new(point: Vector3<f32>, normal: Vector3<f32>) -> Self {
+        Self {
+            point,
+            normal: normal.normalize(),
+        }
+    }
+
+    /// Get the plane's equation `(normal, d)`, where `dot(normal, p) + d = 0` for points on it.
+    pub fn equation(&self) -> Vector4<f32> {
+        self.normal.extend(-self.normal.dot(self.point))
+    }
+
+    /// Get the matrix mirroring points across the plane.
+    pub fn reflection_matrix(&self) -> Matrix4<f32> {
+        let n = self.normal;
+        let d = -n.dot(self.point);
+        let column = |axis: Vector3<f32>, n_i: f32| (axis - n * (2.0 * n_i)).extend(0.0);
+        Matrix4::from_cols(
+            column(Vector3::unit_x(), n.x),
+            column(Vector3::unit_y(), n.y),
+            column(Vector3::unit_z(), n.z),
+            (n * (-2.0 * d)).extend(1.0),
+        )
+    }
+}
+
+/// The offscreen target the scene is rendered into when mirrored across a `ReflectionPlane`.
+///
+/// The mirrored camera is also flipped horizontally, so that triangles keep their winding (and aren't culled);
+/// this means the texture is mirrored left-to-right, which the reflective surface shader undoes when sampling.
+pub struct PlanarReflection {
+    color: StandardTexture,
+    depth: StandardTexture,
+    camera_buffer: GpuBuffer,
+    camera_bind_group: BindGroupId,
+}
+
+impl PlanarReflection {
+    const SURFACE_BIND_GROUP_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 2] = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ];
+
+    /// Initialize the target at the given size + format, with the mirrored camera's buffer and (registered) bind group.
+    pub fn new(
+        gpu: &GpuContext,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        camera_buffer: GpuBuffer,
+        camera_bind_group: BindGroupId,
+    ) -> Self {
+        let [color, depth] = Self::create_textures(gpu, width, height, format);
+        Self {
+            color,
+            depth,
+            camera_buffer,
+            camera_bind_group,
+        }
+    }
+
+    /// Recreate the textures at the given size + format, ie when the renderer is resized or HDR is toggled.
+    pub fn resize(&mut self, gpu: &GpuContext, width: u32, height: u32, format: wgpu::TextureFormat) {
+        [self.color, self.depth] = Self::create_textures(gpu, width, height, format);
+    }
+
+    /// Get the texture the reflection is rendered into.
+    pub fn texture(&self) -> &GpuTexture {
+        self.color.inner()
+    }
+
+    /// Get the depth texture used while rendering the reflection.
+    pub fn depth_texture(&self) -> &GpuTexture {
+        self.depth.inner()
+    }
+
+    /// Get the mirrored camera's bind group.
+    pub fn camera_bind_group(&self) -> BindGroupId {
+        self.camera_bind_group
+    }
+
+    /// Write the camera, mirrored across the plane, to the mirrored camera's buffer.
+    ///
+    /// The projection's near plane is also moved onto the reflection plane (an oblique near plane),
+    /// so anything behind the plane (ie underwater) doesn't show up in the reflection.
+    pub fn write_camera(&self, gpu: &GpuContext, view: Matrix4<f32>, projection: Matrix4<f32>, plane: &ReflectionPlane) {
+        let view = view * plane.reflection_matrix();
+        let view_plane = view
+            .invert()
+            .expect("The mirrored view matrix must be invertible")
+            .transpose()
+            * plane.equation();
+        let projection = Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0) * Self::oblique_projection(projection, view_plane);
+        let inverse_view = view.invert().expect("The mirrored view matrix must be invertible");
+        let uniform = CameraUniform::from_matrices(view, projection, inverse_view.w.truncate());
+        gpu.queue().write_buffer(
+            self.camera_buffer.handle(),
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+    }
+
+    /// Create the pipeline for drawing a reflective surface, which shows the reflection texture at each fragment's
+    /// screen position. It renders to the given format (ie the renderer's `target_format`).
+    ///
+    /// It takes the camera at `REFLECTION_CAMERA_BIND_GROUP_SLOT` and a bind group from `create_surface_bind_group`
+    /// at `REFLECTION_TEXTURE_BIND_GROUP_SLOT`, with the same vertex + instance buffers as meshes, so a mesh can be
+    /// drawn with it as a `RawRenderCommand`.
+    pub fn create_surface_pipeline(gpu: &GpuContext, color_format: wgpu::TextureFormat) -> GpuPipeline {
+        let device = gpu.device();
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PlanarReflection::camera_layout"),
+            entries: &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PlanarReflection::texture_layout"),
+            entries: &Self::SURFACE_BIND_GROUP_LAYOUT_ENTRIES,
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../../reflection.wgsl"));
+        GpuPipeline::create(
+            "PlanarReflection::surface_pipeline",
+            gpu,
+            &[&camera_layout, &texture_layout],
+            &[ModelVertex::desc(), MeshInstanceData::desc()],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                cull_mode: None,
+                color_targets: vec![GpuPipeline::color_target(color_format)],
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create the bind group for sampling the reflection texture in the reflective surface pipeline.
+    ///
+    /// The texture is replaced when the renderer is resized or HDR is toggled, so this needs recreating then.
+    pub fn create_surface_bind_group(gpu: &GpuContext, texture: &GpuTexture) -> GpuBindGroup {
+        GpuBindGroup::create_default(
+            "PlanarReflection::surface_bind_group",
+            gpu,
+            &Self::SURFACE_BIND_GROUP_LAYOUT_ENTRIES,
+            &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(texture.sampler()),
+                },
+            ],
+        )
+    }
+
+    /// Replace the projection's near plane with the given view-space plane, keeping the far plane outside the frustum.
+    ///
+    /// See Lengyel, "Oblique View Frustum Depth Projection and Clipping"; this is the variant for 0..1 clip depth.
+    fn oblique_projection(projection: Matrix4<f32>, view_plane: Vector4<f32>) -> Matrix4<f32> {
+        let Some(inverse) = projection.invert() else {
+            return projection;
+        };
+        // the frustum corner opposite the plane, which the new far plane passes through
+        let corner = inverse * Vector4::new(view_plane.x.signum(), view_plane.y.signum(), 1.0, 1.0);
+        let row = view_plane / view_plane.dot(corner);
+
+        let mut projection = projection;
+        projection.x.z = row.x;
+        projection.y.z = row.y;
+        projection.z.z = row.z;
+        projection.w.z = row.w;
+        projection
+    }
+
+    /// Create the color + depth textures.
+    fn create_textures(gpu: &GpuContext, width: u32, height: u32, format: wgpu::TextureFormat) -> [StandardTexture; 2] {
+        [
+            (format, "PlanarReflection::color"),
+            (DepthTexture::DEPTH_FORMAT, "PlanarReflection::depth"),
+        ].map(|(format, label)| StandardTexture::new(
+            gpu,
+            width,
+            height,
+            format,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            Some(label)
+        ))
+    }
+}
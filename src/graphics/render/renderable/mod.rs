@@ -1,3 +1,4 @@
 pub mod model;
+pub mod primitives;
 pub mod sprite;
 pub mod skybox;
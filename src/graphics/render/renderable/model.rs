@@ -1,24 +1,128 @@
+use cgmath::{Vector3, Vector4};
+use crate::core::entity::spatial_transform::SpatialTransform;
 use crate::core::world::WorldEntityId;
+use crate::graphics::gpu::vertex::Vertex;
 use crate::graphics::textures::standard::StandardTexture;
 use crate::graphics::{
-    gpu::{bind_group::GpuBindGroup, buffer::GpuBuffer, texture::GpuTexture},
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, texture::GpuTexture},
     render::{
         assets::{MaterialId, MeshId},
         commands::{DrawCommand, MeshRenderCommand},
         renderer::{BindGroupId, PipelineId},
     },
-    scene::instance_buffer::InstanceBufferRange,
+    scene::instance_buffer::{InstanceBufferRange, SecondaryInstanceData},
 };
 
 /// Represents an instance of a mesh.
 ///
 /// The instance points to the actual mesh it is an instance of,
 /// the entity containing its spatial data,
-/// and the material for it.
+/// and optionally a material overriding the mesh's.
 #[derive(Clone)]
 pub struct MeshInstance {
     pub mesh: MeshId,
     pub entity: WorldEntityId,
+    /// Whether the instance is drawn. Hidden instances keep their ID, so toggling this is cheaper than removal.
+    pub visible: bool,
+    /// The material to draw this instance with, instead of the mesh's.
+    pub material_override: Option<MaterialId>,
+    /// The layers the instance is on, as a bitmask; it's only drawn when this intersects the render's layer mask.
+    pub layers: u32,
+    /// Custom values passed to the mesh shaders with the instance's transform.
+    pub params: ObjectParams,
+    /// User-defined values for the secondary instance stream; only used by scenes with the stream enabled.
+    pub secondary: SecondaryInstanceData,
+    /// A transform relative to the entity's, ie to offset a wheel from a car's body without a child entity.
+    pub local_offset: Option<SpatialTransform>,
+}
+
+impl MeshInstance {
+    /// The layer mask including every layer.
+    pub const ALL_LAYERS: u32 = u32::MAX;
+
+    /// Create a visible instance on every layer, with the mesh's own material.
+    pub fn new(mesh: MeshId, entity: WorldEntityId) -> Self {
+        Self {
+            mesh,
+            entity,
+            visible: true,
+            material_override: None,
+            layers: Self::ALL_LAYERS,
+            params: ObjectParams::zeroed(),
+            secondary: SecondaryInstanceData::zeroed(),
+            local_offset: None,
+        }
+    }
+}
+
+/// Custom per-object values for the mesh shaders, ie for a highlight flag, an animation phase or a hit-flash color.
+///
+/// These are part of the instance data (at locations 11 + 12, one `vec4<f32>` per param), so instances with
+/// different params are still drawn together. The built-in shaders pass them through but don't use them.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectParams {
+    pub values: [[f32; 4]; ObjectParams::COUNT],
+}
+
+impl ObjectParams {
+    /// The number of params; each is a `vec4<f32>`, so this is limited by the number of vertex attributes.
+    pub const COUNT: usize = 2;
+
+    /// Get params with every value set to 0.
+    pub fn zeroed() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+
+    /// Set the param at the index, returning whether the index was in range.
+    pub fn set(&mut self, index: usize, value: impl ObjectParam) -> bool {
+        match self.values.get_mut(index) {
+            Some(param) => {
+                *param = value.to_raw();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A value that can be stored in one of the `ObjectParams`.
+///
+/// Smaller values are padded with zeroes, ie a `f32` is read from the param's `x`.
+pub trait ObjectParam {
+    /// Convert the value to the param's raw `vec4<f32>`.
+    fn to_raw(self) -> [f32; 4];
+}
+
+impl ObjectParam for f32 {
+    fn to_raw(self) -> [f32; 4] {
+        [self, 0.0, 0.0, 0.0]
+    }
+}
+
+/// Stored as 1 or 0.
+impl ObjectParam for bool {
+    fn to_raw(self) -> [f32; 4] {
+        [if self { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]
+    }
+}
+
+impl ObjectParam for [f32; 4] {
+    fn to_raw(self) -> [f32; 4] {
+        self
+    }
+}
+
+impl ObjectParam for Vector3<f32> {
+    fn to_raw(self) -> [f32; 4] {
+        self.extend(0.0).into()
+    }
+}
+
+impl ObjectParam for Vector4<f32> {
+    fn to_raw(self) -> [f32; 4] {
+        self.into()
+    }
 }
 
 /// A model, essentially a collection of materials (textures) and meshes (vertices).
@@ -32,20 +136,142 @@ pub struct Material {
     pub name: String,
     pub diffuse_texture: StandardTexture,
     pub normal_texture: StandardTexture,
+    /// A lightmap/detail texture multiplied into the diffuse color, sampled with the second UV channel.
+    ///
+    /// This is 1x1 white for materials without one, so it has no effect; the loaders share one between materials.
+    pub lightmap_texture: StandardTexture,
     pub bind_group: BindGroupId,
+    /// The diffuse alpha below which fragments are discarded, for cutout materials (ie foliage/fences).
+    ///
+    /// Cutout materials are still drawn in the opaque pass; set this with `set_alpha_cutoff` so the uniform is updated.
+    pub alpha_cutoff: Option<f32>,
+    /// The buffer holding the material's `MaterialUniform`.
+    pub uniform_buffer: GpuBuffer,
+    /// Whether the material is lit from both sides (ie for foliage/cloth).
+    ///
+    /// Meshes with a double-sided material are drawn without backface culling,
+    /// and the shader flips the normal for back faces.
+    pub double_sided: bool,
+}
+
+impl Material {
+    /// Swap the material's textures, returning the bind group for them.
+    ///
+    /// The bind group isn't used until it replaces the current one, ie via `Renderer::replace_bind_group(material.bind_group, ..)`.
+    pub fn rebuild_bind_group(
+        &mut self,
+        gpu: &GpuContext,
+        diffuse_texture: StandardTexture,
+        normal_texture: StandardTexture,
+    ) -> GpuBindGroup {
+        self.diffuse_texture = diffuse_texture;
+        self.normal_texture = normal_texture;
+        let layout_entries = StandardTexture::bind_group_entries(
+            &self.diffuse_texture,
+            &self.normal_texture,
+            &self.lightmap_texture,
+            &self.uniform_buffer,
+        );
+        GpuBindGroup::create_default(&self.name, gpu, &layout_entries.0, &layout_entries.1)
+    }
+
+    /// Set the alpha cutoff and write it to the material's uniform.
+    pub fn set_alpha_cutoff(&mut self, gpu: &GpuContext, alpha_cutoff: Option<f32>) {
+        self.alpha_cutoff = alpha_cutoff;
+        gpu.queue().write_buffer(
+            self.uniform_buffer.handle(),
+            0,
+            bytemuck::cast_slice(&[MaterialUniform::new(alpha_cutoff)]),
+        );
+    }
+}
+
+/// The per-material data used in the shader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    alpha_cutoff: f32,
+    _padding: [f32; 3], // uniforms need 16 byte alignment
+}
+
+impl MaterialUniform {
+    /// Create the uniform. Without a cutoff, it's 0 so nothing is discarded.
+    pub fn new(alpha_cutoff: Option<f32>) -> Self {
+        Self {
+            alpha_cutoff: alpha_cutoff.unwrap_or(0.0),
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Create a uniform buffer holding the uniform.
+    pub fn create_buffer(self, label: &str, gpu: &GpuContext) -> GpuBuffer {
+        GpuBuffer::create_uniform(label, gpu, bytemuck::cast_slice(&[self]))
+    }
 }
 
 /// A mesh; the actual thing rendered.
+///
+/// The buffers can be shared with other meshes (see `AssetStore::add_meshes_packed`),
+/// in which case the mesh's data is located by `base_vertex` + `first_index`.
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: GpuBuffer,
-    pub index_buffer: GpuBuffer,
+    /// The index buffer, or `None` if the mesh isn't indexed (ie procedural geometry or point clouds).
+    pub index_buffer: Option<GpuBuffer>,
     pub material: MaterialId,
+    /// The number of indices, or of vertices for a non-indexed mesh.
     pub num_elements: u32,
+    /// The offset added to each index, ie where the mesh's vertices start in the vertex buffer.
+    ///
+    /// For a non-indexed mesh, this is the first vertex drawn.
+    pub base_vertex: i32,
+    /// Where the mesh's indices start in the index buffer.
+    pub first_index: u32,
 }
 
 impl Mesh {
+    /// Create a mesh with its own buffers from its data.
+    ///
+    /// If the data has no indices, the mesh is non-indexed, ie every 3 vertices are a triangle.
+    pub fn from_data(gpu: &GpuContext, data: MeshData) -> Self {
+        let vertex_buffer = GpuBuffer::create_vertex(
+            &format!("{}_vertex_buffer", data.name),
+            gpu,
+            bytemuck::cast_slice(&data.vertices),
+        );
+        let index_buffer = (!data.indices.is_empty()).then(|| GpuBuffer::create_index(
+            &format!("{}_index_buffer", data.name),
+            gpu,
+            bytemuck::cast_slice(&data.indices),
+        ));
+        Self {
+            num_elements: data.element_count(),
+            name: data.name,
+            vertex_buffer,
+            index_buffer,
+            material: data.material,
+            base_vertex: 0,
+            first_index: 0,
+        }
+    }
+
+    /// Create a non-indexed mesh from its vertices, where every 3 vertices are a triangle.
+    ///
+    /// Vertices without tangents should have zeroed ones, so the shader falls back to the vertex normals.
+    pub fn from_vertices(gpu: &GpuContext, name: &str, vertices: Vec<ModelVertex>, material: MaterialId) -> Self {
+        Self::from_data(gpu, MeshData {
+            name: name.to_string(),
+            vertices,
+            indices: Vec::new(),
+            material,
+        })
+    }
+
     /// Create a command for rendering this mesh.
+    ///
+    /// ## Note
+    /// The draw's instances are `0..count` rather than the range itself, since the renderer binds
+    /// just the range's slice of the instance buffer. Binding the whole buffer instead would need `range.start..range.end`.
     pub fn to_render_command<'buf>(
         &'buf self,
         id: MeshId,
@@ -59,17 +285,53 @@ impl Mesh {
             name: &self.name,
             mesh: id,
             pipeline,
+            double_sided: material.double_sided,
             camera_bind_group,
             lighting_bind_group,
             material_bind_group: material.bind_group,
             vertex_buffer: self.vertex_buffer.handle().slice(..),
             instance_buffer_range: instance_buffer_range,
-            index_buffer: self.index_buffer.handle().slice(..),
-            draw: DrawCommand::Indexed {
-                base_vertex: 0,
-                instances: 0..(instance_buffer_range.end - instance_buffer_range.start) as u32,
-                indices: 0..self.num_elements,
+            secondary_instance_buffer_range: None,
+            index_buffer: self.index_buffer.as_ref().map(|buffer| buffer.handle().slice(..)),
+            draw: self.draw_command(instance_buffer_range),
+        }
+    }
+
+    /// Get the draw for this mesh's elements, for the instances in the range.
+    fn draw_command(&self, instance_buffer_range: InstanceBufferRange) -> DrawCommand {
+        let instances = 0..(instance_buffer_range.end - instance_buffer_range.start) as u32;
+        match self.index_buffer {
+            Some(_) => DrawCommand::Indexed {
+                base_vertex: self.base_vertex,
+                instances,
+                indices: self.first_index..self.first_index + self.num_elements,
             },
+            None => {
+                let first_vertex = self.base_vertex as u32;
+                DrawCommand::NonIndexed {
+                    vertices: first_vertex..first_vertex + self.num_elements,
+                    instances,
+                }
+            }
+        }
+    }
+}
+
+/// The CPU-side data of a mesh, for creating meshes with shared buffers.
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    pub material: MaterialId,
+}
+
+impl MeshData {
+    /// Get the number of elements drawn; the indices, or the vertices if there aren't any.
+    pub fn element_count(&self) -> u32 {
+        if self.indices.is_empty() {
+            self.vertices.len() as u32
+        } else {
+            self.indices.len() as u32
         }
     }
 }
@@ -82,12 +344,16 @@ pub struct ModelVertex {
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
     pub tangent: [f32; 3],
-    pub bitangent: [f32; 3]
+    pub bitangent: [f32; 3],
+    /// The vertex color, multiplied into the diffuse color. White if the model doesn't have any.
+    pub color: [f32; 4],
+    /// The second UV channel, for the material's lightmap/detail texture.
+    /// The same as `tex_coords` if the model only has one channel.
+    pub tex_coords2: [f32; 2],
 }
 
-impl ModelVertex {
-    /// Get the vertex buffer layout.
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+impl Vertex for ModelVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: size_of::<ModelVertex>() as u64,
             step_mode: wgpu::VertexStepMode::Vertex,
@@ -117,7 +383,55 @@ impl ModelVertex {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 18]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+    use super::*;
+
+    #[test]
+    fn model_vertex_layout_matches_the_struct() {
+        let layout = ModelVertex::desc();
+        assert_eq!(layout.array_stride, size_of::<ModelVertex>() as u64);
+
+        // in shader location order
+        let offsets = [
+            offset_of!(ModelVertex, position),
+            offset_of!(ModelVertex, tex_coords),
+            offset_of!(ModelVertex, normal),
+            offset_of!(ModelVertex, tangent),
+            offset_of!(ModelVertex, bitangent),
+            offset_of!(ModelVertex, color),
+            offset_of!(ModelVertex, tex_coords2),
+        ];
+        assert_eq!(layout.attributes.len(), offsets.len());
+        for (location, (attribute, offset)) in layout.attributes.iter().zip(offsets).enumerate() {
+            assert_eq!(attribute.shader_location, location as u32);
+            assert_eq!(attribute.offset, offset as u64, "offset of location {location}");
+        }
+    }
+
+    #[test]
+    fn second_uv_channel_is_the_last_attribute() {
+        let layout = ModelVertex::desc();
+        let attribute = layout.attributes.last().unwrap();
+        assert_eq!(attribute.shader_location, 6);
+        assert_eq!(attribute.format, wgpu::VertexFormat::Float32x2);
+        assert_eq!(attribute.offset, offset_of!(ModelVertex, tex_coords2) as u64);
+        assert_eq!(attribute.offset + attribute.format.size(), layout.array_stride);
+    }
+}
@@ -0,0 +1,241 @@
+use std::f32::consts::PI;
+use cgmath::{InnerSpace, Vector3};
+use crate::graphics::{
+    gpu::GpuContext,
+    render::{assets::MaterialId, renderable::model::{Mesh, MeshData, ModelVertex}},
+};
+
+// All primitives are centred on the origin, and wound counter-clockwise from the outside.
+//
+// UVs go right/down across each surface as seen from the outside, so the tangent follows +u
+// and the bitangent -v, the same as for loaded models.
+
+impl Mesh {
+    /// Create a cube with the given side length.
+    pub fn cube(gpu: &GpuContext, size: f32, material: MaterialId) -> Self {
+        Self::from_data(gpu, MeshData::cube(size, material))
+    }
+
+    /// Create a UV sphere with the given radius, and number of segments around it.
+    pub fn sphere(gpu: &GpuContext, radius: f32, segments: u32, material: MaterialId) -> Self {
+        Self::from_data(gpu, MeshData::sphere(radius, segments, material))
+    }
+
+    /// Create a flat plane facing up (ie on the XZ plane), split into `subdivisions` segments along each side.
+    pub fn plane(gpu: &GpuContext, width: f32, depth: f32, subdivisions: u32, material: MaterialId) -> Self {
+        Self::from_data(gpu, MeshData::plane(width, depth, subdivisions, material))
+    }
+
+    /// Create a capped cylinder along the Y axis, with the given number of segments around it.
+    pub fn cylinder(gpu: &GpuContext, radius: f32, height: f32, segments: u32, material: MaterialId) -> Self {
+        Self::from_data(gpu, MeshData::cylinder(radius, height, segments, material))
+    }
+}
+
+impl MeshData {
+    /// Generate a cube's data; see `Mesh::cube`.
+    pub fn cube(size: f32, material: MaterialId) -> Self {
+        // each face's normal, and the direction that's up when looking at it
+        let faces = [
+            (Vector3::unit_x(), Vector3::unit_y()),
+            (-Vector3::unit_x(), Vector3::unit_y()),
+            (Vector3::unit_z(), Vector3::unit_y()),
+            (-Vector3::unit_z(), Vector3::unit_y()),
+            (Vector3::unit_y(), -Vector3::unit_z()),
+            (-Vector3::unit_y(), Vector3::unit_z()),
+        ];
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, up) in faces {
+            let right = up.cross(normal);
+            let down = -up;
+            let first = vertices.len() as u32;
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let position = (normal * 0.5 + right * (u - 0.5) + down * (v - 0.5)) * size;
+                vertices.push(vertex(position, normal, [u, v], right, down));
+            }
+            indices.extend(quad_indices(first, first + 1, first + 2, first + 3));
+        }
+        Self::primitive("cube", vertices, indices, material)
+    }
+
+    /// Generate a sphere's data; see `Mesh::sphere`.
+    pub fn sphere(radius: f32, segments: u32, material: MaterialId) -> Self {
+        let segments = segments.max(3);
+        let rings = (segments / 2).max(2);
+        let mut vertices = Vec::with_capacity(((rings + 1) * (segments + 1)) as usize);
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let theta = v * PI;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let phi = u * 2.0 * PI;
+                let normal = Vector3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+                // the derivatives along u + v; the tangent's taken from phi alone so it's still defined at the poles
+                let right = Vector3::new(-phi.sin(), 0.0, -phi.cos());
+                let down = Vector3::new(theta.cos() * phi.cos(), -theta.sin(), -theta.cos() * phi.sin());
+                vertices.push(vertex(normal * radius, normal, [u, v], right, down));
+            }
+        }
+        let indices = grid_indices(segments, rings);
+        Self::primitive("sphere", vertices, indices, material)
+    }
+
+    /// Generate a plane's data; see `Mesh::plane`.
+    pub fn plane(width: f32, depth: f32, subdivisions: u32, material: MaterialId) -> Self {
+        let subdivisions = subdivisions.max(1);
+        let mut vertices = Vec::with_capacity(((subdivisions + 1) * (subdivisions + 1)) as usize);
+        for row in 0..=subdivisions {
+            let v = row as f32 / subdivisions as f32;
+            for column in 0..=subdivisions {
+                let u = column as f32 / subdivisions as f32;
+                let position = Vector3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+                vertices.push(vertex(position, Vector3::unit_y(), [u, v], Vector3::unit_x(), Vector3::unit_z()));
+            }
+        }
+        let indices = grid_indices(subdivisions, subdivisions);
+        Self::primitive("plane", vertices, indices, material)
+    }
+
+    /// Generate a cylinder's data; see `Mesh::cylinder`.
+    pub fn cylinder(radius: f32, height: f32, segments: u32, material: MaterialId) -> Self {
+        let segments = segments.max(3);
+        let mut vertices = Vec::new();
+
+        // the side, as a grid 1 row high
+        for row in 0..=1 {
+            let v = row as f32;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let phi = u * 2.0 * PI;
+                let normal = Vector3::new(phi.cos(), 0.0, -phi.sin());
+                let right = Vector3::new(-phi.sin(), 0.0, -phi.cos());
+                let position = normal * radius + Vector3::unit_y() * (0.5 - v) * height;
+                vertices.push(vertex(position, normal, [u, v], right, -Vector3::unit_y()));
+            }
+        }
+        let mut indices = grid_indices(segments, 1);
+
+        // the caps, as fans around their centres; the bottom's seen from below, so it's flipped along z
+        for (normal, z_flip) in [(Vector3::unit_y(), 1.0), (-Vector3::unit_y(), -1.0)] {
+            let centre = vertices.len() as u32;
+            let cap_vertex = |position: Vector3<f32>| {
+                let uv = [0.5 + position.x / (2.0 * radius), 0.5 + z_flip * position.z / (2.0 * radius)];
+                vertex(position + normal * height * 0.5, normal, uv, Vector3::unit_x(), Vector3::unit_z() * z_flip)
+            };
+            vertices.push(cap_vertex(Vector3::new(0.0, 0.0, 0.0)));
+            for segment in 0..=segments {
+                let phi = segment as f32 / segments as f32 * 2.0 * PI;
+                vertices.push(cap_vertex(Vector3::new(phi.cos(), 0.0, -phi.sin()) * radius));
+            }
+            for segment in 0..segments {
+                let (a, b) = (centre + 1 + segment, centre + 2 + segment);
+                match z_flip > 0.0 {
+                    true => indices.extend([centre, a, b]),
+                    false => indices.extend([centre, b, a]),
+                }
+            }
+        }
+        Self::primitive("cylinder", vertices, indices, material)
+    }
+
+    /// Wrap generated vertices + indices.
+    fn primitive(name: &str, vertices: Vec<ModelVertex>, indices: Vec<u32>, material: MaterialId) -> Self {
+        Self {
+            name: name.to_string(),
+            vertices,
+            indices,
+            material,
+        }
+    }
+}
+
+/// Create a white vertex, with the tangent + bitangent from the surface's directions along +u and +v.
+fn vertex(position: Vector3<f32>, normal: Vector3<f32>, uv: [f32; 2], right: Vector3<f32>, down: Vector3<f32>) -> ModelVertex {
+    ModelVertex {
+        position: position.into(),
+        tex_coords: uv,
+        normal: normal.normalize().into(),
+        tangent: right.normalize().into(),
+        bitangent: (-down.normalize()).into(),
+        color: [1.0; 4],
+        tex_coords2: uv,
+    }
+}
+
+/// The indices for a quad from its corners, as seen from the outside.
+fn quad_indices(top_left: u32, top_right: u32, bottom_right: u32, bottom_left: u32) -> [u32; 6] {
+    [top_left, bottom_left, bottom_right, top_left, bottom_right, top_right]
+}
+
+/// The indices for a grid of vertices laid out row by row, with `columns + 1` vertices per row.
+fn grid_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .flat_map(|(row, column)| {
+            let top_left = row * stride + column;
+            quad_indices(top_left, top_left + 1, top_left + stride + 1, top_left + stride)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check every index is in range and every triangle faces away from the origin.
+    fn assert_valid(mesh: &MeshData) {
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(mesh.vertices[triangle[i] as usize].position));
+            let normal = (b - a).cross(c - a);
+            // degenerate triangles (ie at a sphere's poles) have no facing
+            if normal.magnitude2() > 1e-12 {
+                assert!(normal.dot(a + b + c) >= 0.0, "{} has an inward-facing triangle", mesh.name);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_has_four_vertices_and_two_triangles_per_face() {
+        let cube = MeshData::cube(1.0, MaterialId::default());
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.indices.len(), 36);
+        assert_valid(&cube);
+    }
+
+    #[test]
+    fn sphere_counts_follow_its_segments() {
+        let sphere = MeshData::sphere(1.0, 8, MaterialId::default());
+        // 8 segments -> 4 rings, with a duplicated seam column
+        assert_eq!(sphere.vertices.len(), 5 * 9);
+        assert_eq!(sphere.indices.len(), 4 * 8 * 6);
+        assert_valid(&sphere);
+    }
+
+    #[test]
+    fn plane_counts_follow_its_subdivisions() {
+        let plane = MeshData::plane(2.0, 2.0, 3, MaterialId::default());
+        assert_eq!(plane.vertices.len(), 4 * 4);
+        assert_eq!(plane.indices.len(), 3 * 3 * 6);
+        assert_valid(&plane);
+    }
+
+    #[test]
+    fn cylinder_counts_follow_its_segments() {
+        let cylinder = MeshData::cylinder(1.0, 2.0, 6, MaterialId::default());
+        // the side's 2 rows of 7, then each cap's centre + 7 around it
+        assert_eq!(cylinder.vertices.len(), 2 * 7 + 2 * 8);
+        assert_eq!(cylinder.indices.len(), 6 * 6 + 2 * 6 * 3);
+        assert_valid(&cylinder);
+    }
+
+    #[test]
+    fn degenerate_arguments_are_clamped() {
+        assert_eq!(MeshData::sphere(1.0, 0, MaterialId::default()).indices.len(), 2 * 3 * 6);
+        assert_eq!(MeshData::plane(1.0, 1.0, 0, MaterialId::default()).indices.len(), 6);
+        assert_eq!(MeshData::cylinder(1.0, 1.0, 1, MaterialId::default()).indices.len(), 3 * 6 + 2 * 3 * 3);
+    }
+}
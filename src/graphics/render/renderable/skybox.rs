@@ -1,15 +1,202 @@
-use crate::graphics::{gpu::{GpuContext, pipeline::GpuPipeline}, render::{commands::SkyboxRenderCommand, hdr::HdrPipeline, renderer::{BindGroupId, PipelineId}}, textures::{cube::CubeMapTexture, depth::DepthTexture}};
+use crate::{core::world::{World, WorldEntityId}, graphics::{gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, pipeline::{GpuPipeline, GpuPipelineDescriptor}}, render::{commands::SkyboxRenderCommand, hdr::HdrPipeline, renderer::{BindGroupId, PipelineId}}, textures::{cube::CubeMapTexture, depth::DepthTexture}}};
 
 /// A skybox.
+///
+/// This is either sampled from a cubemap, or procedurally shaded around a sun (see `SkyBox::from_sun`).
 pub struct SkyBox {
     name: String,
-    texture: CubeMapTexture
+    source: SkySource,
+}
+
+/// What the sky is drawn from.
+enum SkySource {
+    CubeMap(CubeMapSky),
+    Sun(SunSky),
+}
+
+/// Which axis of the view ray is negated when sampling a cubemap sky.
+///
+/// This is for correcting cubemaps authored with the other handedness, which otherwise appear mirrored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkyFlip {
+    None,
+    X,
+    Z,
+}
+
+impl SkyFlip {
+    /// Get what the view ray is multiplied by.
+    fn ray_scale(&self) -> [f32; 3] {
+        match self {
+            SkyFlip::None => [1.0, 1.0, 1.0],
+            SkyFlip::X => [-1.0, 1.0, 1.0],
+            SkyFlip::Z => [1.0, 1.0, -1.0],
+        }
+    }
 }
 
 impl SkyBox {
     /// Initialize a skybox.
-    pub fn new(name: String, texture: CubeMapTexture) -> Self {
-        Self { name, texture }
+    pub fn new(gpu: &GpuContext, name: String, texture: CubeMapTexture) -> Self {
+        let uniform = CubeMapUniform::new(SkyFlip::None);
+        let buffer = GpuBuffer::create_uniform(&name, gpu, bytemuck::cast_slice(&[uniform]));
+        Self { name, source: SkySource::CubeMap(CubeMapSky { texture, uniform, buffer }) }
+    }
+
+    /// Initialize a procedural sky, lit by the sun with the given entity.
+    ///
+    /// The entity should be the one of the scene's sun `DirectionalLight`. The sun disk is drawn opposite its forward direction
+    /// (ie where the light comes from), and the sky's colors follow its elevation, so moving the entity moves the sun.
+    pub fn from_sun(gpu: &GpuContext, name: String, sun_entity: WorldEntityId) -> Self {
+        let uniform = SunUniform::new();
+        let buffer = GpuBuffer::create_uniform(&name, gpu, bytemuck::cast_slice(&[uniform]));
+        Self { name, source: SkySource::Sun(SunSky { entity: sun_entity, uniform, buffer }) }
+    }
+
+    /// Create the bind group for the sky's texture or sun uniform.
+    pub fn create_bind_group(&self, label: &str, gpu: &GpuContext) -> GpuBindGroup {
+        match &self.source {
+            SkySource::CubeMap(cube_map) => GpuBindGroup::create_default(
+                label,
+                gpu,
+                &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(cube_map.texture.inner().view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(cube_map.texture.inner().sampler()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: cube_map.buffer.handle().as_entire_binding(),
+                    },
+                ],
+            ),
+            SkySource::Sun(sun) => GpuBindGroup::create_default(
+                label,
+                gpu,
+                &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sun.buffer.handle().as_entire_binding(),
+                }],
+            ),
+        }
+    }
+
+    /// Create the pipeline for drawing the sky, with its shader depending on the sky's source.
+    pub fn create_pipeline(
+        &self,
+        label: &str,
+        gpu: &GpuContext,
+        camera_bind_group: &GpuBindGroup,
+        sky_bind_group: &GpuBindGroup,
+    ) -> GpuPipeline {
+        let shader = match &self.source {
+            SkySource::CubeMap(_) => gpu.device().create_shader_module(wgpu::include_wgsl!("../../../sky.wgsl")),
+            SkySource::Sun(_) => gpu.device().create_shader_module(wgpu::include_wgsl!("../../../sun_sky.wgsl")),
+        };
+        GpuPipeline::create(
+            label,
+            gpu,
+            &[camera_bind_group.layout(), sky_bind_group.layout()],
+            &[],
+            &shader,
+            &shader,
+            GpuPipelineDescriptor {
+                depth_stencil: Some(Self::depth_stencil_state()),
+                color_targets: vec![GpuPipeline::color_target(HdrPipeline::COLOR_FORMAT)],
+                ..Default::default()
+            },
+        )
+    }
+
+    /// The sky pipeline's depth state.
+    ///
+    /// The sky is at the far plane, which the depth is already cleared to, so it's drawn where nothing closer was,
+    /// and writing it would only get in the way of later depth-tested passes.
+    fn depth_stencil_state() -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: DepthTexture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }
+    }
+
+    /// Set which axis a cubemap sky's sampling direction is flipped along.
+    ///
+    /// Does nothing for procedural skies. Takes effect on the next `update_and_write_buffer`.
+    pub fn set_flip(&mut self, flip: SkyFlip) {
+        if let SkySource::CubeMap(cube_map) = &mut self.source {
+            cube_map.uniform = CubeMapUniform::new(flip);
+        }
+    }
+
+    /// Update the sky's uniform and write it to the buffer.
+    ///
+    /// For procedural skies, the sun's direction is updated from its entity.
+    pub fn update_and_write_buffer(&mut self, world: &World, gpu: &GpuContext) {
+        match &mut self.source {
+            SkySource::CubeMap(cube_map) => {
+                gpu.queue().write_buffer(
+                    cube_map.buffer.handle(),
+                    0,
+                    bytemuck::cast_slice(&[cube_map.uniform]),
+                );
+            }
+            SkySource::Sun(sun) => {
+                let entity = world
+                    .entity(sun.entity)
+                    .expect("Sun entity should exist");
+                sun.uniform.direction = (-entity.transform().forward()).into();
+                gpu.queue().write_buffer(
+                    sun.buffer.handle(),
+                    0,
+                    bytemuck::cast_slice(&[sun.uniform]),
+                );
+            }
+        }
     }
 
     /// Create a command for rendering this skybox.
@@ -26,4 +213,192 @@ impl SkyBox {
             camera_bind_group
         }
     }
-}
\ No newline at end of file
+}
+
+/// A sky sampled from a cubemap.
+struct CubeMapSky {
+    texture: CubeMapTexture,
+    uniform: CubeMapUniform,
+    buffer: GpuBuffer,
+}
+
+/// What the view ray is multiplied by before sampling, ie for flipping it.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+struct CubeMapUniform {
+    ray_scale: [f32; 3],
+    _padding: u32,
+}
+
+impl CubeMapUniform {
+    /// Create the uniform for the given flip.
+    fn new(flip: SkyFlip) -> Self {
+        Self {
+            ray_scale: flip.ray_scale(),
+            _padding: 0,
+        }
+    }
+}
+
+/// A procedural sky following a sun entity.
+struct SunSky {
+    entity: WorldEntityId,
+    uniform: SunUniform,
+    buffer: GpuBuffer,
+}
+
+/// The direction towards the sun.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+struct SunUniform {
+    direction: [f32; 3],
+    _padding: u32,
+}
+
+impl SunUniform {
+    /// Create the uniform, with the sun straight up.
+    fn new() -> Self {
+        Self {
+            direction: [0.0, 1.0, 0.0],
+            _padding: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::entity::spatial_transform::SpatialTransform, graphics::gpu::test_context, systems::camera::{CameraUniform, create_camera_bind_group}};
+    use super::*;
+
+    /// Draws a full-screen triangle at the far plane where the depth is exactly 1, since not every adapter can copy depth out.
+    const FAR_PLANE_PROBE_SHADER: &str = "
+        @vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+            let uv = vec2<f32>(f32(i & 1u), f32((i >> 1u) & 1u));
+            return vec4<f32>(uv * 4.0 - 1.0, 1.0, 1.0);
+        }
+        @fragment fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    /// Create a 1x1 color target of the given format.
+    fn color_target(gpu: &GpuContext, format: wgpu::TextureFormat) -> wgpu::Texture {
+        gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("test_color"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Draw 3 vertices with the pipeline + bind groups into the 1x1 color target, loading the existing depth (or clearing it to 1).
+    fn draw(gpu: &GpuContext, pipeline: &GpuPipeline, bind_groups: &[&GpuBindGroup], color: &wgpu::Texture, depth: &DepthTexture, depth_load: wgpu::LoadOp<f32>) {
+        let color_view = color.create_view(&Default::default());
+        let mut encoder = gpu.device().create_command_encoder(&Default::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("test_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth.inner().view(),
+                    depth_ops: Some(wgpu::Operations { load: depth_load, store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            render_pass.set_pipeline(pipeline.handle());
+            for (index, bind_group) in bind_groups.iter().enumerate() {
+                render_pass.set_bind_group(index as u32, bind_group.handle(), &[]);
+            }
+            render_pass.draw(0..3, 0..1);
+        }
+        gpu.queue().submit([encoder.finish()]);
+    }
+
+    /// Read back the 1x1 texture's texel, which is `size` bytes.
+    fn read_texel(gpu: &GpuContext, texture: &wgpu::Texture, size: usize) -> Vec<u8> {
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("test_readback_buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu.device().create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        gpu.queue().submit([encoder.finish()]);
+
+        let slice = buffer.slice(..size as u64);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        gpu.device().poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        slice.get_mapped_range().to_vec()
+    }
+
+    #[test]
+    fn sky_is_drawn_over_cleared_depth_without_writing_it() {
+        let Some(gpu) = test_context() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let mut world = World::new();
+        let sun = world.add_entity(None, vec![], SpatialTransform::identity());
+        let sky = SkyBox::from_sun(&gpu, "test_sky".into(), sun);
+        let camera_buffer = GpuBuffer::create_uniform("test_camera", &gpu, bytemuck::cast_slice(&[CameraUniform::new()]));
+        let camera_bind_group = create_camera_bind_group(&gpu, &camera_buffer);
+        let sky_bind_group = sky.create_bind_group("test_sky_bind_group", &gpu);
+        let pipeline = sky.create_pipeline("test_sky_pipeline", &gpu, &camera_bind_group, &sky_bind_group);
+
+        let color = color_target(&gpu, HdrPipeline::COLOR_FORMAT);
+        let depth = DepthTexture::new(&gpu, "test_depth", 1, 1);
+        draw(&gpu, &pipeline, &[&camera_bind_group, &sky_bind_group], &color, &depth, wgpu::LoadOp::Clear(1.0));
+
+        // the sky passes the depth test at the far plane...
+        let texel = read_texel(&gpu, &color, 8);
+        assert!(texel.iter().any(|byte| *byte != 0), "the sky wasn't drawn over the cleared depth");
+
+        // ...and leaves the depth as it was cleared, which only an `Equal` test at the far plane passes
+        let probe_shader = gpu.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("test_probe_shader"),
+            source: wgpu::ShaderSource::Wgsl(FAR_PLANE_PROBE_SHADER.into()),
+        });
+        let probe_pipeline = GpuPipeline::create(
+            "test_probe_pipeline",
+            &gpu,
+            &[],
+            &[],
+            &probe_shader,
+            &probe_shader,
+            GpuPipelineDescriptor {
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    depth_compare: wgpu::CompareFunction::Equal,
+                    ..SkyBox::depth_stencil_state()
+                }),
+                cull_mode: None,
+                color_targets: vec![GpuPipeline::color_target(wgpu::TextureFormat::Rgba8Unorm)],
+                ..Default::default()
+            },
+        );
+        let probe = color_target(&gpu, wgpu::TextureFormat::Rgba8Unorm);
+        draw(&gpu, &probe_pipeline, &[], &probe, &depth, wgpu::LoadOp::Load);
+        assert_eq!(read_texel(&gpu, &probe, 4), [255; 4], "the depth isn't 1 after drawing the sky");
+    }
+}
@@ -1,9 +1,52 @@
-use crate::{core::world::WorldEntityId, graphics::render::assets::SpriteTextureId};
+use crate::{core::world::WorldEntityId, graphics::{gpu::vertex::Vertex, render::assets::SpriteTextureId}};
 
 /// An instance of a sprite.
 pub struct SpriteInstance {
     texture: SpriteTextureId,
-    entity: WorldEntityId
+    entity: WorldEntityId,
+}
+
+/// The data per sprite instance, for batching sprites through an `InstanceBuffer`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstanceData {
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    /// The texture region as `[min_u, min_v, max_u, max_v]`.
+    pub uv_rect: [f32; 4],
+    pub tint: [f32; 4],
+}
+
+impl Vertex for SpriteInstanceData {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            // Note that we start at location 3 to reserve 0-2 for the quad vertex.
+            attributes: &[
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 3,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                },
+            ],
+        }
+    }
 }
 
 /// The data for a quad vertex.
@@ -14,8 +57,8 @@ pub struct QuadVertex {
     pub uv: [f32; 2],
 }
 
-impl QuadVertex {
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+impl Vertex for QuadVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: size_of::<Self>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
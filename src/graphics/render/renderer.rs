@@ -1,14 +1,17 @@
 use crate::{core::world::World, graphics::{
     constants::{
-        INDEX_BUFFER_FORMAT, INSTANCE_BUFFER_SLOT, MESH_CAMERA_BIND_GROUP_SLOT, MESH_LIGHTING_BIND_GROUP_SLOT, MESH_MATERIAL_BIND_GROUP_SLOT, SKYBOX_CAMERA_BIND_GROUP_SLOT, SKYBOX_CUBEMAP_BIND_GROUP_SLOT, VERTEX_BUFFER_SLOT
+        INDEX_BUFFER_FORMAT, INSTANCE_BUFFER_SLOT, MESH_CAMERA_BIND_GROUP_SLOT, MESH_CLUSTER_BIND_GROUP_SLOT, MESH_LIGHTING_BIND_GROUP_SLOT, MESH_MATERIAL_BIND_GROUP_SLOT, SECONDARY_INSTANCE_BUFFER_SLOT, SKYBOX_CAMERA_BIND_GROUP_SLOT, SKYBOX_CUBEMAP_BIND_GROUP_SLOT, VERTEX_BUFFER_SLOT
     },
-    gpu::{GpuContext, bind_group::GpuBindGroup, pipeline::GpuPipeline, texture::GpuTexture},
+    gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, pipeline::GpuPipeline, texture::GpuTexture},
     render::{
         assets::{AssetStore, MeshId},
-        commands::{DrawCommand, MeshRenderCommand, SkyboxRenderCommand}, hdr::HdrPipeline,
+        renderable::model::MeshInstance,
+        commands::{DrawCommand, MeshRenderCommand, RawRenderCommand, RenderCommandBuffer, SkyboxRenderCommand}, clustered::{ClusterConfig, ClusteredLighting}, debug_view::{DebugView, DebugViewPipelines}, deferred::DeferredPipeline, graph::{BuiltinPass, GraphPassContext, PassKind, RenderGraph, RenderGraphError}, grid::GridPipeline, hdr::HdrPipeline, label::{ScreenLabel, WorldLabel}, reflection::{PlanarReflection, ReflectionPlane},
     },
-    scene::{Scene, SceneError, instance_buffer::InstanceBuffer}, textures::depth::DepthTexture,
-}};
+    scene::{Scene, SceneDepth, SceneError, instance_buffer::{MeshInstanceBuffer, MeshInstanceData, SecondaryInstanceBuffer}}, textures::depth::DepthTexture,
+}, systems::camera::{CameraUniform, create_camera_bind_group}};
+use std::cell::Cell;
+use cgmath::Vector3;
 use slotmap::{SlotMap, new_key_type};
 use thiserror::Error;
 use wgpu::{CommandEncoder, RenderPass, SurfaceTexture, TextureView};
@@ -20,6 +23,60 @@ new_key_type! {
     pub struct BindGroupId;
 }
 
+/// The color the frame is cleared to before drawing the scene.
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// How the renderer draws scenes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderingMode {
+    /// Each mesh is lit while it's drawn, by its own pipeline (ie `shader.wgsl`).
+    Forward,
+    /// Meshes are drawn into a G-buffer, which is then lit once per pixel in a fullscreen pass.
+    ///
+    /// This scales much better with many lights, since each pixel is only lit once regardless of overdraw.
+    /// Meshes are drawn with the deferred geometry pipelines rather than their own, and alpha blending isn't supported.
+    Deferred,
+    /// Like forward, but point lights are first assigned to clusters of the view frustum in a compute pass,
+    /// so each fragment only iterates the ones reaching it (see `ClusteredLighting`).
+    ///
+    /// Meshes are drawn with the clustered pipelines rather than their own, and point lights are limited to the
+    /// `ClusterConfig`'s light range.
+    Clustered,
+}
+
+/// Counts of what was drawn in a frame, ie for performance tuning.
+///
+/// This covers the scene's meshes + skybox and raw commands, but not the renderer's internal fullscreen passes.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub instances: u32,
+    pub triangles: u64,
+}
+
+impl FrameStats {
+    /// Stats with nothing drawn.
+    pub fn zeroed() -> Self {
+        Self {
+            draw_calls: 0,
+            instances: 0,
+            triangles: 0,
+        }
+    }
+
+    /// Count a draw with the given number of elements (ie indices or vertices) and instances.
+    fn record_draw(&mut self, elements: u32, instances: u32) {
+        self.draw_calls += 1;
+        self.instances += instances;
+        self.triangles += (elements / 3) as u64 * instances as u64;
+    }
+}
+
 /// Data for a currently rendering frame.
 struct CurrentFrameData {
     output: SurfaceTexture,
@@ -33,12 +90,29 @@ pub struct Renderer<'a> {
     surface_config: wgpu::SurfaceConfiguration,
     surface_is_configured: bool,
     depth_texture: DepthTexture,
-    instance_buffer: InstanceBuffer,
+    instance_buffer: MeshInstanceBuffer,
+    secondary_instance_buffer: SecondaryInstanceBuffer,
     assets: AssetStore,
     hdr: HdrPipeline,
+    hdr_enabled: bool,
+    render_scale: f32,
+    upscale_filter: wgpu::FilterMode,
+    rendering_mode: RenderingMode,
+    deferred: Option<DeferredPipeline>,
+    clustered: Option<ClusteredLighting>,
+    cluster_config: ClusterConfig,
+    graph: RenderGraph,
+    layer_mask: u32,
+    reflection: Option<PlanarReflection>,
+    debug_view: Option<DebugView>,
+    debug_view_pipelines: Option<DebugViewPipelines>,
+    grid: GridPipeline,
     pipelines: SlotMap<PipelineId, GpuPipeline>,
     bind_groups: SlotMap<BindGroupId, GpuBindGroup>,
-    current_frame: Option<CurrentFrameData>
+    current_frame: Option<CurrentFrameData>,
+    pending_resize: Option<(u32, u32)>,
+    world_labels: Vec<WorldLabel>,
+    frame_stats: Cell<FrameStats>,
 }
 
 impl<'a> Renderer<'a> {
@@ -49,9 +123,12 @@ impl<'a> Renderer<'a> {
         surface_config: wgpu::SurfaceConfiguration,
         assets: AssetStore,
     ) -> Self {
-        let depth_texture = DepthTexture::new(&gpu, "depth_texture", &surface_config);
-        let instance_buffer = InstanceBuffer::new(gpu.clone(), "instance_buffer".into());
-        let hdr = HdrPipeline::new(&gpu, &surface_config);
+        let depth_texture = DepthTexture::new(&gpu, "depth_texture", surface_config.width, surface_config.height);
+        let instance_buffer = MeshInstanceBuffer::new(gpu.clone(), "instance_buffer".into());
+        // most scenes don't use the secondary stream, so this starts small + grows on demand
+        let secondary_instance_buffer = SecondaryInstanceBuffer::with_capacity(gpu.clone(), "secondary_instance_buffer".into(), 1);
+        let hdr = HdrPipeline::new(&gpu, surface_config.width, surface_config.height, surface_config.format);
+        let grid = GridPipeline::new(&gpu, HdrPipeline::COLOR_FORMAT);
         Self {
             gpu,
             surface,
@@ -59,25 +136,402 @@ impl<'a> Renderer<'a> {
             surface_is_configured: false,
             depth_texture,
             instance_buffer,
+            secondary_instance_buffer,
             assets,
             hdr,
+            hdr_enabled: true,
+            render_scale: 1.0,
+            upscale_filter: wgpu::FilterMode::Linear,
+            rendering_mode: RenderingMode::Forward,
+            deferred: None,
+            clustered: None,
+            cluster_config: ClusterConfig::new(Self::DEFAULT_CLUSTER_LIGHT_RANGE),
+            graph: RenderGraph::new(),
+            layer_mask: MeshInstance::ALL_LAYERS,
+            reflection: None,
+            debug_view: None,
+            debug_view_pipelines: None,
+            grid,
             pipelines: SlotMap::with_key(),
             bind_groups: SlotMap::with_key(),
-            current_frame: None
+            current_frame: None,
+            pending_resize: None,
+            world_labels: Vec::new(),
+            frame_stats: Cell::new(FrameStats::zeroed()),
         }
     }
 
+    /// How far point lights reach in clustered rendering, until set otherwise.
+    pub const DEFAULT_CLUSTER_LIGHT_RANGE: f32 = 20.0;
+
+    /// The highest frame latency that can be set; see `set_frame_latency`.
+    pub const MAX_FRAME_LATENCY: u32 = 3;
+
+    /// The highest render scale that can be set; see `set_render_scale`.
+    pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+    /// The minimum width/height the surface is configured at.
+    pub const MIN_SURFACE_SIZE: u32 = 16;
+
     /// Handle resizing of the surface.
+    ///
+    /// The resize is deferred until the next `begin_frame`, so that rapid resizes (ie while dragging the window)
+    /// only reconfigure the surface once per frame.
+    ///
+    /// Sizes below `MIN_SURFACE_SIZE` (ie a minimized window) are ignored, rather than configuring the surface
+    /// larger than the window; the surface keeps its last size until the window grows again.
     pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.surface_config.width = width;
-            self.surface_config.height = height;
-            self.surface
-                .configure(&self.gpu.device(), &self.surface_config);
-            self.surface_is_configured = true;
-            self.depth_texture = DepthTexture::new(&self.gpu, "depth_texture", &self.surface_config);
-            self.hdr.resize(&self.gpu, width, height);
+        if width >= Self::MIN_SURFACE_SIZE && height >= Self::MIN_SURFACE_SIZE {
+            self.pending_resize = Some((width, height));
+        }
+    }
+
+    /// Whether the renderer can render a frame, ie the surface is configured.
+    ///
+    /// A pending resize counts, since it configures the surface at the start of the next frame.
+    /// Until then, the frame methods return `RenderError::UnconfiguredSurface`.
+    pub fn is_ready(&self) -> bool {
+        self.surface_is_configured || self.pending_resize.is_some()
+    }
+
+    /// Reconfigure the surface at its current size, ie to recover from it being lost or outdated.
+    pub fn reconfigure(&mut self) {
+        self.surface
+            .configure(self.gpu.device(), &self.surface_config);
+        self.surface_is_configured = true;
+    }
+
+    /// Apply the pending resize, if there is one.
+    ///
+    /// The surface + size-dependent textures are only recreated if the size actually changed.
+    fn apply_pending_resize(&mut self) {
+        let Some((width, height)) = self.pending_resize.take() else {
+            return;
+        };
+        if self.surface_is_configured
+            && (width, height) == (self.surface_config.width, self.surface_config.height)
+        {
+            return;
+        }
+
+        self.surface_config.width = width;
+        self.surface_config.height = height;
+        self.surface
+            .configure(self.gpu.device(), &self.surface_config);
+        self.surface_is_configured = true;
+        self.resize_render_targets();
+    }
+
+    /// Recreate the size-dependent textures for the current `render_size`.
+    fn resize_render_targets(&mut self) {
+        let (width, height) = self.render_size();
+        self.depth_texture = DepthTexture::new(&self.gpu, "depth_texture", width, height);
+        self.hdr.resize(&self.gpu, width, height);
+        if let Some(deferred) = &mut self.deferred {
+            deferred.resize(&self.gpu, width, height);
+        }
+        let format = self.target_format();
+        if let Some(reflection) = &mut self.reflection {
+            reflection.resize(&self.gpu, width, height, format);
+        }
+    }
+
+    /// Get the format of the surface, ie what the final output is written as.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.surface_config.format
+    }
+
+    /// Read the depth at the given pixel, as of the last rendered frame.
+    ///
+    /// The depth is normalized (ie 0 at the near plane and 1 at the far plane/sky), so unprojecting it
+    /// with the camera's inverse view-projection gives the world position under the pixel.
+    ///
+    /// ## Note
+    /// This waits for the GPU to finish all submitted work, so it's best used sparingly (ie on click rather than every frame).
+    /// On the web the readback can't be waited on, so this returns `RenderError::DepthReadback`.
+    pub fn read_depth_at(&self, x: u32, y: u32) -> RenderResult<f32> {
+        if !self.surface_is_configured {
+            return Err(RenderError::UnconfiguredSurface);
+        }
+        if x >= self.surface_config.width || y >= self.surface_config.height {
+            return Err(RenderError::DepthOutOfBounds { x, y });
+        }
+        // the depth texture is at the render size, so find the texel under the surface pixel
+        let (render_width, render_height) = self.render_size();
+        let texel_x = (x as u64 * render_width as u64 / self.surface_config.width as u64) as u32;
+        let texel_y = (y as u64 * render_height as u64 / self.surface_config.height as u64) as u32;
+
+        // a single row doesn't need padding, but buffer copies are still aligned to 256 bytes
+        let device = self.gpu.device();
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("depth_readback_buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("depth_readback_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: self.depth_texture.inner().handle(),
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: texel_x, y: texel_y, z: 0 },
+                aspect: wgpu::TextureAspect::DepthOnly,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        self.gpu.queue().submit([encoder.finish()]);
+
+        let slice = buffer.slice(..size_of::<f32>() as u64);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|err| RenderError::DepthReadback(err.to_string()))?;
+        receiver
+            .try_recv()
+            .map_err(|_| RenderError::DepthReadback("the buffer wasn't mapped".into()))?
+            .map_err(|err| RenderError::DepthReadback(err.to_string()))?;
+
+        let depth = bytemuck::pod_read_unaligned::<f32>(&slice.get_mapped_range());
+        buffer.unmap();
+        Ok(depth)
+    }
+
+    /// Get the format of the HDR texture, ie what scene pipelines should render to.
+    pub fn hdr_format(&self) -> wgpu::TextureFormat {
+        HdrPipeline::COLOR_FORMAT
+    }
+
+    /// Whether rendering goes through the HDR texture + tonemapping pass.
+    pub fn hdr_enabled(&self) -> bool {
+        self.hdr_enabled
+    }
+
+    /// Set whether rendering goes through the HDR texture + tonemapping pass.
+    ///
+    /// When disabled, everything renders directly to the surface, so the added pipelines' `hdr_format` targets
+    /// are recreated as `surface_format`, and back again when re-enabled.
+    pub fn set_hdr_enabled(&mut self, enabled: bool) {
+        if self.hdr_enabled == enabled {
+            return;
+        }
+        let previous_format = self.target_format();
+        self.hdr_enabled = enabled;
+        self.apply_target_format(previous_format);
+        // the render scale only applies with HDR, so the render size may have changed
+        if self.surface_is_configured && self.render_scale != 1.0 {
+            self.resize_render_targets();
+        }
+    }
+
+    /// Get the scale of the size the scene is rendered at, relative to the surface.
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Set the scale of the size the scene is rendered at relative to the surface, ie below 1 for performance or pixel art.
+    ///
+    /// The scene (including its depth, the G-buffer and the reflection target) is rendered at `render_size`,
+    /// then stretched over the surface by the tonemapping pass (see `set_upscale_filter`).
+    /// This must be in `(0, MAX_RENDER_SCALE]`, and only applies while HDR is enabled, since otherwise the scene
+    /// renders directly to the surface.
+    pub fn set_render_scale(&mut self, scale: f32) -> RenderResult<()> {
+        if !(scale > 0.0 && scale <= Self::MAX_RENDER_SCALE) {
+            return Err(RenderError::InvalidRenderScale(scale));
+        }
+        if self.render_scale != scale {
+            self.render_scale = scale;
+            if self.surface_is_configured {
+                self.resize_render_targets();
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the filter used when stretching the rendered scene over the surface.
+    pub fn upscale_filter(&self) -> wgpu::FilterMode {
+        self.upscale_filter
+    }
+
+    /// Set the filter used when stretching the rendered scene over the surface; `Nearest` for pixel art, otherwise `Linear`.
+    pub fn set_upscale_filter(&mut self, filter: wgpu::FilterMode) {
+        self.upscale_filter = filter;
+        self.hdr.set_upscale_filter(&self.gpu, filter);
+    }
+
+    /// Set the surface's format (ie after the window moved to a display with other formats), reconfiguring it.
+    ///
+    /// The tonemapping pass is rebuilt for the new format, as are the built-in passes + added pipelines if HDR is disabled.
+    pub fn set_surface_format(&mut self, format: wgpu::TextureFormat) {
+        if self.surface_config.format == format {
+            return;
+        }
+        let previous_format = self.surface_config.format;
+        self.surface_config.format = format;
+        self.hdr.recreate_for_format(&self.gpu, format);
+        if !self.hdr_enabled {
+            self.apply_target_format(previous_format);
+        }
+        if self.surface_is_configured {
+            self.reconfigure();
+        }
+    }
+
+    /// Get the surface's present mode, ie whether it's vsynced (`Fifo`) or uncapped (`Immediate`/`Mailbox`).
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    /// Set the surface's present mode (ie to toggle vsync), reconfiguring it.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        if self.surface_config.present_mode == present_mode {
+            return;
+        }
+        self.surface_config.present_mode = present_mode;
+        if self.surface_is_configured {
+            self.reconfigure();
+        }
+    }
+
+    /// Set the color grading LUT applied after tonemapping, or `None` to leave colors unchanged.
+    ///
+    /// See `HdrPipeline::set_color_lut` for the LUT's layout; it isn't applied while HDR is disabled.
+    pub fn set_color_lut(&mut self, lut: Option<&GpuTexture>) {
+        self.hdr.set_color_lut(&self.gpu, lut);
+    }
+
+    /// Get the maximum number of frames queued ahead of the one being displayed.
+    pub fn frame_latency(&self) -> u32 {
+        self.surface_config.desired_maximum_frame_latency
+    }
+
+    /// Set the maximum number of frames queued ahead of the one being displayed, reconfiguring the surface.
+    ///
+    /// Lower values reduce input latency at the cost of throughput. This must be in `1..=MAX_FRAME_LATENCY`,
+    /// and is a hint; the backend may clamp it further.
+    pub fn set_frame_latency(&mut self, latency: u32) -> RenderResult<()> {
+        if !(1..=Self::MAX_FRAME_LATENCY).contains(&latency) {
+            return Err(RenderError::InvalidFrameLatency(latency));
+        }
+        if self.surface_config.desired_maximum_frame_latency != latency {
+            self.surface_config.desired_maximum_frame_latency = latency;
+            if self.surface_is_configured {
+                self.reconfigure();
+            }
+        }
+        Ok(())
+    }
+
+    /// Recreate the built-in passes' pipelines + targets for the current `target_format`,
+    /// along with the added pipelines that wrote to the `previous` one.
+    fn apply_target_format(&mut self, previous: wgpu::TextureFormat) {
+        let format = self.target_format();
+        for pipeline in self.pipelines.values_mut() {
+            if let Some(recreated) = pipeline.with_color_format(&self.gpu, previous, format) {
+                *pipeline = recreated;
+            }
+        }
+        self.grid.set_color_format(&self.gpu, format);
+        if let Some(deferred) = &mut self.deferred {
+            deferred.set_output_format(&self.gpu, format);
+        }
+        if let Some(clustered) = &mut self.clustered {
+            clustered.set_output_format(&self.gpu, format);
+        }
+        if let Some(debug_view_pipelines) = &mut self.debug_view_pipelines {
+            debug_view_pipelines.set_color_format(&self.gpu, format);
+        }
+        let (width, height) = self.render_size();
+        if let Some(reflection) = &mut self.reflection {
+            reflection.resize(&self.gpu, width, height, format);
+        }
+    }
+
+    /// Get how scenes are rendered.
+    pub fn rendering_mode(&self) -> RenderingMode {
+        self.rendering_mode
+    }
+
+    /// Set how scenes are rendered.
+    ///
+    /// The G-buffer for deferred rendering and the clusters for clustered rendering are only created the first time
+    /// they're used, and kept afterwards. Overlay scenes (see `render_overlay_scene`) are always rendered forward.
+    pub fn set_rendering_mode(&mut self, mode: RenderingMode) {
+        match mode {
+            RenderingMode::Deferred if self.deferred.is_none() => {
+                let (width, height) = self.render_size();
+                self.deferred = Some(DeferredPipeline::new(&self.gpu, width, height, self.target_format()));
+            }
+            RenderingMode::Clustered if self.clustered.is_none() => {
+                self.clustered = Some(ClusteredLighting::new(&self.gpu, self.cluster_config, self.target_format()));
+            }
+            _ => {}
+        }
+        self.rendering_mode = mode;
+    }
+
+    /// Get the debug view meshes are drawn with, if any.
+    pub fn debug_view(&self) -> Option<DebugView> {
+        self.debug_view
+    }
+
+    /// Draw meshes with a debug view (ie their normals) instead of their own shading, or `None` to go back to it.
+    ///
+    /// While set, meshes are drawn like in forward rendering regardless of the rendering mode.
+    pub fn set_debug_view(&mut self, view: Option<DebugView>) {
+        if view.is_some() && self.debug_view_pipelines.is_none() {
+            self.debug_view_pipelines = Some(DebugViewPipelines::new(&self.gpu, self.target_format()));
         }
+        self.debug_view = view;
+    }
+
+    /// Get the config for clustered rendering.
+    pub fn cluster_config(&self) -> ClusterConfig {
+        self.cluster_config
+    }
+
+    /// Set the config for clustered rendering, ie its cluster dimensions + light range.
+    pub fn set_cluster_config(&mut self, config: ClusterConfig) {
+        self.cluster_config = config;
+        if let Some(clustered) = &mut self.clustered {
+            clustered.set_config(&self.gpu, config);
+        }
+    }
+
+    /// Get the format that passes render to; the HDR format, or the surface format if HDR is disabled.
+    pub fn target_format(&self) -> wgpu::TextureFormat {
+        match self.hdr_enabled {
+            true => self.hdr_format(),
+            false => self.surface_format(),
+        }
+    }
+
+    /// Get the current size of the surface as `(width, height)`.
+    pub fn size(&self) -> (u32, u32) {
+        (self.surface_config.width, self.surface_config.height)
+    }
+
+    /// Get the size the scene is rendered at as `(width, height)`, ie the surface's size scaled by the render scale.
+    ///
+    /// Without HDR, this is always the surface's size.
+    pub fn render_size(&self) -> (u32, u32) {
+        if !self.hdr_enabled {
+            return self.size();
+        }
+        let scale = |size: u32| ((size as f32 * self.render_scale).round() as u32).max(1);
+        (scale(self.surface_config.width), scale(self.surface_config.height))
     }
 
     /// Add the pipelines to the renderer and get back their IDs for referencing.
@@ -96,6 +550,38 @@ impl<'a> Renderer<'a> {
             .collect()
     }
 
+    /// Replace the referenced bind group (ie to swap a material's textures), returning the old one.
+    ///
+    /// Returns `None` and doesn't add the group if the ID doesn't exist.
+    pub fn replace_bind_group(&mut self, id: BindGroupId, group: GpuBindGroup) -> Option<GpuBindGroup> {
+        self.bind_groups
+            .get_mut(id)
+            .map(|cur_group| std::mem::replace(cur_group, group))
+    }
+
+    /// Remove the referenced pipeline, ie when the scene using it is dropped.
+    ///
+    /// Any command still referencing it fails with `RenderError::PipelineNotFound`.
+    pub fn remove_pipeline(&mut self, id: PipelineId) -> Option<GpuPipeline> {
+        self.pipelines.remove(id)
+    }
+
+    /// Remove the referenced bind group, ie when the scene using it is dropped.
+    ///
+    /// Any command still referencing it fails with `RenderError::GlobalBindGroupNotFound`.
+    pub fn remove_bind_group(&mut self, id: BindGroupId) -> Option<GpuBindGroup> {
+        self.bind_groups.remove(id)
+    }
+
+    /// Finish any pending texture loads in the asset store, and swap their materials' bind groups in.
+    ///
+    /// See `AssetStore::load_material_textures`; this should be called each frame while there are pending loads.
+    pub fn poll_pending_assets(&mut self) {
+        for (id, group) in self.assets.poll_pending(&self.gpu) {
+            self.replace_bind_group(id, group);
+        }
+    }
+
     /// Get the referenced pipeline.
     pub fn get_pipeline(&self, id: PipelineId, command_label: &str) -> RenderResult<&GpuPipeline> {
         self.pipelines
@@ -110,13 +596,48 @@ impl<'a> Renderer<'a> {
             .ok_or(RenderError::GlobalBindGroupNotFound { label: command_label.into() })
     }
 
+    /// Toggle and configure the infinite ground grid on the y = 0 plane.
+    ///
+    /// `spacing` is the world-space distance between lines.
+    pub fn set_grid(&mut self, enabled: bool, spacing: f32, color: [f32; 4]) {
+        self.grid.set(&self.gpu, enabled, spacing, color);
+    }
+
+    /// Queue a text label at a position in the world, to be drawn as a screen-space billboard this frame.
+    ///
+    /// Labels are flushed by `take_screen_labels`, and are culled if they're behind the camera.
+    pub fn draw_world_label(&mut self, text: impl Into<String>, world_position: Vector3<f32>, color: [f32; 4]) {
+        self.world_labels.push(WorldLabel {
+            text: text.into(),
+            world_position,
+            color,
+        });
+    }
+
+    /// Take the queued world labels, projected to screen space through the scene's camera.
+    pub fn take_screen_labels(&mut self, scene: &Scene) -> Vec<ScreenLabel> {
+        let view_proj = scene.camera().uniform_view_proj();
+        let (width, height) = self.size();
+        self.world_labels
+            .drain(..)
+            .filter_map(|label| label.to_screen(view_proj, width, height))
+            .collect()
+    }
+
     /// Get the assets store.
     pub fn get_assets_store(&mut self) -> &mut AssetStore {
         &mut self.assets
     }
 
     /// Begin a frame for rendering.
+    ///
+    /// This also applies any pending resize, and resets the frame stats.
     pub fn begin_frame(&mut self) -> RenderResult<()> {
+        self.apply_pending_resize();
+        self.frame_stats.set(FrameStats::zeroed());
+        if !self.surface_is_configured {
+            return Err(RenderError::UnconfiguredSurface);
+        }
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -125,28 +646,99 @@ impl<'a> Renderer<'a> {
         Ok(())
     }
 
-    /// End a frame for rendering by displaying it.
+    /// End a frame for rendering by post-processing it, then displaying it.
+    ///
+    /// The render graph's `post` pass (ie tonemapping) and the passes after it run here, once for everything
+    /// drawn in the frame. If any of them fails, the frame is dropped without being presented.
     pub fn end_frame(&mut self) -> RenderResult<()> {
-        if let Some(frame) = self.current_frame.take() {
-            frame.output.present();
+        let Some(frame) = self.current_frame.take() else {
+            return Err(RenderError::NoFrameInProgress);
+        };
+        let (_, order) = self.graph.split_at_post_process(self.graph.execution_order()?);
+        let mut encoder = self.gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("post_process_encoder"),
+            });
+
+        let mut graph = std::mem::replace(&mut self.graph, RenderGraph::new());
+        let result = self.run_graph_passes(&mut graph, &order, &mut encoder, &frame, None);
+        self.graph = graph;
+        result?;
+
+        self.gpu.queue().submit([encoder.finish()]);
+        frame.output.present();
+        Ok(())
+    }
+
+    /// Drop the frame in progress without presenting it, ie after rendering it failed.
+    pub fn discard_frame(&mut self) {
+        self.current_frame = None;
+    }
+
+    /// Render the given scene as a whole frame, ie begin the frame, render the scene, then present it.
+    ///
+    /// This is for the common case; use the granular methods to render other things in the same frame.
+    /// If rendering fails, the frame is dropped without being presented.
+    pub fn draw_scene(&mut self, scene: &Scene, world: &World) -> RenderResult<()> {
+        self.begin_frame()?;
+        if let Err(err) = self.render_scene_for_frame(scene, world) {
+            self.discard_frame();
+            return Err(err);
+        }
+        self.end_frame()
+    }
+
+    /// Render the given scenes in order as a whole frame, ie a 3D world with a 2D UI over it.
+    ///
+    /// The first scene is rendered like in `draw_scene`. The rest are drawn over it into the same target,
+    /// each keeping or clearing the depth as per its `Scene::depth`; only their meshes are drawn (ie not their skybox
+    /// or the grid), always with forward rendering, and the render graph's passes aren't run for them.
+    /// If there are no scenes, nothing is rendered. If rendering fails, the frame is dropped without being presented.
+    pub fn render_frame(&mut self, scenes: &[&Scene], world: &World) -> RenderResult<()> {
+        let Some((first, rest)) = scenes.split_first() else {
             return Ok(());
+        };
+        self.begin_frame()?;
+        let result = self
+            .render_scene_for_frame(first, world)
+            .and_then(|()| {
+                rest.iter().try_for_each(|scene| {
+                    let depth_load = match scene.depth() {
+                        SceneDepth::Keep => wgpu::LoadOp::Load,
+                        SceneDepth::Clear => wgpu::LoadOp::Clear(1.0),
+                    };
+                    self.render_scene_over(scene, world, "layered_render", depth_load)
+                })
+            });
+        if let Err(err) = result {
+            self.discard_frame();
+            return Err(err);
         }
-        Err(RenderError::NoFrameInProgress)
+        self.end_frame()
     }
 
-    /// Render the given scene only for the frame.
+    /// Render the given scene only for the frame, by running the render graph's passes up to `post`,
+    /// which runs in `end_frame`.
     ///
-    /// If any command fails, rendering stops there and this returns a `RenderError`.
+    /// If any command or pass fails, rendering stops there and this returns a `RenderError`.
     pub fn render_scene_for_frame(&mut self, scene: &Scene, world: &World) -> RenderResult<()> {
         if !self.surface_is_configured {
             return Err(RenderError::UnconfiguredSurface);
         }
+        let (order, _) = self.graph.split_at_post_process(self.graph.execution_order()?);
 
         // get the render commands
-        let commands = scene.to_commands(&world, &self.assets, &mut self.instance_buffer)?;
+        let commands = scene.to_commands(
+            world,
+            &self.assets,
+            &mut self.instance_buffer,
+            &mut self.secondary_instance_buffer,
+            self.layer_mask,
+        )?;
         self.instance_buffer.write();
+        self.secondary_instance_buffer.write();
 
-        // get the surface, encoder, render pass
         let frame = match &self.current_frame {
             Some(frame) => frame,
             None => return Err(RenderError::NoFrameInProgress)
@@ -156,24 +748,178 @@ impl<'a> Renderer<'a> {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("render_encoder"),
             });
+
+        // the graph is taken out while it runs, so custom passes can be called alongside the renderer's methods
+        let mut graph = std::mem::replace(&mut self.graph, RenderGraph::new());
+        let result = self.run_graph_passes(&mut graph, &order, &mut encoder, frame, Some((scene, &commands)));
+        self.graph = graph;
+        result?;
+
+        self.gpu.queue().submit([encoder.finish()]);
+        self.instance_buffer.clear();
+        self.secondary_instance_buffer.clear();
+
+        Ok(())
+    }
+
+    /// Get the counts of what's been drawn since the frame began.
+    ///
+    /// Between frames (ie while updating), this is everything drawn in the last frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats.get()
+    }
+
+    /// Recreate the mesh instance buffer with room for the given number of instances, ie to save memory on small targets.
+    ///
+    /// It still grows when a frame has more instances than that.
+    pub fn set_instance_buffer_capacity(&mut self, capacity: u64) {
+        self.instance_buffer.handle().handle().destroy();
+        self.instance_buffer = MeshInstanceBuffer::with_capacity(self.gpu.clone(), "instance_buffer".into(), capacity);
+    }
+
+    /// Get the mask of the layers whose mesh instances are drawn.
+    pub fn layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    /// Set the mask of the layers whose mesh instances are drawn, ie to exclude some from a render.
+    ///
+    /// This applies to every scene rendered until it's set again; it's `MeshInstance::ALL_LAYERS` by default.
+    pub fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+
+    /// Get the render graph, ie for adding custom passes.
+    pub fn graph_mut(&mut self) -> &mut RenderGraph {
+        &mut self.graph
+    }
+
+    /// Render the given scene on top of what has already been rendered for the frame, ie for an FPS viewmodel.
+    ///
+    /// The depth buffer is cleared first, so the scene never clips into what's underneath it.
+    /// Only the scene's meshes are drawn (ie not its skybox or the grid), always with forward rendering,
+    /// and the render graph's passes aren't run.
+    /// If any command fails, rendering stops there and this returns a `RenderError`.
+    pub fn render_overlay_scene(&mut self, scene: &Scene, world: &World) -> RenderResult<()> {
+        self.render_scene_over(scene, world, "overlay_render", wgpu::LoadOp::Clear(1.0))
+    }
+
+    /// Draw the scene's meshes over what has already been rendered for the frame.
+    ///
+    /// The `label` prefixes the encoder + render pass labels.
+    fn render_scene_over(&mut self, scene: &Scene, world: &World, label: &str, depth_load: wgpu::LoadOp<f32>) -> RenderResult<()> {
+        if !self.surface_is_configured {
+            return Err(RenderError::UnconfiguredSurface);
+        }
+
+        // get the render commands
+        let commands = scene.to_commands(
+            world,
+            &self.assets,
+            &mut self.instance_buffer,
+            &mut self.secondary_instance_buffer,
+            self.layer_mask,
+        )?;
+        self.instance_buffer.write();
+        self.secondary_instance_buffer.write();
+
+        // get the surface, encoder, render pass
+        let frame = match &self.current_frame {
+            Some(frame) => frame,
+            None => return Err(RenderError::NoFrameInProgress)
+        };
+        let mut encoder = self.gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(&format!("{label}_encoder")),
+            });
+        let mut render_pass = self.begin_scene_pass(&mut encoder, frame, &format!("{label}_pass"), wgpu::LoadOp::Load, depth_load);
+        for command in commands.mesh {
+            let pipeline = self.get_pipeline(command.pipeline, command.name)?;
+            self.write_mesh_command(&command, pipeline, &mut render_pass)?
+        }
+        drop(render_pass);
+
+        self.gpu.queue().submit([encoder.finish()]);
+        self.instance_buffer.clear();
+        self.secondary_instance_buffer.clear();
+
+        Ok(())
+    }
+
+    /// Render the scene mirrored across the plane into an offscreen texture, and return it; ie for water or mirrors.
+    ///
+    /// The texture is the renderer's size and `target_format`, and is meant to be sampled at screen-space UVs
+    /// by the reflective surface (see `PlanarReflection::create_surface_pipeline`), so this should be called each frame
+    /// before the scene itself is rendered. There's a single reflection target, so only one plane is supported.
+    ///
+    /// Only the skybox and meshes are drawn, always with forward rendering, and the render graph's passes aren't run.
+    /// The layer mask still applies, ie to exclude the reflective surface itself.
+    /// The texture is replaced when the renderer is resized or HDR is toggled.
+    pub fn render_reflection(&mut self, scene: &Scene, world: &World, plane: ReflectionPlane) -> RenderResult<GpuTexture> {
+        if !self.surface_is_configured {
+            return Err(RenderError::UnconfiguredSurface);
+        }
+
+        // the target is only created the first time a reflection is rendered
+        if self.reflection.is_none() {
+            let camera_buffer = GpuBuffer::create_uniform(
+                "reflection_camera_buffer",
+                &self.gpu,
+                bytemuck::cast_slice(&[CameraUniform::new()]),
+            );
+            let camera_bind_group = self.bind_groups.insert(create_camera_bind_group(&self.gpu, &camera_buffer));
+            let (width, height) = self.render_size();
+            self.reflection = Some(PlanarReflection::new(
+                &self.gpu,
+                width,
+                height,
+                self.target_format(),
+                camera_buffer,
+                camera_bind_group,
+            ));
+        }
+        let Some(reflection) = &self.reflection else {
+            unreachable!("The reflection target was just created");
+        };
+        let camera = scene.camera();
+        reflection.write_camera(&self.gpu, camera.view_matrix(world), camera.projection_matrix(), &plane);
+
+        // get the render commands, drawn with the mirrored camera
+        let mut commands = scene.to_commands(
+            world,
+            &self.assets,
+            &mut self.instance_buffer,
+            &mut self.secondary_instance_buffer,
+            self.layer_mask,
+        )?;
+        self.instance_buffer.write();
+        self.secondary_instance_buffer.write();
+        for command in &mut commands.mesh {
+            command.camera_bind_group = reflection.camera_bind_group();
+        }
+        if let Some(command) = &mut commands.skybox {
+            command.camera_bind_group = reflection.camera_bind_group();
+        }
+
+        let mut encoder = self.gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("reflection_render_encoder"),
+            });
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("render_pass"),
+            label: Some("reflection_render_pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.hdr.texture().view(),
+                view: reflection.texture().view(),
                 resolve_target: None,
                 depth_slice: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
+                    load: wgpu::LoadOp::Clear(CLEAR_COLOR),
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.inner().view(),
+                view: reflection.depth_texture().view(),
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: wgpu::StoreOp::Store,
@@ -183,20 +929,236 @@ impl<'a> Renderer<'a> {
             occlusion_query_set: None,
             timestamp_writes: None,
         });
-
-        // write the render commands
-        if let Some(command) = &commands.skybox { 
-            self.write_skybox_command(&command, &mut render_pass)?
+        if let Some(command) = &commands.skybox {
+            self.write_skybox_command(command, &mut render_pass)?
         }
-        for command in commands.mesh {
-            self.write_mesh_command(&command, &mut render_pass)?
+        for command in &commands.mesh {
+            let pipeline = self.get_pipeline(command.pipeline, command.name)?;
+            self.write_mesh_command(command, pipeline, &mut render_pass)?
         }
         drop(render_pass);
 
-        // process the HDR view into the final surface view and submit the queue
-        self.hdr.process(&mut encoder, &frame.view);
         self.gpu.queue().submit([encoder.finish()]);
         self.instance_buffer.clear();
+        self.secondary_instance_buffer.clear();
+
+        Ok(reflection.texture().clone())
+    }
+
+    /// Run the graph's passes with the given indices in order; the scene is only needed for the built-in passes drawing it.
+    fn run_graph_passes(
+        &self,
+        graph: &mut RenderGraph,
+        order: &[usize],
+        encoder: &mut CommandEncoder,
+        frame: &CurrentFrameData,
+        scene: Option<(&Scene, &RenderCommandBuffer)>,
+    ) -> RenderResult<()> {
+        order.iter().try_for_each(|&index| {
+            match &mut graph.passes_mut()[index].kind {
+                PassKind::Builtin(BuiltinPass::PostProcess) => {
+                    self.resolve_to_surface(encoder, frame);
+                    Ok(())
+                }
+                PassKind::Builtin(pass) => {
+                    let Some((scene, commands)) = scene else {
+                        unreachable!("Only the post pass runs without a scene");
+                    };
+                    self.run_builtin_pass(*pass, encoder, frame, scene, commands)
+                }
+                PassKind::Custom(pass) => pass(&mut GraphPassContext {
+                    gpu: &self.gpu,
+                    encoder,
+                    scene_color: self.color_target(frame),
+                    scene_depth: self.depth_texture.inner().view(),
+                    surface: &frame.view,
+                    scene_color_format: self.target_format(),
+                }),
+            }
+        })
+    }
+
+    /// Run one of the built-in passes.
+    fn run_builtin_pass(
+        &self,
+        pass: BuiltinPass,
+        encoder: &mut CommandEncoder,
+        frame: &CurrentFrameData,
+        scene: &Scene,
+        commands: &RenderCommandBuffer,
+    ) -> RenderResult<()> {
+        match pass {
+            BuiltinPass::Skybox => {
+                let mut render_pass = self.begin_scene_pass(encoder, frame, "skybox_pass", wgpu::LoadOp::Clear(CLEAR_COLOR), wgpu::LoadOp::Clear(1.0));
+                if let Some(command) = &commands.skybox {
+                    self.write_skybox_command(command, &mut render_pass)?
+                }
+            }
+            BuiltinPass::Opaque if self.debug_view.is_some() => {
+                let (Some(view), Some(pipelines)) = (self.debug_view, &self.debug_view_pipelines) else {
+                    unreachable!("The debug view pipelines are created when setting a debug view");
+                };
+                let mut render_pass = self.begin_scene_pass(encoder, frame, "debug_view_pass", wgpu::LoadOp::Load, wgpu::LoadOp::Load);
+                for command in &commands.mesh {
+                    self.write_mesh_command(command, pipelines.pipeline(view), &mut render_pass)?
+                }
+            }
+            BuiltinPass::Opaque => match self.rendering_mode {
+                RenderingMode::Forward => {
+                    let mut render_pass = self.begin_scene_pass(encoder, frame, "opaque_pass", wgpu::LoadOp::Load, wgpu::LoadOp::Load);
+                    for command in &commands.mesh {
+                        let pipeline = self.get_pipeline(command.pipeline, command.name)?;
+                        self.write_mesh_command(command, pipeline, &mut render_pass)?
+                    }
+                }
+                RenderingMode::Clustered => {
+                    let Some(clustered) = &self.clustered else {
+                        unreachable!("The clusters are created when switching to clustered rendering");
+                    };
+                    // assign the lights to clusters first
+                    clustered.assign_lights(
+                        &self.gpu,
+                        encoder,
+                        scene.camera(),
+                        self.render_size(),
+                        self.get_bind_group(scene.camera_bind_group(), "cluster_assign")?,
+                        self.get_bind_group(scene.lighting_bind_group(), "cluster_assign")?,
+                    );
+                    let mut render_pass = self.begin_scene_pass(encoder, frame, "opaque_pass", wgpu::LoadOp::Load, wgpu::LoadOp::Load);
+                    render_pass.set_bind_group(MESH_CLUSTER_BIND_GROUP_SLOT, clustered.shading_bind_group().handle(), &[]);
+                    for command in &commands.mesh {
+                        self.write_mesh_command(command, clustered.mesh_pipeline(command.double_sided), &mut render_pass)?
+                    }
+                }
+                RenderingMode::Deferred => {
+                    let Some(deferred) = &self.deferred else {
+                        unreachable!("The deferred pipeline is created when switching to deferred rendering");
+                    };
+                    // draw the meshes into the G-buffer, then light it over the sky
+                    let mut geometry_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("deferred_geometry_pass"),
+                        color_attachments: &deferred.color_attachments(),
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: self.depth_texture.inner().view(),
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }),
+                        occlusion_query_set: None,
+                        timestamp_writes: None,
+                    });
+                    for command in &commands.mesh {
+                        let pipeline = deferred.geometry_pipeline(command.double_sided);
+                        self.write_mesh_command(command, pipeline, &mut geometry_pass)?
+                    }
+                    drop(geometry_pass);
+                    deferred.light(
+                        encoder,
+                        self.color_target(frame),
+                        self.get_bind_group(scene.camera_bind_group(), "deferred_lighting")?,
+                        self.get_bind_group(scene.lighting_bind_group(), "deferred_lighting")?,
+                    );
+                }
+            },
+            BuiltinPass::Grid => {
+                if self.grid.enabled() {
+                    // the grid is blended, so it goes after the opaque meshes
+                    let camera_bind_group = self
+                        .get_bind_group(scene.camera_bind_group(), "grid")?
+                        .handle();
+                    let mut render_pass = self.begin_scene_pass(encoder, frame, "grid_pass", wgpu::LoadOp::Load, wgpu::LoadOp::Load);
+                    self.grid.draw(&mut render_pass, camera_bind_group);
+                }
+            }
+            BuiltinPass::PostProcess => unreachable!("The post pass is run by `run_graph_passes`, without the scene"),
+        }
+        Ok(())
+    }
+
+    /// Begin a render pass over the scene color + depth, with the given load ops.
+    fn begin_scene_pass<'e>(
+        &self,
+        encoder: &'e mut CommandEncoder,
+        frame: &CurrentFrameData,
+        label: &str,
+        color_load: wgpu::LoadOp<wgpu::Color>,
+        depth_load: wgpu::LoadOp<f32>,
+    ) -> RenderPass<'e> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.color_target(frame),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: color_load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: self.depth_texture.inner().view(),
+                depth_ops: Some(wgpu::Operations {
+                    load: depth_load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+
+    /// Render the given raw commands for the frame, on top of what has already been rendered.
+    ///
+    /// The existing depth buffer is kept, so the commands are depth-tested against the scene.
+    /// If any command fails, rendering stops there and this returns a `RenderError`.
+    pub fn render_raw_commands(&mut self, commands: &[RawRenderCommand]) -> RenderResult<()> {
+        if !self.surface_is_configured {
+            return Err(RenderError::UnconfiguredSurface);
+        }
+
+        // get the surface, encoder, render pass
+        let frame = match &self.current_frame {
+            Some(frame) => frame,
+            None => return Err(RenderError::NoFrameInProgress)
+        };
+        let mut encoder = self.gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("raw_render_encoder"),
+            });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("raw_render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.color_target(frame),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: self.depth_texture.inner().view(),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        // write the render commands
+        for command in commands {
+            self.write_raw_command(command, &mut render_pass)?
+        }
+        drop(render_pass);
+
+        self.gpu.queue().submit([encoder.finish()]);
 
         Ok(())
     }
@@ -210,10 +1172,9 @@ impl<'a> Renderer<'a> {
             return Err(RenderError::UnconfiguredSurface);
         }
 
-        let frame = match &self.current_frame {
-            Some(frame) => frame,
-            None => return Err(RenderError::NoFrameInProgress)
-        };
+        if self.current_frame.is_none() {
+            return Err(RenderError::NoFrameInProgress);
+        }
 
         let mut encoder = self.gpu
             .device()
@@ -223,7 +1184,6 @@ impl<'a> Renderer<'a> {
 
         encode(&mut encoder);
 
-        self.hdr.process(&mut encoder, &frame.view);
         self.gpu
             .queue()
             .submit(std::iter::once(encoder.finish()));
@@ -231,7 +1191,10 @@ impl<'a> Renderer<'a> {
     }
 
     /// Render with a render pass.
-    pub fn render_with_render_pass<F>(&mut self, mut render: F, use_depth: bool) -> RenderResult<()> 
+    ///
+    /// With a `depth_load`, the depth texture is attached to the pass; `LoadOp::Load` keeps the scene's depth
+    /// (ie to draw things depth-tested against it), and `LoadOp::Clear` starts afresh.
+    pub fn render_with_render_pass<F>(&mut self, mut render: F, depth_load: Option<wgpu::LoadOp<f32>>) -> RenderResult<()> 
     where 
         F: FnMut(RenderPass<'_>)
     {
@@ -251,22 +1214,18 @@ impl<'a> Renderer<'a> {
                     label: Some("render_encoder"),
                 });
 
-        let depth_stencil_attachment = if use_depth {
-            Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.inner().view(),
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            })
-        } else {
-            None
-        };
+        let depth_stencil_attachment = depth_load.map(|load| wgpu::RenderPassDepthStencilAttachment {
+            view: self.depth_texture.inner().view(),
+            depth_ops: Some(wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        });
         let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("render_pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: self.hdr.texture().view(),
+                view: self.color_target(frame),
                 resolve_target: None,
                 depth_slice: None,
                 ops: wgpu::Operations {
@@ -281,24 +1240,34 @@ impl<'a> Renderer<'a> {
         
         render(render_pass);
 
-        self.hdr.process(&mut encoder, &frame.view);
         self.gpu.queue().submit(std::iter::once(encoder.finish()));
         Ok(())
     }
 
-    /// Write the mesh command.
-    ///
-    /// Additionally requires the mesh ID + the instance buffer that the mesh's instance data is in.
+    /// Get the view that passes render to; the HDR texture, or the surface directly if HDR is disabled.
+    fn color_target<'f>(&'f self, frame: &'f CurrentFrameData) -> &'f TextureView {
+        match self.hdr_enabled {
+            true => self.hdr.texture().view(),
+            false => &frame.view,
+        }
+    }
+
+    /// Tonemap the HDR texture into the surface, if HDR is enabled.
+    fn resolve_to_surface(&self, encoder: &mut CommandEncoder, frame: &CurrentFrameData) {
+        if self.hdr_enabled {
+            self.hdr.process(encoder, &frame.view);
+        }
+    }
+
+    /// Write the mesh command with the given pipeline; the command's own, or a deferred/clustered one.
     fn write_mesh_command(
         &self,
         command: &MeshRenderCommand,
+        pipeline: &GpuPipeline,
         render_pass: &mut wgpu::RenderPass,
     ) -> RenderResult<()>
     {
-        let pipeline = self
-            .get_pipeline(command.pipeline, command.name)?
-            .handle();
-        render_pass.set_pipeline(pipeline);
+        render_pass.set_pipeline(pipeline.handle());
 
         // get and set the bind groups
         let camera_bind_group = self
@@ -320,12 +1289,39 @@ impl<'a> Renderer<'a> {
         // instance vertex buffer - write the buffer data, then get our buffer slices
         let instance_buffer_slice = self
             .instance_buffer
-            .get_slice(command.mesh)
+            .get_range_slice(command.instance_buffer_range)
             .ok_or(RenderError::MeshHasNoInstanceData(command.mesh))?;
+
+        // the draw's instance indices are relative to this slice (ie they start at 0), not to the whole buffer,
+        // so the slice must cover exactly the command's range
+        let range = command.instance_buffer_range;
+        debug_assert_eq!(
+            instance_buffer_slice.size().get(),
+            (range.end - range.start) * size_of::<MeshInstanceData>() as u64,
+            "Instance buffer slice for {} doesn't match its instance range",
+            command.name
+        );
         render_pass.set_vertex_buffer(INSTANCE_BUFFER_SLOT, instance_buffer_slice);
 
-        // index buffer
-        render_pass.set_index_buffer(command.index_buffer, INDEX_BUFFER_FORMAT);
+        // the secondary instance stream, if the scene gathered it; it's parallel to the instance data, so the same draw covers it
+        if let Some(range) = command.secondary_instance_buffer_range {
+            let secondary_slice = self
+                .secondary_instance_buffer
+                .get_range_slice(range)
+                .ok_or(RenderError::MeshHasNoInstanceData(command.mesh))?;
+            render_pass.set_vertex_buffer(SECONDARY_INSTANCE_BUFFER_SLOT, secondary_slice);
+        }
+
+        // index buffer, unless the mesh isn't indexed
+        match (&command.draw, command.index_buffer) {
+            (DrawCommand::Indexed { .. }, Some(index_buffer)) => {
+                render_pass.set_index_buffer(index_buffer, INDEX_BUFFER_FORMAT)
+            }
+            (DrawCommand::Indexed { .. }, None) => {
+                return Err(RenderError::MissingIndexBuffer { label: command.name.into() })
+            }
+            (DrawCommand::NonIndexed { .. }, _) => {}
+        }
 
         // draw
         self.draw(command.draw.clone(), render_pass);
@@ -354,13 +1350,57 @@ impl<'a> Renderer<'a> {
         render_pass.set_bind_group(SKYBOX_CAMERA_BIND_GROUP_SLOT, camera_bind_group, &[]);
         render_pass.set_bind_group(SKYBOX_CUBEMAP_BIND_GROUP_SLOT, sky_bind_group, &[]);
 
-        render_pass.draw(0..3, 0..1);
+        self.draw(DrawCommand::NonIndexed { vertices: 0..3, instances: 0..1 }, render_pass);
+
+        Ok(())
+    }
+
+    /// Write a raw render command to the render pass.
+    fn write_raw_command(
+        &self,
+        command: &RawRenderCommand,
+        render_pass: &mut wgpu::RenderPass<'_>,
+    ) -> RenderResult<()>
+    {
+        let pipeline = self
+            .get_pipeline(command.pipeline, command.name)?
+            .handle();
+        render_pass.set_pipeline(pipeline);
+
+        for &(slot, bind_group) in &command.bind_groups {
+            let bind_group = self
+                .get_bind_group(bind_group, command.name)?
+                .handle();
+            render_pass.set_bind_group(slot, bind_group, &[]);
+        }
+        for &(slot, vertex_buffer) in &command.vertex_buffers {
+            render_pass.set_vertex_buffer(slot, vertex_buffer);
+        }
+
+        match (&command.draw, command.index_buffer) {
+            (DrawCommand::Indexed { .. }, Some(index_buffer)) => {
+                render_pass.set_index_buffer(index_buffer, INDEX_BUFFER_FORMAT)
+            }
+            (DrawCommand::Indexed { .. }, None) => {
+                return Err(RenderError::MissingIndexBuffer { label: command.name.into() })
+            }
+            (DrawCommand::NonIndexed { .. }, _) => {}
+        }
+
+        self.draw(command.draw.clone(), render_pass);
 
         Ok(())
     }
 
     /// Handle the draw command.
     fn draw(&self, draw_command: DrawCommand, render_pass: &mut wgpu::RenderPass<'_>) {
+        let mut stats = self.frame_stats.get();
+        match &draw_command {
+            DrawCommand::NonIndexed { vertices, instances } => stats.record_draw(vertices.len() as u32, instances.len() as u32),
+            DrawCommand::Indexed { indices, instances, .. } => stats.record_draw(indices.len() as u32, instances.len() as u32),
+        }
+        self.frame_stats.set(stats);
+
         match draw_command {
             DrawCommand::NonIndexed {
                 vertices,
@@ -388,8 +1428,20 @@ pub enum RenderError {
     LightingBindGroupNotFound { label: String },
     #[error("The surface is not configured yet")]
     UnconfiguredSurface,
+    #[error("Command {label} uses an indexed draw but has no index buffer")]
+    MissingIndexBuffer { label: String },
     #[error("The mesh {0:?} didn't have a corresponding instance buffer slice")]
     MeshHasNoInstanceData(MeshId),
+    #[error("Tried to read the depth at ({x}, {y}), which is outside the surface")]
+    DepthOutOfBounds { x: u32, y: u32 },
+    #[error("Failed to read back the depth: {0}")]
+    DepthReadback(String),
+    #[error("Frame latency {0} is outside the supported range 1..={max}", max = Renderer::MAX_FRAME_LATENCY)]
+    InvalidFrameLatency(u32),
+    #[error("Render scale {0} is outside the supported range (0, {max}]", max = Renderer::MAX_RENDER_SCALE)]
+    InvalidRenderScale(f32),
+    #[error("{0}")]
+    Graph(#[from] RenderGraphError),
     #[error("{0}")]
     Scene(#[from] SceneError),
     #[error("{0}")]
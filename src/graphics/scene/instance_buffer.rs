@@ -1,17 +1,125 @@
-use slotmap::SecondaryMap;
-use wgpu::BufferSlice;
+use bytemuck::{Pod, Zeroable};
+use slotmap::{Key, SecondaryMap};
+use wgpu::{BufferSlice, VertexAttribute, VertexBufferLayout, VertexFormat};
 
-use crate::graphics::gpu::{GpuContext, buffer::GpuBuffer};
+use crate::graphics::gpu::{GpuContext, buffer::GpuBuffer, vertex::Vertex};
+use crate::graphics::render::{assets::SpriteTextureId, renderable::{model::{ObjectParam, ObjectParams}, sprite::SpriteInstanceData}};
 use crate::graphics::scene::MeshId;
 use crate::graphics::scene::raw_spatial_transform::RawSpatialTransform;
 
-/// The data per instance. Currently just the spacial transform for it.
-pub type MeshInstanceData = RawSpatialTransform;
+/// The data per mesh instance; its spatial transform, and its custom params.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct MeshInstanceData {
+    pub transform: RawSpatialTransform,
+    pub params: ObjectParams,
+}
 
-/// Describes the range for a mesh's instance data within the entire buffer.
+impl Vertex for MeshInstanceData {
+    /// Get the vertex buffer description of the instance data; the transform's attributes, followed by the params'.
+    fn desc() -> VertexBufferLayout<'static> {
+        const ATTRIBUTES: [VertexAttribute; 6] = {
+            let transform = RawSpatialTransform::ATTRIBUTES;
+            let params_offset = size_of::<RawSpatialTransform>() as wgpu::BufferAddress;
+            [
+                transform[0],
+                transform[1],
+                transform[2],
+                transform[3],
+                VertexAttribute {
+                    offset: params_offset,
+                    shader_location: 11,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: params_offset + size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: VertexFormat::Float32x4,
+                },
+            ]
+        };
+        VertexBufferLayout {
+            array_stride: size_of::<MeshInstanceData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// User-defined data per mesh instance, in a second instance stream next to `MeshInstanceData`,
+/// ie for an animation frame index driving a sprite sheet on a mesh.
+///
+/// It's only gathered + bound (at `SECONDARY_INSTANCE_BUFFER_SLOT`) for scenes with the stream enabled,
+/// so the main instance stream stays lean. Pipelines reading it add `SecondaryInstanceData::desc()` to their vertex buffers,
+/// which puts its values at locations 13-15 (one `vec4<f32>` per value).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct SecondaryInstanceData {
+    pub values: [[f32; 4]; SecondaryInstanceData::COUNT],
+}
+
+impl SecondaryInstanceData {
+    /// The number of values; each is a `vec4<f32>`, taking the vertex attributes left after the mesh's.
+    pub const COUNT: usize = 3;
+
+    /// Get data with every value set to 0.
+    pub fn zeroed() -> Self {
+        Zeroable::zeroed()
+    }
+
+    /// Set the value at the index, returning whether the index was in range.
+    pub fn set(&mut self, index: usize, value: impl ObjectParam) -> bool {
+        match self.values.get_mut(index) {
+            Some(slot) => {
+                *slot = value.to_raw();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Vertex for SecondaryInstanceData {
+    /// Get the vertex buffer description of the secondary instance data, following the main instance data's locations.
+    fn desc() -> VertexBufferLayout<'static> {
+        const ATTRIBUTES: [VertexAttribute; SecondaryInstanceData::COUNT] = [
+            VertexAttribute {
+                offset: 0,
+                shader_location: 13,
+                format: VertexFormat::Float32x4,
+            },
+            VertexAttribute {
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 14,
+                format: VertexFormat::Float32x4,
+            },
+            VertexAttribute {
+                offset: size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                shader_location: 15,
+                format: VertexFormat::Float32x4,
+            },
+        ];
+        VertexBufferLayout {
+            array_stride: size_of::<SecondaryInstanceData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// The instance buffer for meshes.
+pub type MeshInstanceBuffer = InstanceBuffer<MeshInstanceData, MeshId>;
+
+/// The instance buffer for meshes' secondary instance data, parallel to the `MeshInstanceBuffer`.
+pub type SecondaryInstanceBuffer = InstanceBuffer<SecondaryInstanceData, MeshId>;
+
+/// The instance buffer for sprites, batched by texture.
+pub type SpriteInstanceBuffer = InstanceBuffer<SpriteInstanceData, SpriteTextureId>;
+
+/// Describes the range for a mesh's (or other key's) instance data within the entire buffer.
 ///
 /// ## Note
-/// This is in terms of `MeshInstanceData`, not bytes. Thus the total number of instances
+/// This is in terms of instance data items (ie `MeshInstanceData`), not bytes. Thus the total number of instances
 /// can be calculated from `end - start`.
 #[derive(Clone, Copy)]
 pub struct InstanceBufferRange {
@@ -22,33 +130,48 @@ pub struct InstanceBufferRange {
 /// This is a special big vertex buffer, functioning as a single instance buffer for many meshes.
 /// 
 /// This is so that we can just use slices out of this 1 buffer for many meshes, apparently an optimization.
-pub struct InstanceBuffer {
+///
+/// It's generic over the instance data `T` + the key `K` its ranges are mapped by,
+/// so other renderables (ie sprites) can batch the same way; see `MeshInstanceBuffer` for meshes.
+pub struct InstanceBuffer<T: Pod, K: Key> {
     gpu: GpuContext,
     buffer: GpuBuffer,
     buffer_label: String,
-    buffer_data: Vec<MeshInstanceData>,
+    buffer_data: Vec<T>,
     buffer_size: u64,
-    mesh_ranges: SecondaryMap<MeshId, InstanceBufferRange>,
+    ranges: SecondaryMap<K, InstanceBufferRange>,
 }
 
-impl InstanceBuffer {
-    /// The initial size of the buffer (in items, not bytes).
-    const INITIAL_BUF_SIZE: u64 = 10_000;
+impl<T: Pod, K: Key> InstanceBuffer<T, K> {
+    /// The initial size of the buffer (in items, not bytes), unless created `with_capacity`.
+    pub const INITIAL_BUF_SIZE: u64 = 10_000;
 
-    /// Instantiate the buffer.
+    /// Instantiate the buffer, with room for `INITIAL_BUF_SIZE` items.
     pub fn new(gpu: GpuContext, label: String) -> Self {
-        let initial_buffer_size = Self::INITIAL_BUF_SIZE * size_of::<MeshInstanceData>() as u64;
-        let buffer = GpuBuffer::create_writeable_vertex_uninit(&label, &gpu, initial_buffer_size);
+        Self::with_capacity(gpu, label, Self::INITIAL_BUF_SIZE)
+    }
+
+    /// Instantiate the buffer with room for the given number of items (at least 1), ie to save memory on small targets.
+    ///
+    /// The buffer still grows when it's exceeded.
+    pub fn with_capacity(gpu: GpuContext, label: String, capacity: u64) -> Self {
+        let capacity = capacity.max(1);
+        let buffer = GpuBuffer::create_writeable_vertex_uninit(&label, &gpu, capacity * size_of::<T>() as u64);
         Self {
             gpu,
             buffer,
             buffer_label: label,
-            buffer_data: Vec::with_capacity(Self::INITIAL_BUF_SIZE as usize),
-            buffer_size: Self::INITIAL_BUF_SIZE,
-            mesh_ranges: SecondaryMap::new(),
+            buffer_data: Vec::with_capacity(capacity as usize),
+            buffer_size: capacity,
+            ranges: SecondaryMap::new(),
         }
     }
 
+    /// Get the number of items the buffer currently has room for.
+    pub fn capacity(&self) -> u64 {
+        self.buffer_size
+    }
+
     /// Get the actual buffer.
     pub fn handle(&self) -> &GpuBuffer {
         &self.buffer
@@ -56,44 +179,54 @@ impl InstanceBuffer {
 
     /// Clear the mappings (ie for a new frame).
     pub fn clear(&mut self) {
-        self.mesh_ranges.clear();
+        self.ranges.clear();
         self.buffer_data.clear();
     }
 
     /// Add the given data to the internal Vec + create a mapping for it.
-    pub fn add(&mut self, data: Vec<MeshInstanceData>, mesh: MeshId) -> InstanceBufferRange {
-        // create new gpu buffer with double the size when we've maxed it out
+    pub fn add(&mut self, data: Vec<T>, key: K) -> InstanceBufferRange {
+        // create new gpu buffer with double the size (or more, if that's still short) when we've maxed it out
         let required_size = (self.buffer_data.len() + data.len()) as u64;
         if required_size > self.buffer_size {
+            let new_size = (self.buffer_size * 2).max(required_size);
             self.buffer.handle().destroy();
             self.buffer = GpuBuffer::create_writeable_vertex_uninit(
                 &self.buffer_label,
                 &self.gpu,
-                self.buffer_size * 2,
+                new_size * size_of::<T>() as u64,
             );
-            self.buffer_size *= 2;
+            self.buffer_size = new_size;
         }
 
         let range = InstanceBufferRange {
             start: self.buffer_data.len() as u64,
             end: (self.buffer_data.len() + data.len()) as u64,
         };
-        self.mesh_ranges.insert(mesh, range.clone());
+        self.ranges.insert(key, range.clone());
         self.buffer_data.extend_from_slice(&data);
 
         range
     }
 
+    /// Remove the mapping for the given key (ie mesh), returning its range if it had one.
+    ///
+    /// ## Note
+    /// The key's data stays in the buffer until the next `clear`, but `get_slice` returns `None` for it from now on.
+    pub fn remove(&mut self, key: K) -> Option<InstanceBufferRange> {
+        self.ranges.remove(key)
+    }
+
     /// Writes the internal buffered instance data to the actual GPU buffer.
     ///
     /// You should do this once all your instance data has been written,
-    /// and you're ready to render.
+    /// and you're ready to render. Like other buffer writes, it's applied at the next queue submission,
+    /// ie the render pass using it.
     ///
     /// ## Panic
     /// Panics if the buffer data is somehow larger than the buffer size.
     pub fn write(&self) {
         if self.buffer.handle().size()
-            < (self.buffer_data.len() * size_of::<MeshInstanceData>()) as u64
+            < (self.buffer_data.len() * size_of::<T>()) as u64
         {
             panic!("Instance buffer data is larger than buffer's capacity!");
         }
@@ -103,18 +236,34 @@ impl InstanceBuffer {
             0,
             &bytemuck::cast_slice(&self.buffer_data),
         );
-        self.gpu.queue().submit([]);
     }
 
-    /// Get the buffer slice for the given mesh, if it exists.
+    /// Get the buffer slice for the given range, if it's within the added data.
     ///
     /// ## Note
     /// This becomes invalid when the instance buffer is cleared.
-    pub fn get_slice(&self, mesh: MeshId) -> Option<BufferSlice<'_>> {
-        if let Some(range) = self.mesh_ranges.get(mesh) {
+    pub fn get_range_slice(&self, range: InstanceBufferRange) -> Option<BufferSlice<'_>> {
+        if range.start < range.end && range.end <= self.buffer_data.len() as u64 {
+            let slice = self.buffer.handle().slice(
+                range.start * size_of::<T>() as u64
+                    ..range.end * size_of::<T>() as u64,
+            );
+            Some(slice)
+        } else {
+            None
+        }
+    }
+
+    /// Get the buffer slice for the given key (ie mesh), if it exists.
+    ///
+    /// ## Note
+    /// This becomes invalid when the instance buffer is cleared, or the key is removed.
+    /// If the key's data was added multiple times (ie for different materials), this is the last range added.
+    pub fn get_slice(&self, key: K) -> Option<BufferSlice<'_>> {
+        if let Some(range) = self.ranges.get(key) {
             let slice = self.buffer.handle().slice(
-                range.start * size_of::<MeshInstanceData>() as u64
-                    ..range.end * size_of::<MeshInstanceData>() as u64,
+                range.start * size_of::<T>() as u64
+                    ..range.end * size_of::<T>() as u64,
             );
             Some(slice)
         } else {
@@ -122,3 +271,32 @@ impl InstanceBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use slotmap::SlotMap;
+    use crate::graphics::gpu::test_context;
+    use super::*;
+
+    #[test]
+    fn removed_mesh_has_no_slice() {
+        let Some(gpu) = test_context() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let mut meshes: SlotMap<MeshId, ()> = SlotMap::with_key();
+        let (kept, removed) = (meshes.insert(()), meshes.insert(()));
+        let mut buffer = MeshInstanceBuffer::with_capacity(gpu, "test_instance_buffer".to_string(), 4);
+        let data = vec![MeshInstanceData::zeroed(); 2];
+        buffer.add(data.clone(), kept);
+        let removed_range = buffer.add(data, removed);
+
+        let range = buffer.remove(removed).unwrap();
+        assert_eq!((range.start, range.end), (removed_range.start, removed_range.end));
+        assert!(buffer.get_slice(removed).is_none());
+        assert!(buffer.get_slice(kept).is_some());
+        assert!(buffer.remove(removed).is_none());
+        // the data's still there until the next clear
+        assert!(buffer.get_range_slice(removed_range).is_some());
+    }
+}
@@ -0,0 +1,172 @@
+use cgmath::Vector3;
+use crate::{core::{entity::WorldEntity, world::{World, WorldEntityId}}, graphics::gpu::{GpuContext, buffer::GpuBuffer}};
+
+/// The capacity directional light collections are usually created with.
+pub const DEFAULT_DIRECTIONAL_LIGHT_CAPACITY: usize = 16;
+
+/// A collection of directional lights.
+pub struct DirectionalLightCollection {
+    lights: Vec<DirectionalLight>,
+    capacity: usize,
+    light_buffer: GpuBuffer,
+    light_count_buffer: GpuBuffer
+}
+
+impl DirectionalLightCollection {
+    /// Create a new collection, holding at most `capacity` lights (ie `DEFAULT_DIRECTIONAL_LIGHT_CAPACITY`).
+    ///
+    /// The storage buffer is allocated for the whole capacity up front; the shaders use runtime-sized arrays,
+    /// so they needn't be changed along with it.
+    ///
+    /// Panics if there are more lights than the capacity.
+    pub fn new(label: &str, lights: Vec<DirectionalLight>, capacity: usize, gpu: &GpuContext) -> Self {
+        if lights.len() > capacity {
+            panic!("Too many directional lights for the collection's capacity");
+        }
+        let light_buffer = GpuBuffer::create_storage_uninit(
+            label, 
+            gpu, 
+            (size_of::<DirectionalLightUniform>() * capacity.max(1)) as u64
+        );
+        let light_count_buffer = GpuBuffer::create_uniform(
+            label, 
+            gpu, 
+            bytemuck::cast_slice(&[0u32])
+        );
+        Self {
+            lights,
+            capacity,
+            light_buffer,
+            light_count_buffer
+        }
+    }
+
+    /// Get the maximum number of lights in the collection.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the buffer holding the lights.
+    pub fn light_buffer(&self) -> &GpuBuffer {
+        &self.light_buffer
+    }
+
+    /// Get the buffer holding the number of lights.
+    pub fn light_count_buffer(&self) -> &GpuBuffer {
+        &self.light_count_buffer
+    }
+
+    /// Add the given lights to the collection. 
+    /// 
+    /// Panics if exceeds buffer capacity.
+    pub fn add(&mut self, mut lights: Vec<DirectionalLight>) {
+        if self.lights.len() + lights.len() > self.capacity {
+            panic!("Too many directional lights in the collection");
+        }
+        self.lights.append(&mut lights)
+    }
+
+    /// Iterate over the lights mutably, ie to animate them. Changes are written to the GPU on the next update.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut DirectionalLight> {
+        self.lights.iter_mut()
+    }
+
+    /// Remove the directional lights with the given entity IDs.
+    pub fn remove(&mut self, lights: Vec<WorldEntityId>) {
+        self.lights.retain(|l| !lights.contains(&l.entity));
+    }
+
+    /// Update the lights from their entities and write them to the buffer, returning how many were written.
+    pub fn update_and_write_buffer(&mut self, world: &World, gpu: &GpuContext) -> usize {
+        // lights of disabled entities, or ones under a disabled parent, are left out
+        let uniform_data = self.lights
+            .iter_mut()
+            .filter(|light| world.enabled_in_hierarchy(light.entity) != Some(false))
+            .map(|light| light.update_and_return_uniform(world))
+            .collect::<Vec<_>>();
+        gpu.queue().write_buffer(
+            self.light_buffer.handle(),
+            0,
+            bytemuck::cast_slice(&uniform_data),
+        );
+        gpu.queue().write_buffer(
+            self.light_count_buffer.handle(), 
+            0, 
+            bytemuck::cast_slice(&[uniform_data.len() as u32])
+        );
+        uniform_data.len()
+    }
+}
+
+/// A directional light, ie one infinitely far away like the sun.
+/// 
+/// The light points in the forward direction of its entity.
+pub struct DirectionalLight {
+    entity: WorldEntityId,
+    uniform: DirectionalLightUniform,
+}
+
+impl DirectionalLight {
+    /// Create a new directional light tied to the given entity.
+    pub fn new(
+        entity: WorldEntityId, 
+        color: Vector3<f32>
+    ) -> Self {
+        let uniform = DirectionalLightUniform::new(color.into());
+        Self { 
+            entity,
+            uniform, 
+        }
+    }
+
+    /// Get the entity the light points along.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
+    /// Get the light's color.
+    pub fn color(&self) -> [f32; 3] {
+        self.uniform.color
+    }
+
+    /// Set the light's color. This is written to the GPU on the next update.
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        self.uniform.color = color;
+    }
+
+    /// Update and return the light's uniform.
+    pub fn update_and_return_uniform(&mut self, world: &World) -> DirectionalLightUniform {
+        let entity = world
+            .entity(self.entity)
+            .expect("Directional light entity should exist");
+        self.uniform.update(entity);
+        self.uniform
+    }
+}
+
+/// Represents a colored light shining in a direction.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+pub struct DirectionalLightUniform {
+    pub direction: [f32; 3],
+    _padding: u32, // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here...
+    pub color: [f32; 3],
+    _padding2: u32, // ...And here
+}
+
+impl DirectionalLightUniform {
+    /// Create a light uniform.
+    pub fn new(color: [f32; 3]) -> Self {
+        Self {
+            direction: [0.0, 0.0, 1.0],
+            _padding: 0,
+            color,
+            _padding2: 0,
+        }
+    }
+
+    /// Update the uniform.
+    pub fn update(&mut self, entity: &WorldEntity) {
+        self.direction = entity.transform().forward().into();
+    }
+}
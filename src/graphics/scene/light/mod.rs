@@ -1 +1,139 @@
+pub mod directional;
 pub mod point;
+pub mod spot;
+
+use crate::{core::world::World, graphics::{gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer}, scene::light::{
+    directional::DirectionalLightCollection, point::PointLightCollection, spot::SpotLightCollection,
+}}};
+
+/// All the lights of a scene, packed into a single bind group.
+///
+/// This keeps the mesh pipeline's bind group count fixed as light types are added.
+/// The bind group layout is stable, and the shader expects:
+/// - bindings 0 + 1: the point lights + their count
+/// - bindings 2 + 3: the directional lights + their count
+/// - bindings 4 + 5: the spot lights + their count
+/// - binding 6: the ambient light's color, as a `vec4` (with the 4th component unused)
+pub struct Lighting {
+    point_lights: PointLightCollection,
+    directional_lights: DirectionalLightCollection,
+    spot_lights: SpotLightCollection,
+    ambient_buffer: GpuBuffer,
+    default_ambient_when_unlit: bool,
+    /// Whether it's been logged that there are no lights, so it's only logged once.
+    warned_unlit: bool,
+}
+
+impl Lighting {
+    /// The ambient light applied when there are no lights, unless disabled; see `set_default_ambient_when_unlit`.
+    pub const DEFAULT_UNLIT_AMBIENT: [f32; 3] = [0.2, 0.2, 0.2];
+
+    /// Combine the light collections.
+    pub fn new(
+        gpu: &GpuContext,
+        point_lights: PointLightCollection,
+        directional_lights: DirectionalLightCollection,
+        spot_lights: SpotLightCollection,
+    ) -> Self {
+        let ambient_buffer = GpuBuffer::create_uniform(
+            "ambient_light_buffer",
+            gpu,
+            bytemuck::cast_slice(&[0.0f32; 4])
+        );
+        Self {
+            point_lights,
+            directional_lights,
+            spot_lights,
+            ambient_buffer,
+            default_ambient_when_unlit: true,
+            warned_unlit: false,
+        }
+    }
+
+    /// Create the bind group with all the collections' buffers.
+    pub fn create_bind_group(&self, label: &str, gpu: &GpuContext) -> GpuBindGroup {
+        let buffers = [
+            self.point_lights.light_buffer(),
+            self.point_lights.light_count_buffer(),
+            self.directional_lights.light_buffer(),
+            self.directional_lights.light_count_buffer(),
+            self.spot_lights.light_buffer(),
+            self.spot_lights.light_count_buffer(),
+            &self.ambient_buffer,
+        ];
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.handle().as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        GpuBindGroup::create_default(label, gpu, &Self::bind_group_layout_entries(), &entries)
+    }
+
+    /// Get the bind group's layout entries, ie for creating other pipelines that read the lights.
+    pub fn bind_group_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+        (0..7)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    // the lights are storage buffers; their counts + the ambient light are uniforms
+                    ty: match binding {
+                        0 | 2 | 4 => wgpu::BufferBindingType::Storage { read_only: true },
+                        _ => wgpu::BufferBindingType::Uniform,
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect()
+    }
+
+    /// Update all the collections and write them to their buffers, along with the ambient light.
+    pub fn update_and_write_buffers(&mut self, world: &World, gpu: &GpuContext) {
+        let light_count = self.point_lights.update_and_write_buffer(world, gpu)
+            + self.directional_lights.update_and_write_buffer(world, gpu)
+            + self.spot_lights.update_and_write_buffer(world, gpu);
+
+        // without any lights, meshes would render black
+        let unlit = light_count == 0;
+        if unlit && !self.default_ambient_when_unlit && !self.warned_unlit {
+            log::warn!("The scene has no lights and no default ambient light, so it renders black");
+            self.warned_unlit = true;
+        }
+        let [r, g, b] = match unlit && self.default_ambient_when_unlit {
+            true => Self::DEFAULT_UNLIT_AMBIENT,
+            false => [0.0; 3],
+        };
+        gpu.queue().write_buffer(self.ambient_buffer.handle(), 0, bytemuck::cast_slice(&[r, g, b, 0.0]));
+    }
+
+    /// Whether a small ambient light (`DEFAULT_UNLIT_AMBIENT`) is applied while there are no lights.
+    pub fn default_ambient_when_unlit(&self) -> bool {
+        self.default_ambient_when_unlit
+    }
+
+    /// Set whether a small ambient light (`DEFAULT_UNLIT_AMBIENT`) is applied while there are no lights,
+    /// so an unlit scene isn't rendered black; it's applied by default. Otherwise, a warning is logged once.
+    pub fn set_default_ambient_when_unlit(&mut self, enabled: bool) {
+        self.default_ambient_when_unlit = enabled;
+    }
+
+    /// Get the point lights mutably.
+    pub fn point_lights_mut(&mut self) -> &mut PointLightCollection {
+        &mut self.point_lights
+    }
+
+    /// Get the directional lights mutably.
+    pub fn directional_lights_mut(&mut self) -> &mut DirectionalLightCollection {
+        &mut self.directional_lights
+    }
+
+    /// Get the spot lights mutably.
+    pub fn spot_lights_mut(&mut self) -> &mut SpotLightCollection {
+        &mut self.spot_lights
+    }
+}
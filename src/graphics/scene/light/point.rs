@@ -1,22 +1,32 @@
-use cgmath::Vector3;
-use crate::{core::world::{World, WorldEntityId}, graphics::gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer}};
+use cgmath::{Vector3, Zero};
+use crate::{core::{entity::{WorldEntity, spatial_transform::SpatialTransform}, world::{World, WorldEntityId}}, graphics::gpu::{GpuContext, buffer::GpuBuffer}};
 
-pub const MAX_POINT_LIGHTS: usize = 1000;
+/// The capacity point light collections are usually created with.
+pub const DEFAULT_POINT_LIGHT_CAPACITY: usize = 1000;
 
 /// A collection of point lights.
 pub struct PointLightCollection {
     lights: Vec<PointLight>,
+    capacity: usize,
     light_buffer: GpuBuffer,
     light_count_buffer: GpuBuffer
 }
 
 impl PointLightCollection {
-    /// Create a new collection with a max capacity of `MAX_POINT_LIGHTS`.
-    pub fn new(label: &str, lights: Vec<PointLight>, gpu: &GpuContext) -> Self {
+    /// Create a new collection, holding at most `capacity` lights (ie `DEFAULT_POINT_LIGHT_CAPACITY`).
+    ///
+    /// The storage buffer is allocated for the whole capacity up front; the shaders use runtime-sized arrays,
+    /// so they needn't be changed along with it.
+    ///
+    /// Panics if there are more lights than the capacity.
+    pub fn new(label: &str, lights: Vec<PointLight>, capacity: usize, gpu: &GpuContext) -> Self {
+        if lights.len() > capacity {
+            panic!("Too many point lights for the collection's capacity");
+        }
         let light_buffer = GpuBuffer::create_storage_uninit(
             label, 
             gpu, 
-            (size_of::<PointLightUniform>() * MAX_POINT_LIGHTS) as u64
+            (size_of::<PointLightUniform>() * capacity.max(1)) as u64
         );
         let light_count_buffer = GpuBuffer::create_uniform(
             label, 
@@ -25,70 +35,58 @@ impl PointLightCollection {
         );
         Self {
             lights,
+            capacity,
             light_buffer,
             light_count_buffer
         }
     }
 
-    /// Create the bind group with this collection's buffer.
-    pub fn create_bind_group(&self, label: &str, gpu: &GpuContext) -> GpuBindGroup {
-        GpuBindGroup::create_default(
-            label,
-            gpu,
-            &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None, 
-                }
-            ],
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: self.light_buffer.handle().as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: self.light_count_buffer.handle().as_entire_binding(),
-                },
-            ],
-        )
+    /// Get the maximum number of lights in the collection.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the buffer holding the lights.
+    pub fn light_buffer(&self) -> &GpuBuffer {
+        &self.light_buffer
+    }
+
+    /// Get the buffer holding the number of lights.
+    pub fn light_count_buffer(&self) -> &GpuBuffer {
+        &self.light_count_buffer
     }
 
     /// Add the given lights to the collection. 
     /// 
     /// Panics if exceeds buffer capacity.
     pub fn add(&mut self, mut lights: Vec<PointLight>) {
-        if self.lights.len() + lights.len() > MAX_POINT_LIGHTS {
+        if self.lights.len() + lights.len() > self.capacity {
             panic!("Too many point lights in the collection");
         }
         self.lights.append(&mut lights)
     }
 
+    /// Get the light at the given index mutably, ie to change its color.
+    pub fn light_mut(&mut self, index: usize) -> Option<&mut PointLight> {
+        self.lights.get_mut(index)
+    }
+
+    /// Iterate over the lights mutably, ie to animate them. Changes are written to the GPU on the next update.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut PointLight> {
+        self.lights.iter_mut()
+    }
+
     /// Remove the point lights with the given entity IDs.
     pub fn remove(&mut self, lights: Vec<WorldEntityId>) {
         self.lights.retain(|l| !lights.contains(&l.entity));
     }
 
-    /// Remove the lights with the given entity IDs from the collection.
-    pub fn update_and_write_buffer(&mut self, world: &World, gpu: &GpuContext) {
+    /// Update the lights from their entities and write them to the buffer, returning how many were written.
+    pub fn update_and_write_buffer(&mut self, world: &World, gpu: &GpuContext) -> usize {
+        // lights of disabled entities, or ones under a disabled parent, are left out
         let uniform_data = self.lights
             .iter_mut()
+            .filter(|light| world.enabled_in_hierarchy(light.entity) != Some(false))
             .map(|light| light.update_and_return_uniform(world))
             .collect::<Vec<_>>();
         gpu.queue().write_buffer(
@@ -99,14 +97,16 @@ impl PointLightCollection {
         gpu.queue().write_buffer(
             self.light_count_buffer.handle(), 
             0, 
-            bytemuck::cast_slice(&[self.lights.len() as u32])
+            bytemuck::cast_slice(&[uniform_data.len() as u32])
         );
+        uniform_data.len()
     }
 }
 
 /// A point light.
 pub struct PointLight {
     entity: WorldEntityId,
+    offset: Vector3<f32>,
     uniform: PointLightUniform,
 }
 
@@ -115,26 +115,61 @@ impl PointLight {
     pub fn new(
         entity: WorldEntityId, 
         color: Vector3<f32>
+    ) -> Self {
+        Self::with_offset(entity, color, Vector3::zero())
+    }
+
+    /// Create a new point light tied to the given entity, offset from it in the entity's local space.
+    ///
+    /// The offset moves + rotates + scales with the entity, ie for a lamp on a moving car.
+    pub fn with_offset(
+        entity: WorldEntityId,
+        color: Vector3<f32>,
+        offset: Vector3<f32>
     ) -> Self {
         let uniform = PointLightUniform::new(color.into());
-        Self { 
+        Self {
             entity,
-            uniform, 
+            offset,
+            uniform,
         }
     }
 
+    /// Get the entity the light is positioned by.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
+    /// Get the light's color.
+    pub fn color(&self) -> [f32; 3] {
+        self.uniform.color
+    }
+
+    /// Get the light's offset from its entity.
+    pub fn offset(&self) -> Vector3<f32> {
+        self.offset
+    }
+
+    /// Set the light's offset from its entity. This is applied on the next update.
+    pub fn set_offset(&mut self, offset: Vector3<f32>) {
+        self.offset = offset;
+    }
+
+    /// Set the light's color. This is written to the GPU on the next update.
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        self.uniform.color = color;
+    }
+
     /// Update and return the light's uniform.
     pub fn update_and_return_uniform(&mut self, world: &World) -> PointLightUniform {
         let entity = world
             .entity(self.entity)
             .expect("Point light entity should exist");
-        self.uniform.update(entity);
+        self.uniform.update(entity, self.offset);
         self.uniform
     }
 }
 
-use crate::core::entity::WorldEntity;
-
 /// Represents a colored point in space.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
@@ -156,8 +191,35 @@ impl PointLightUniform {
         }
     }
 
-    /// Update the uniform.
-    pub fn update(&mut self, entity: &WorldEntity) {
-        self.position = entity.transform().position.into();
+    /// Update the uniform, with the light offset from the entity in its local space.
+    pub fn update(&mut self, entity: &WorldEntity, offset: Vector3<f32>) {
+        let offset = SpatialTransform {
+            position: offset,
+            ..SpatialTransform::identity()
+        };
+        self.position = entity.transform().combine(&offset).position.into();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Quaternion, Rotation3, assert_abs_diff_eq};
+    use super::*;
+
+    #[test]
+    fn offset_is_rotated_with_the_entity() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform {
+            position: Vector3::new(10.0, 0.0, 0.0),
+            rotation: Quaternion::from_angle_y(Deg(90.0)),
+            ..SpatialTransform::identity()
+        });
+        world.update_graph();
+
+        let mut light = PointLight::with_offset(entity, Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 2.0, 3.0));
+        let uniform = light.update_and_return_uniform(&world);
+
+        // +z rotated 90 degrees about y is +x
+        let position: Vector3<f32> = uniform.position.into();
+        assert_abs_diff_eq!(position, Vector3::new(13.0, 2.0, 0.0), epsilon = 1e-5);
+    }
+}
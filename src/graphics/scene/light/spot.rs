@@ -0,0 +1,186 @@
+use cgmath::{Deg, Rad, Vector3};
+use crate::{core::{entity::WorldEntity, world::{World, WorldEntityId}}, graphics::gpu::{GpuContext, buffer::GpuBuffer}};
+
+/// The capacity spot light collections are usually created with.
+pub const DEFAULT_SPOT_LIGHT_CAPACITY: usize = 1000;
+
+/// A collection of spot lights.
+pub struct SpotLightCollection {
+    lights: Vec<SpotLight>,
+    capacity: usize,
+    light_buffer: GpuBuffer,
+    light_count_buffer: GpuBuffer
+}
+
+impl SpotLightCollection {
+    /// Create a new collection, holding at most `capacity` lights (ie `DEFAULT_SPOT_LIGHT_CAPACITY`).
+    ///
+    /// The storage buffer is allocated for the whole capacity up front; the shaders use runtime-sized arrays,
+    /// so they needn't be changed along with it.
+    ///
+    /// Panics if there are more lights than the capacity.
+    pub fn new(label: &str, lights: Vec<SpotLight>, capacity: usize, gpu: &GpuContext) -> Self {
+        if lights.len() > capacity {
+            panic!("Too many spot lights for the collection's capacity");
+        }
+        let light_buffer = GpuBuffer::create_storage_uninit(
+            label, 
+            gpu, 
+            (size_of::<SpotLightUniform>() * capacity.max(1)) as u64
+        );
+        let light_count_buffer = GpuBuffer::create_uniform(
+            label, 
+            gpu, 
+            bytemuck::cast_slice(&[0u32])
+        );
+        Self {
+            lights,
+            capacity,
+            light_buffer,
+            light_count_buffer
+        }
+    }
+
+    /// Get the maximum number of lights in the collection.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the buffer holding the lights.
+    pub fn light_buffer(&self) -> &GpuBuffer {
+        &self.light_buffer
+    }
+
+    /// Get the buffer holding the number of lights.
+    pub fn light_count_buffer(&self) -> &GpuBuffer {
+        &self.light_count_buffer
+    }
+
+    /// Add the given lights to the collection. 
+    /// 
+    /// Panics if exceeds buffer capacity.
+    pub fn add(&mut self, mut lights: Vec<SpotLight>) {
+        if self.lights.len() + lights.len() > self.capacity {
+            panic!("Too many spot lights in the collection");
+        }
+        self.lights.append(&mut lights)
+    }
+
+    /// Iterate over the lights mutably, ie to animate them. Changes are written to the GPU on the next update.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut SpotLight> {
+        self.lights.iter_mut()
+    }
+
+    /// Remove the spot lights with the given entity IDs.
+    pub fn remove(&mut self, lights: Vec<WorldEntityId>) {
+        self.lights.retain(|l| !lights.contains(&l.entity));
+    }
+
+    /// Update the lights from their entities and write them to the buffer, returning how many were written.
+    pub fn update_and_write_buffer(&mut self, world: &World, gpu: &GpuContext) -> usize {
+        // lights of disabled entities, or ones under a disabled parent, are left out
+        let uniform_data = self.lights
+            .iter_mut()
+            .filter(|light| world.enabled_in_hierarchy(light.entity) != Some(false))
+            .map(|light| light.update_and_return_uniform(world))
+            .collect::<Vec<_>>();
+        gpu.queue().write_buffer(
+            self.light_buffer.handle(),
+            0,
+            bytemuck::cast_slice(&uniform_data),
+        );
+        gpu.queue().write_buffer(
+            self.light_count_buffer.handle(), 
+            0, 
+            bytemuck::cast_slice(&[uniform_data.len() as u32])
+        );
+        uniform_data.len()
+    }
+}
+
+/// A spot light, ie a cone of light.
+/// 
+/// The cone starts at its entity's position and points in its forward direction.
+pub struct SpotLight {
+    entity: WorldEntityId,
+    uniform: SpotLightUniform,
+}
+
+impl SpotLight {
+    /// Create a new spot light tied to the given entity.
+    /// 
+    /// The light is at full strength within `inner_angle` of the cone's centre, and fades out up to `outer_angle`.
+    pub fn new(
+        entity: WorldEntityId, 
+        color: Vector3<f32>,
+        inner_angle: Deg<f32>,
+        outer_angle: Deg<f32>,
+    ) -> Self {
+        let uniform = SpotLightUniform::new(
+            color.into(), 
+            Rad::from(inner_angle).0.cos(), 
+            Rad::from(outer_angle).0.cos()
+        );
+        Self { 
+            entity,
+            uniform, 
+        }
+    }
+
+    /// Get the entity the light is positioned + pointed by.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
+    /// Get the light's color.
+    pub fn color(&self) -> [f32; 3] {
+        self.uniform.color
+    }
+
+    /// Set the light's color. This is written to the GPU on the next update.
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        self.uniform.color = color;
+    }
+
+    /// Update and return the light's uniform.
+    pub fn update_and_return_uniform(&mut self, world: &World) -> SpotLightUniform {
+        let entity = world
+            .entity(self.entity)
+            .expect("Spot light entity should exist");
+        self.uniform.update(entity);
+        self.uniform
+    }
+}
+
+/// Represents a colored cone of light.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::NoUninit)]
+pub struct SpotLightUniform {
+    pub position: [f32; 3],
+    pub inner_cutoff: f32, // The cosine of the inner angle, which also pads to 16 bytes...
+    pub direction: [f32; 3],
+    pub outer_cutoff: f32, // ...As does the outer one
+    pub color: [f32; 3],
+    _padding: u32,
+}
+
+impl SpotLightUniform {
+    /// Create a light uniform.
+    pub fn new(color: [f32; 3], inner_cutoff: f32, outer_cutoff: f32) -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            inner_cutoff,
+            direction: [0.0, 0.0, 1.0],
+            outer_cutoff,
+            color,
+            _padding: 0,
+        }
+    }
+
+    /// Update the uniform.
+    pub fn update(&mut self, entity: &WorldEntity) {
+        let transform = entity.transform();
+        self.position = transform.position.into();
+        self.direction = transform.forward().into();
+    }
+}
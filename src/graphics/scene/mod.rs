@@ -2,15 +2,17 @@ pub mod instance_buffer;
 pub mod light;
 pub mod raw_spatial_transform;
 
+use std::collections::BTreeMap;
+
 use slotmap::{SecondaryMap, SlotMap, new_key_type};
 use thiserror::Error;
-use crate::{core::world::{World, WorldEntityId}, graphics::{
+use crate::{core::{entity::spatial_transform::SpatialTransform, world::{World, WorldEntityId}}, graphics::{
     gpu::GpuContext,
     render::{
-        assets::{AssetStore, MaterialId, MeshId}, commands::RenderCommandBuffer, renderable::{model::MeshInstance, skybox::SkyBox, sprite::SpriteInstance}, renderer::{BindGroupId, PipelineId}
+        assets::{AssetStore, MaterialId, MeshId}, commands::RenderCommandBuffer, renderable::{model::{MeshInstance, ObjectParam, ObjectParams}, skybox::SkyBox, sprite::SpriteInstance}, renderer::{BindGroupId, PipelineId}
     },
     scene::{
-        instance_buffer::InstanceBuffer, light::point::{PointLight, PointLightCollection}, raw_spatial_transform::RawSpatialTransform
+        instance_buffer::{MeshInstanceBuffer, MeshInstanceData, SecondaryInstanceBuffer, SecondaryInstanceData}, light::{Lighting, directional::DirectionalLight, point::PointLight, spot::SpotLight}
     },
 },
     systems::camera::Camera};
@@ -22,57 +24,92 @@ new_key_type! {
     pub struct SpriteInstanceId;
 }
 
+/// How a scene rendered over others in a frame (see `Renderer::render_frame`) treats the depth underneath it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneDepth {
+    /// Keep the depth, so the scene is depth-tested against what's underneath, ie for more of the world.
+    Keep,
+    /// Clear the depth first, so the scene is drawn over what's underneath, ie for UI or a viewmodel.
+    Clear,
+}
+
+/// A scene's skybox, with the pipeline + bind group it's drawn with.
+pub struct SceneSky {
+    pub skybox: SkyBox,
+    pub pipeline: PipelineId,
+    pub bind_group: BindGroupId,
+}
+
 /// The main representation of "something" in the game.
 pub struct Scene {
     mesh_instances: SlotMap<MeshInstanceId, MeshInstance>,
     instances_by_mesh: SecondaryMap<MeshId, Vec<MeshInstanceId>>,
     sprite_instances: SlotMap<SpriteInstanceId, SpriteInstance>,
     camera: Camera,
-    point_lights: PointLightCollection,
+    lighting: Lighting,
     pipeline: PipelineId,
+    double_sided_pipeline: PipelineId,
     camera_bind_group: BindGroupId,
     lighting_bind_group: BindGroupId,
     skybox: SkyBox,
     sky_pipeline: PipelineId,
     sky_bind_group: BindGroupId,
+    skip_orphaned_instances: bool,
+    secondary_instance_stream: bool,
+    pipeline_override: Option<PipelineId>,
+    depth: SceneDepth,
 }
 
 impl Scene {
     /// Construct a scene.
     pub fn new(
         camera: Camera,
-        point_lights: PointLightCollection,
+        lighting: Lighting,
         pipeline: PipelineId,
+        double_sided_pipeline: PipelineId,
         camera_bind_group: BindGroupId,
         lighting_bind_group: BindGroupId,
-        skybox: SkyBox,
-        sky_pipeline: PipelineId,
-        sky_bind_group: BindGroupId,
+        sky: SceneSky,
     ) -> Self {
         Self {
             mesh_instances: SlotMap::with_key(),
             instances_by_mesh: SecondaryMap::new(),
             sprite_instances: SlotMap::with_key(),
             camera,
-            point_lights,
+            lighting,
             pipeline,
-            skybox,
-            sky_pipeline,
-            sky_bind_group,
+            double_sided_pipeline,
+            skybox: sky.skybox,
+            sky_pipeline: sky.pipeline,
+            sky_bind_group: sky.bind_group,
             camera_bind_group,
             lighting_bind_group,
+            skip_orphaned_instances: false,
+            secondary_instance_stream: false,
+            pipeline_override: None,
+            depth: SceneDepth::Keep,
         }
     }
 
     /// Convert the scene to render commands.
     ///
-    /// Writes the scene's meshes' instance data into the `instance_buffer`,
-    /// passing their ranges into the render command.
+    /// Writes the scene's meshes' visible instance data into the `instance_buffer`,
+    /// passing their ranges into the render command. Meshes without visible instances are skipped,
+    /// as are instances of disabled entities.
+    ///
+    /// Each instance is drawn with its entity's transform, combined with its local offset if it has one.
+    /// Instances of a mesh are grouped by their material (ie the mesh's, unless overridden), with a command per group.
+    /// Only instances whose layers intersect the `layer_mask` are included; use `MeshInstance::ALL_LAYERS` for all of them.
+    ///
+    /// With the secondary instance stream enabled, the instances' secondary data is written into the
+    /// `secondary_instance_buffer` in the same order, and its range passed into the command too.
     pub fn to_commands<'a>(
         &'a self,
         world: &World,
         assets: &'a AssetStore,
-        instance_buffer: &mut InstanceBuffer,
+        instance_buffer: &mut MeshInstanceBuffer,
+        secondary_instance_buffer: &mut SecondaryInstanceBuffer,
+        layer_mask: u32,
     ) -> Result<RenderCommandBuffer<'a>, SceneError> {
         let mut mesh_commands = Vec::new();
 
@@ -80,34 +117,42 @@ impl Scene {
             let mesh = assets
                 .mesh(mesh_id)
                 .ok_or(SceneError::MeshNotFound(mesh_id))?;
-            let material = assets
-                .material(mesh.material)
-                .ok_or(SceneError::MaterialNotFound(mesh.material))?;
-            let instance_transforms: Vec<RawSpatialTransform> = mesh_instances
+
+            let instances = mesh_instances
                 .iter()
-                .map(|&inst_id| {
-                    let instance = self
-                        .mesh_instances
-                        .get(inst_id)
-                        .ok_or(SceneError::MeshInstanceNotFound(inst_id))?;
-                    let entity = world
-                        .entity(instance.entity)
-                        .ok_or(SceneError::EntityNotFound(instance.entity))?;
-                    Ok(
-                        entity.transform_raw()
-                    )
-                })
-                .collect::<Result<_, SceneError>>()?;
-            let instance_buffer_range = instance_buffer.add(instance_transforms, mesh_id);
-            let command = mesh.to_render_command(
-                mesh_id,
-                material,
-                self.pipeline,
-                instance_buffer_range,
-                self.camera_bind_group,
-                self.lighting_bind_group,
-            );
-            mesh_commands.push(command);
+                .map(|&inst_id| self.mesh_instances.get(inst_id).ok_or(SceneError::MeshInstanceNotFound(inst_id)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let instances_by_material = group_instances_by_material(
+                world,
+                instances,
+                mesh.material,
+                layer_mask,
+                self.skip_orphaned_instances,
+            )?;
+
+            for (material_id, instances) in instances_by_material {
+                let material = assets
+                    .material(material_id)
+                    .ok_or(SceneError::MaterialNotFound(material_id))?;
+                let instance_buffer_range = instance_buffer.add(instances.data, mesh_id);
+                let pipeline = match (self.pipeline_override, material.double_sided) {
+                    (Some(pipeline), _) => pipeline,
+                    (None, true) => self.double_sided_pipeline,
+                    (None, false) => self.pipeline,
+                };
+                let mut command = mesh.to_render_command(
+                    mesh_id,
+                    material,
+                    pipeline,
+                    instance_buffer_range,
+                    self.camera_bind_group,
+                    self.lighting_bind_group,
+                );
+                if self.secondary_instance_stream {
+                    command.secondary_instance_buffer_range = Some(secondary_instance_buffer.add(instances.secondary, mesh_id));
+                }
+                mesh_commands.push(command);
+            }
         }
         let sky_command = self.skybox.to_render_command(
             self.sky_pipeline,
@@ -123,10 +168,207 @@ impl Scene {
 
     /// Updates and writes updateable buffers.
     ///
-    /// Currently, this is for the camera and light uniforms.
+    /// Currently, this is for the camera, lighting and sky buffers.
+    /// The writes are only queued, so they're all applied together at the next submission (ie the main pass),
+    /// rather than each needing its own.
     pub fn update_and_write_buffers(&mut self, world: &World, gpu: &GpuContext) {
         self.camera.update_and_write_uniform_buffer(world, gpu);
-        self.point_lights.update_and_write_buffer(world, gpu);
+        self.lighting.update_and_write_buffers(world, gpu);
+        self.skybox.update_and_write_buffer(world, gpu);
+    }
+
+    /// Get the camera.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Get the camera mutably.
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Get the lighting mutably, ie to add/remove lights.
+    pub fn lighting_mut(&mut self) -> &mut Lighting {
+        &mut self.lighting
+    }
+
+    /// Run the function on each point light, ie to animate them. Changes are written to the GPU on the next update.
+    pub fn for_each_point_light(&mut self, f: impl FnMut(&mut PointLight)) {
+        self.lighting.point_lights_mut().iter_mut().for_each(f);
+    }
+
+    /// Run the function on each directional light. Changes are written to the GPU on the next update.
+    pub fn for_each_directional_light(&mut self, f: impl FnMut(&mut DirectionalLight)) {
+        self.lighting.directional_lights_mut().iter_mut().for_each(f);
+    }
+
+    /// Run the function on each spot light. Changes are written to the GPU on the next update.
+    pub fn for_each_spot_light(&mut self, f: impl FnMut(&mut SpotLight)) {
+        self.lighting.spot_lights_mut().iter_mut().for_each(f);
+    }
+
+    /// Get the skybox mutably.
+    pub fn skybox_mut(&mut self) -> &mut SkyBox {
+        &mut self.skybox
+    }
+
+    /// Get the camera's bind group.
+    pub fn camera_bind_group(&self) -> BindGroupId {
+        self.camera_bind_group
+    }
+
+    /// Get the lighting's bind group.
+    pub fn lighting_bind_group(&self) -> BindGroupId {
+        self.lighting_bind_group
+    }
+
+    /// Get the IDs of all the pipelines the scene references, ie to remove them from the renderer along with it.
+    pub fn pipeline_ids(&self) -> [PipelineId; 3] {
+        [self.pipeline, self.double_sided_pipeline, self.sky_pipeline]
+    }
+
+    /// Get the IDs of all the bind groups the scene references, ie to remove them from the renderer along with it.
+    pub fn bind_group_ids(&self) -> [BindGroupId; 3] {
+        [self.camera_bind_group, self.lighting_bind_group, self.sky_bind_group]
+    }
+
+    /// Get the pipeline all meshes are drawn with instead of their own, if any.
+    pub fn pipeline_override(&self) -> Option<PipelineId> {
+        self.pipeline_override
+    }
+
+    /// Draw all meshes with the given pipeline instead of their own (ie for a normals/albedo debug view),
+    /// or `None` to go back to their own.
+    ///
+    /// The pipeline takes the same vertex buffers + bind groups as the mesh pipelines. This only applies
+    /// to forward rendering, since the deferred/clustered modes use their own pipelines.
+    pub fn set_pipeline_override(&mut self, pipeline: Option<PipelineId>) {
+        self.pipeline_override = pipeline;
+    }
+
+    /// Get how the scene treats the depth underneath it when rendered over other scenes.
+    pub fn depth(&self) -> SceneDepth {
+        self.depth
+    }
+
+    /// Set how the scene treats the depth underneath it when rendered over other scenes; it's `SceneDepth::Keep` by default.
+    ///
+    /// This doesn't apply to the first scene in a frame, which always clears it. To not depth-test at all
+    /// (ie for 2D UI drawn in order), the scene's pipelines should compare with `wgpu::CompareFunction::Always`.
+    pub fn set_depth(&mut self, depth: SceneDepth) {
+        self.depth = depth;
+    }
+
+    /// Set whether a small ambient light is applied while the scene has no lights, so it isn't rendered black.
+    ///
+    /// It's applied by default; see `Lighting::set_default_ambient_when_unlit`.
+    pub fn set_default_ambient_when_unlit(&mut self, enabled: bool) {
+        self.lighting.set_default_ambient_when_unlit(enabled);
+    }
+
+    /// Set whether instances whose entity no longer exists are skipped when rendering.
+    ///
+    /// Otherwise (the default), `to_commands` fails with `SceneError::EntityNotFound` for them.
+    pub fn set_skip_orphaned_instances(&mut self, skip: bool) {
+        self.skip_orphaned_instances = skip;
+    }
+
+    /// Remove the instances whose entity no longer exists, returning their IDs.
+    pub fn remove_orphaned_instances(&mut self, world: &World) -> Vec<MeshInstanceId> {
+        let orphaned = self
+            .mesh_instances
+            .iter()
+            .filter(|(_, instance)| world.entity(instance.entity).is_none())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for &id in &orphaned {
+            let Some(instance) = self.mesh_instances.remove(id) else {
+                continue;
+            };
+            if let Some(mesh_instances) = self.instances_by_mesh.get_mut(instance.mesh) {
+                mesh_instances.retain(|&inst_id| inst_id != id);
+            }
+        }
+        orphaned
+    }
+
+    /// Get a mesh instance.
+    pub fn instance(&self, id: MeshInstanceId) -> Option<&MeshInstance> {
+        self.mesh_instances.get(id)
+    }
+
+    /// Get the mesh a mesh instance is of.
+    pub fn mesh_of_instance(&self, id: MeshInstanceId) -> Option<MeshId> {
+        self.mesh_instances.get(id).map(|instance| instance.mesh)
+    }
+
+    /// Get the number of mesh instances, including hidden ones.
+    pub fn instance_count(&self) -> usize {
+        self.mesh_instances.len()
+    }
+
+    /// Get the number of instances of the mesh, including hidden ones.
+    pub fn instance_count_for_mesh(&self, mesh: MeshId) -> usize {
+        self.instances_by_mesh
+            .get(mesh)
+            .map_or(0, |instances| instances.len())
+    }
+
+    /// Show or hide a mesh instance, without removing it.
+    pub fn set_instance_visible(&mut self, id: MeshInstanceId, visible: bool) -> Result<(), SceneError> {
+        let instance = self
+            .mesh_instances
+            .get_mut(id)
+            .ok_or(SceneError::MeshInstanceNotFound(id))?;
+        instance.visible = visible;
+        Ok(())
+    }
+
+    /// Set a mesh instance's transform relative to its entity's, or `None` to draw it at the entity's transform.
+    pub fn set_instance_local_offset(&mut self, id: MeshInstanceId, offset: Option<SpatialTransform>) -> Result<(), SceneError> {
+        let instance = self
+            .mesh_instances
+            .get_mut(id)
+            .ok_or(SceneError::MeshInstanceNotFound(id))?;
+        instance.local_offset = offset;
+        Ok(())
+    }
+
+    /// Set one of a mesh instance's custom shader params (see `ObjectParams`).
+    pub fn set_object_param(&mut self, id: MeshInstanceId, index: usize, value: impl ObjectParam) -> Result<(), SceneError> {
+        let instance = self
+            .mesh_instances
+            .get_mut(id)
+            .ok_or(SceneError::MeshInstanceNotFound(id))?;
+        match instance.params.set(index, value) {
+            true => Ok(()),
+            false => Err(SceneError::ObjectParamOutOfRange(index)),
+        }
+    }
+
+    /// Set one of a mesh instance's secondary instance values (see `SecondaryInstanceData`).
+    pub fn set_secondary_instance_value(&mut self, id: MeshInstanceId, index: usize, value: impl ObjectParam) -> Result<(), SceneError> {
+        let instance = self
+            .mesh_instances
+            .get_mut(id)
+            .ok_or(SceneError::MeshInstanceNotFound(id))?;
+        match instance.secondary.set(index, value) {
+            true => Ok(()),
+            false => Err(SceneError::SecondaryInstanceValueOutOfRange(index)),
+        }
+    }
+
+    /// Get whether the instances' secondary data is gathered + bound with their transforms.
+    pub fn secondary_instance_stream(&self) -> bool {
+        self.secondary_instance_stream
+    }
+
+    /// Set whether the instances' secondary data is gathered + bound with their transforms (see `SecondaryInstanceData`).
+    ///
+    /// This is off by default. The scene's pipelines must read the stream for its data to have any effect,
+    /// and pipelines reading it can only draw scenes with it enabled.
+    pub fn set_secondary_instance_stream(&mut self, enabled: bool) {
+        self.secondary_instance_stream = enabled;
     }
 
     /// Add the mesh instances under that mesh, returning their IDs.
@@ -150,6 +392,52 @@ impl Scene {
     }
 }
 
+/// The instance data of a mesh's drawn instances with the same material, and their parallel secondary data.
+#[derive(Default)]
+struct MaterialInstances {
+    data: Vec<MeshInstanceData>,
+    secondary: Vec<SecondaryInstanceData>,
+}
+
+/// Get the instance data of a mesh's drawn instances, grouped by their material (as instances can override the mesh's).
+///
+/// Hidden instances, those off the `layer_mask` and those of disabled entities are left out.
+fn group_instances_by_material<'a>(
+    world: &World,
+    instances: impl IntoIterator<Item = &'a MeshInstance>,
+    mesh_material: MaterialId,
+    layer_mask: u32,
+    skip_orphaned_instances: bool,
+) -> Result<BTreeMap<MaterialId, MaterialInstances>, SceneError> {
+    let mut instances_by_material: BTreeMap<MaterialId, MaterialInstances> = BTreeMap::new();
+    for instance in instances {
+        if !instance.visible || instance.layers & layer_mask == 0 {
+            continue;
+        }
+        let entity = match world.entity(instance.entity) {
+            Some(entity) => entity,
+            None if skip_orphaned_instances => continue,
+            None => return Err(SceneError::EntityNotFound(instance.entity)),
+        };
+        // a disabled ancestor disables the whole subtree, whose transforms are stale anyway
+        if world.enabled_in_hierarchy(instance.entity) != Some(true) {
+            continue;
+        }
+        let group = instances_by_material
+            .entry(instance.material_override.unwrap_or(mesh_material))
+            .or_default();
+        group.data.push(MeshInstanceData {
+            transform: match &instance.local_offset {
+                Some(offset) => entity.transform().combine_raw(offset),
+                None => entity.transform_raw(),
+            },
+            params: instance.params,
+        });
+        group.secondary.push(instance.secondary);
+    }
+    Ok(instances_by_material)
+}
+
 #[derive(Debug, Error)]
 pub enum SceneError {
     #[error("Couldn't find mesh of ID {0:?}")]
@@ -159,5 +447,136 @@ pub enum SceneError {
     #[error("Couldn't find mesh instance for ID {0:?}")]
     MeshInstanceNotFound(MeshInstanceId),
     #[error("Couldn't find the entity of ID {0:?}")]
-    EntityNotFound(WorldEntityId)
+    EntityNotFound(WorldEntityId),
+    #[error("Object param {0} is out of range; there are only {count} params", count = ObjectParams::COUNT)]
+    ObjectParamOutOfRange(usize),
+    #[error("Secondary instance value {0} is out of range; there are only {count} values", count = SecondaryInstanceData::COUNT)]
+    SecondaryInstanceValueOutOfRange(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Quaternion, Rotation3, Vector3, Vector4, assert_abs_diff_eq};
+    use super::*;
+
+    /// Count the instances `group_instances_by_material` would draw.
+    fn drawn_count(world: &World, instances: &[MeshInstance], layer_mask: u32) -> usize {
+        group_instances_by_material(world, instances, MaterialId::default(), layer_mask, false)
+            .unwrap()
+            .values()
+            .map(|group| group.data.len())
+            .sum()
+    }
+
+    #[test]
+    fn hidden_and_off_layer_instances_are_not_drawn() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        let mut instances = vec![MeshInstance::new(MeshId::default(), entity); 3];
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 3);
+
+        instances[0].visible = false;
+        instances[1].layers = 0b10;
+        assert_eq!(drawn_count(&world, &instances, 0b01), 1);
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 2);
+
+        instances[0].visible = true;
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 3);
+    }
+
+    #[test]
+    fn instances_of_disabled_entities_are_not_drawn() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        let instances = vec![MeshInstance::new(MeshId::default(), entity); 2];
+        world.entity_mut(entity).unwrap().set_enabled(false);
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 0);
+    }
+
+    #[test]
+    fn instances_under_a_disabled_parent_are_not_drawn() {
+        let mut world = World::new();
+        let parent = world.add_entity(None, vec![], SpatialTransform::identity());
+        let child = world.add_entity(Some(parent), vec![], SpatialTransform::identity());
+        let instances = vec![MeshInstance::new(MeshId::default(), child); 2];
+        world.update_graph();
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 2);
+
+        world.entity_mut(parent).unwrap().set_enabled(false);
+        assert!(world.entity(child).unwrap().enabled());
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 0);
+
+        world.entity_mut(parent).unwrap().set_enabled(true);
+        assert_eq!(drawn_count(&world, &instances, MeshInstance::ALL_LAYERS), 2);
+    }
+
+    #[test]
+    fn orphaned_instances_are_skipped_when_enabled() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        // the world can't despawn entities yet, so use an ID that was never in it
+        let instances = [
+            MeshInstance::new(MeshId::default(), entity),
+            MeshInstance::new(MeshId::default(), WorldEntityId::default()),
+            MeshInstance::new(MeshId::default(), entity),
+        ];
+
+        let grouped = group_instances_by_material(&world, &instances, MaterialId::default(), MeshInstance::ALL_LAYERS, true).unwrap();
+        assert_eq!(grouped[&MaterialId::default()].data.len(), 2);
+        let result = group_instances_by_material(&world, &instances, MaterialId::default(), MeshInstance::ALL_LAYERS, false);
+        assert!(matches!(result, Err(SceneError::EntityNotFound(id)) if id == WorldEntityId::default()));
+    }
+
+    #[test]
+    fn local_offset_applies_on_top_of_the_entity_transform() {
+        let mut world = World::new();
+        let entity_transform = SpatialTransform {
+            position: Vector3::new(1.0, 2.0, 3.0),
+            rotation: Quaternion::from_angle_y(Deg(90.0)),
+            ..SpatialTransform::identity()
+        };
+        let entity = world.add_entity(None, vec![], entity_transform);
+        world.update_graph();
+        let mut offset_instance = MeshInstance::new(MeshId::default(), entity);
+        offset_instance.local_offset = Some(SpatialTransform {
+            position: Vector3::new(1.0, 0.0, 0.0),
+            ..SpatialTransform::identity()
+        });
+        let instances = [MeshInstance::new(MeshId::default(), entity), offset_instance];
+
+        let grouped = group_instances_by_material(&world, &instances, MaterialId::default(), MeshInstance::ALL_LAYERS, false).unwrap();
+        let positions = grouped[&MaterialId::default()]
+            .data
+            .iter()
+            .map(|data| Vector4::from(data.transform.model[3]).truncate())
+            .collect::<Vec<_>>();
+        assert_abs_diff_eq!(positions[0], Vector3::new(1.0, 2.0, 3.0), epsilon = 1e-5);
+        // +x in the entity's space is -z in the world's, once rotated a quarter turn about y
+        assert_abs_diff_eq!(positions[1], Vector3::new(1.0, 2.0, 2.0), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn secondary_data_is_grouped_parallel_to_the_instance_data() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        let mut materials: SlotMap<MaterialId, ()> = SlotMap::with_key();
+        let (mesh_material, other_material) = (materials.insert(()), materials.insert(()));
+        let mut instances = vec![MeshInstance::new(MeshId::default(), entity); 4];
+        for (index, instance) in instances.iter_mut().enumerate() {
+            instance.params.set(0, index as f32);
+            instance.secondary.set(2, index as f32);
+        }
+        instances[1].material_override = Some(other_material);
+        instances[2].visible = false;
+
+        let grouped = group_instances_by_material(&world, &instances, mesh_material, MeshInstance::ALL_LAYERS, false).unwrap();
+        for group in grouped.values() {
+            assert_eq!(group.data.len(), group.secondary.len());
+            for (data, secondary) in group.data.iter().zip(&group.secondary) {
+                assert_eq!(data.params.values[0], secondary.values[2]);
+            }
+        }
+        assert_eq!(grouped[&mesh_material].secondary.len(), 2);
+        assert_eq!(grouped[&other_material].secondary[0].values[2][0], 1.0);
+    }
 }
@@ -54,6 +54,55 @@ impl CubeMapTexture {
         Self { texture }
     }
 
+    /// Create a cube map from six (LDR) face images, ie a traditional skybox, uploading them directly into its layers.
+    ///
+    /// The faces are in wgpu's layer order: right (+X), left (-X), top (+Y), bottom (-Y), front (+Z), back (-Z).
+    /// Each is oriented as seen from inside the cube, following the usual cube map convention most skybox exporters
+    /// use (ie for OpenGL/Vulkan), so exported face PNGs can be passed as-is.
+    ///
+    /// The faces must be square and the same size. They're treated as sRGB color.
+    pub fn from_face_images(gpu: &GpuContext, faces: [&image::DynamicImage; 6], label: Option<&str>) -> anyhow::Result<Self> {
+        let size = faces[0].width();
+        if let Some(face) = faces.iter().find(|face| face.width() != size || face.height() != size) {
+            anyhow::bail!(
+                "Cube map faces must be square and the same size; expected {size}x{size}, got {}x{}",
+                face.width(),
+                face.height()
+            );
+        }
+
+        let cube = Self::new(
+            gpu,
+            size,
+            size,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label
+        );
+        for (layer, face) in faces.iter().enumerate() {
+            gpu.queue().write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: cube.inner().handle(),
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &face.to_rgba8(),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        Ok(cube)
+    }
+
     /// Get a handle to the texture.
     pub fn inner(&self) -> &GpuTexture {
         &self.texture
@@ -9,17 +9,18 @@ impl DepthTexture {
     /// The format for depth textures.
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    /// Creates a depth texture.
+    /// Creates a depth texture of the given size.
     pub fn new(
         gpu: &GpuContext,
         label: &str,
-        surface_config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
     ) -> Self {
         let device = gpu.device();
 
         let size = wgpu::Extent3d {
-            width: surface_config.width.max(1),
-            height: surface_config.height.max(1),
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
@@ -29,7 +30,10 @@ impl DepthTexture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            // copyable for reading back depth, ie for picking
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
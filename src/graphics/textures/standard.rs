@@ -1,8 +1,8 @@
 use image::GenericImageView;
-use crate::graphics::gpu::{GpuContext, texture::GpuTexture};
+use crate::graphics::gpu::{GpuContext, buffer::GpuBuffer, texture::GpuTexture};
 
 /// The bind group layout entries for a diffuse texture.
-pub const DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 4] = [
+pub const DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 7] = [
     // diffuse texture = sampler
     wgpu::BindGroupLayoutEntry {
         binding: 0,
@@ -37,9 +37,59 @@ pub const DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 4] = [
         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), // should match the filterable field of the corresponding texture entry above
         count: None,
     },
+    // lightmap/detail texture + sampler, sampled with the second UV channel
+    wgpu::BindGroupLayoutEntry {
+        binding: 4,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 5,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), // should match the filterable field of the corresponding texture entry above
+        count: None,
+    },
+    // material uniform, ie the alpha cutoff
+    wgpu::BindGroupLayoutEntry {
+        binding: 6,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    },
 ];
 
+/// How a texture's values are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma-encoded color, decoded when sampled; ie for diffuse/emissive textures.
+    Srgb,
+    /// Linear data, sampled as-is; ie for normal/roughness/AO textures.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Get the 8-bit RGBA texture format for the color space.
+    pub fn rgba8_format(&self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
 /// A normal texture from a 2D image, ie for diffuse/normal textures.
+///
+/// Cloning it shares the same GPU texture, ie for a fallback used by many materials.
+#[derive(Clone)]
 pub struct StandardTexture {
     texture: GpuTexture
 }
@@ -87,10 +137,14 @@ impl StandardTexture {
         Self { texture }
     }
     
-    /// Creates a diffuse texture from an image.
+    /// Creates a texture from an image.
+    /// 
+    /// Color textures should be `ColorSpace::Srgb`, and data textures (ie normal maps) `ColorSpace::Linear`,
+    /// otherwise they're gamma-decoded when sampled.
     pub fn from_image(
         gpu: &GpuContext,
         img: &image::DynamicImage,
+        color_space: ColorSpace,
         label: Option<&str>,
     ) -> anyhow::Result<Self> {
         let rgba = img.to_rgba8();
@@ -110,7 +164,7 @@ impl StandardTexture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: color_space.rgba8_format(),
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -147,6 +201,31 @@ impl StandardTexture {
         Ok(Self { texture })
     }
 
+    /// Create a 1x1 white texture, ie for a material without a lightmap.
+    pub fn white(gpu: &GpuContext, label: Option<&str>) -> anyhow::Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+        Self::from_image(gpu, &img, ColorSpace::Srgb, label)
+    }
+
+    /// Create a 1x1 flat normal map texture, ie as a placeholder while a material's normal map is loading.
+    pub fn flat_normal(gpu: &GpuContext, label: Option<&str>) -> anyhow::Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255])));
+        Self::from_image(gpu, &img, ColorSpace::Linear, label)
+    }
+
+    /// Create a color grading LUT that leaves colors unchanged, unrolled into a `size * size` by `size` strip.
+    ///
+    /// Each `size * size` square is a slice of blue, with red increasing to the right and green downwards,
+    /// ie the layout `HdrPipeline::set_color_lut` expects.
+    pub fn identity_color_lut(gpu: &GpuContext, size: u32, label: Option<&str>) -> anyhow::Result<Self> {
+        let max = (size - 1) as f32;
+        let to_u8 = |i: u32| (i as f32 / max * 255.0).round() as u8;
+        let img = image::RgbaImage::from_fn(size * size, size, |x, y| {
+            image::Rgba([to_u8(x % size), to_u8(y), to_u8(x / size), 255])
+        });
+        Self::from_image(gpu, &image::DynamicImage::ImageRgba8(img), ColorSpace::Srgb, label)
+    }
+
     /// Get a handle to the texture.
     pub fn inner(&self) -> &GpuTexture {
         &self.texture
@@ -155,10 +234,12 @@ impl StandardTexture {
     /// Get the default bind group layouts for a texture.
     pub fn bind_group_entries<'a>(
         diffuse_texture: &'a Self,
-        normal_texture: &'a Self
+        normal_texture: &'a Self,
+        lightmap_texture: &'a Self,
+        material_buffer: &'a GpuBuffer,
     ) -> (
-        [wgpu::BindGroupLayoutEntry; 4],
-        [wgpu::BindGroupEntry<'a>; 4],
+        [wgpu::BindGroupLayoutEntry; 7],
+        [wgpu::BindGroupEntry<'a>; 7],
     ) {
         let entries = [
             wgpu::BindGroupEntry {
@@ -177,7 +258,42 @@ impl StandardTexture {
                 binding: 3,
                 resource: wgpu::BindingResource::Sampler(&normal_texture.inner().sampler()),
             },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(lightmap_texture.inner().view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(lightmap_texture.inner().sampler()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: material_buffer.handle().as_entire_binding(),
+            },
         ];
         (DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES, entries)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::graphics::gpu::test_context;
+    use super::*;
+
+    #[test]
+    fn linear_textures_use_a_non_srgb_format() {
+        assert!(!ColorSpace::Linear.rgba8_format().is_srgb());
+        assert!(ColorSpace::Srgb.rgba8_format().is_srgb());
+
+        let Some(gpu) = test_context() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        let normal = StandardTexture::from_image(&gpu, &img, ColorSpace::Linear, None).unwrap();
+        assert_eq!(normal.inner().handle().format(), wgpu::TextureFormat::Rgba8Unorm);
+        let diffuse = StandardTexture::from_image(&gpu, &img, ColorSpace::Srgb, None).unwrap();
+        assert_eq!(diffuse.inner().handle().format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+        let flat_normal = StandardTexture::flat_normal(&gpu, None).unwrap();
+        assert!(!flat_normal.inner().handle().format().is_srgb());
+    }
+}
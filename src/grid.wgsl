@@ -0,0 +1,80 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Grid {
+    color: vec4<f32>,
+    spacing: f32,
+    fade_distance: f32,
+}
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
+struct VertexOutput {
+    @builtin(position) frag_position: vec4<f32>,
+    @location(0) near_point: vec3<f32>,
+    @location(1) far_point: vec3<f32>,
+}
+
+// Go from clip space -> world space via the inverse projection + view
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let view_pos = camera.inv_proj * vec4(ndc, 1.0);
+    let world_pos = camera.inv_view * vec4(view_pos.xyz / view_pos.w, 1.0);
+    return world_pos.xyz;
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(vec2<u32>(
+        id & 1u,
+        (id >> 1u) & 1u,
+    ));
+    let ndc = uv * 4.0 - 1.0;
+
+    var out: VertexOutput;
+    out.frag_position = vec4(ndc, 0.0, 1.0);
+    out.near_point = unproject(vec3(ndc, 0.0));
+    out.far_point = unproject(vec3(ndc, 1.0));
+    return out;
+}
+
+struct FragmentOutput {
+    @builtin(frag_depth) depth: f32,
+    @location(0) color: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // intersect the view ray with the y = 0 plane
+    let t = -in.near_point.y / (in.far_point.y - in.near_point.y);
+    if (t <= 0.0) {
+        discard;
+    }
+    let world_position = in.near_point + t * (in.far_point - in.near_point);
+
+    // anti-aliased lines, ie distance to the nearest line in units of screen pixels
+    let coord = world_position.xz / grid.spacing;
+    let derivative = fwidth(coord);
+    let line_distance = abs(fract(coord - 0.5) - 0.5) / derivative;
+    let line = 1.0 - min(min(line_distance.x, line_distance.y), 1.0);
+
+    // fade out towards the horizon, where the lines alias anyway
+    let distance = length(world_position - camera.view_position);
+    let fade = 1.0 - smoothstep(0.0, grid.fade_distance, distance);
+
+    let clip_position = camera.view_proj * vec4(world_position, 1.0);
+
+    var out: FragmentOutput;
+    out.depth = clip_position.z / clip_position.w;
+    out.color = vec4(grid.color.rgb, grid.color.a * line * fade);
+    return out;
+}
@@ -3,7 +3,7 @@ use crate::state::State;
 use std::sync::Arc;
 use wgpu::SurfaceError;
 use winit::dpi::{PhysicalSize, Size};
-use winit::event::{DeviceEvent, KeyEvent, WindowEvent};
+use winit::event::{DeviceEvent, ElementState, Ime, KeyEvent, WindowEvent};
 use winit::keyboard::PhysicalKey;
 use winit::window::CursorGrabMode;
 use winit::{application::ApplicationHandler, event_loop::ActiveEventLoop, window::Window};
@@ -37,7 +37,11 @@ impl ApplicationHandler<State<'static>> for App<'static> {
 
         #[cfg(not(target_arch = "wasm32"))]
         {
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+            let mut state = pollster::block_on(State::new(window, self.gpu_request)).unwrap();
+            if let Some(config) = self.benchmark.take() {
+                state.start_benchmark(config);
+            }
+            self.state = Some(state);
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -49,7 +53,7 @@ impl ApplicationHandler<State<'static>> for App<'static> {
                     assert!(
                         proxy
                             .send_event(
-                                State::new(window)
+                                State::new(window, self.gpu_request)
                                     .await
                                     .expect("Unable to create canvas!!!")
                             )
@@ -71,6 +75,9 @@ impl ApplicationHandler<State<'static>> for App<'static> {
                 event.window.inner_size().height,
             );
         }
+        if let Some(config) = self.benchmark.take() {
+            event.start_benchmark(config);
+        }
         self.state = Some(event);
     }
 
@@ -116,25 +123,42 @@ impl ApplicationHandler<State<'static>> for App<'static> {
                 state.update();
                 match state.render() {
                     Ok(_) => {}
-                    Err(SurfaceError::Lost | SurfaceError::Outdated) => {
-                        let size = state.window.inner_size();
-                        state.resize(size.width, size.height);
+                    // skip the frame and reconfigure; the next frame should render fine
+                    Err(SurfaceError::Lost | SurfaceError::Outdated) => state.reconfigure_surface(),
+                    Err(SurfaceError::Timeout) => log::warn!("Surface timed out; skipping frame"),
+                    Err(SurfaceError::OutOfMemory) => {
+                        log::error!("Out of memory while rendering; exiting");
+                        event_loop.exit();
                     }
                     Err(err) => {
                         log::error!("Unable to render: {err}");
                     }
                 }
                 state.reset_for_frame();
+                if state.benchmark_finished() {
+                    if let Err(err) = state.finish_benchmark() {
+                        log::error!("Unable to write benchmark results: {err}");
+                    }
+                    event_loop.exit();
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
                         state: key_state,
+                        text,
                         ..
                     },
                 ..
-            } => state.handle_key(event_loop, code, key_state),
+            } => {
+                if let (ElementState::Pressed, Some(text)) = (key_state, &text) {
+                    state.handle_text(text);
+                }
+                state.handle_key(event_loop, code, key_state)
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => state.handle_text(&text),
+            WindowEvent::MouseInput { state: button_state, button, .. } => state.handle_mouse_button(button, button_state),
             _ => {}
         }
     }
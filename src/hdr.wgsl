@@ -17,6 +17,13 @@ fn aces_tone_map(hdr: vec3<f32>) -> vec3<f32> {
     return clamp(m2 * (a / b), vec3(0.0), vec3(1.0));
 }
 
+// Encodes linear values with the sRGB transfer function, for surfaces that don't do it in hardware
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3(0.0031308));
+}
+
 struct VertexOutput {
     @location(0) uv: vec2<f32>,
     @builtin(position) clip_position: vec4<f32>,
@@ -47,10 +54,47 @@ var hdr_image: texture_2d<f32>;
 @binding(1)
 var hdr_sampler: sampler;
 
+// The color grading LUT; a 3D table unrolled into a strip of blue slices (see `HdrPipeline::set_color_lut`)
+@group(1)
+@binding(0)
+var lut_image: texture_2d<f32>;
+
+@group(1)
+@binding(1)
+var lut_sampler: sampler;
+
+// Grades a linear color with the LUT, which is indexed by the gamma-encoded color.
+// Red + green are filtered by the sampler; blue is interpolated between the two nearest slices
+fn apply_lut(linear: vec3<f32>) -> vec3<f32> {
+    let encoded = linear_to_srgb(linear);
+    let dimensions = vec2<f32>(textureDimensions(lut_image));
+    let size = dimensions.y;
+
+    let slice = encoded.b * (size - 1.0);
+    let slice_0 = floor(slice);
+    let slice_1 = min(slice_0 + 1.0, size - 1.0);
+    // the texel centres within a slice
+    let texel = encoded.rg * (size - 1.0) + 0.5;
+    let uv_0 = vec2(slice_0 * size + texel.x, texel.y) / dimensions;
+    let uv_1 = vec2(slice_1 * size + texel.x, texel.y) / dimensions;
+
+    // the LUT is sRGB, so sampling decodes it back to linear
+    let graded_0 = textureSampleLevel(lut_image, lut_sampler, uv_0, 0.0).rgb;
+    let graded_1 = textureSampleLevel(lut_image, lut_sampler, uv_1, 0.0).rgb;
+    return mix(graded_0, graded_1, slice - slice_0);
+}
+
 @fragment
 fn fs_main(vs: VertexOutput) -> @location(0) vec4<f32> {
     let hdr = textureSample(hdr_image, hdr_sampler, vs.uv);
-    let sdr = aces_tone_map(hdr.rgb);
+    let sdr = apply_lut(aces_tone_map(hdr.rgb));
     return vec4(sdr, hdr.a);
 }
- 
\ No newline at end of file
+
+// For non-sRGB surfaces, which store the output as-is
+@fragment
+fn fs_main_encode_srgb(vs: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(hdr_image, hdr_sampler, vs.uv);
+    let sdr = apply_lut(aces_tone_map(hdr.rgb));
+    return vec4(linear_to_srgb(sdr), hdr.a);
+}
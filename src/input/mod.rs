@@ -1 +1,2 @@
+pub mod recording;
 pub mod state;
\ No newline at end of file
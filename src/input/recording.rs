@@ -0,0 +1,77 @@
+use web_time::Duration;
+use super::state::InputState;
+
+/// A single recorded frame, ie its frame time + the input during it.
+#[derive(Clone)]
+pub struct InputFrame {
+    pub dt: Duration,
+    pub input: InputState,
+}
+
+/// Captures the input + frame time of each frame, so they can be played back with an `InputPlayer`.
+#[derive(Default)]
+pub struct InputRecorder {
+    frames: Vec<InputFrame>,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a frame.
+    pub fn record(&mut self, dt: Duration, input: &InputState) {
+        self.frames.push(InputFrame { dt, input: input.clone() });
+    }
+
+    /// The number of frames recorded so far.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frames have been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Finish recording, returning the recorded frames.
+    pub fn finish(self) -> Vec<InputFrame> {
+        self.frames
+    }
+}
+
+/// Feeds back recorded frames, one per update.
+///
+/// Playback only reproduces the recording if it starts from the same state the recording did.
+pub struct InputPlayer {
+    frames: Vec<InputFrame>,
+    next: usize,
+}
+
+impl InputPlayer {
+    pub fn new(frames: Vec<InputFrame>) -> Self {
+        Self { frames, next: 0 }
+    }
+
+    /// Get the next frame to play, or `None` if playback has finished.
+    pub fn next_frame(&mut self) -> Option<&InputFrame> {
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+
+    /// Whether all the frames have been played.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.frames.len()
+    }
+
+    /// Start playing from the first frame again.
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+
+    /// Stop playing, returning the frames, ie to play them again later.
+    pub fn into_frames(self) -> Vec<InputFrame> {
+        self.frames
+    }
+}
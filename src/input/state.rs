@@ -3,15 +3,18 @@ use rustc_hash::FxHashSet;
 use winit::{event::{ElementState, MouseButton, MouseScrollDelta}, keyboard::KeyCode};
 
 /// Contains the state of inputs for the current frame.
+#[derive(Clone)]
 pub struct InputState {
     // keyboard
     keys_held: FxHashSet<KeyCode>,
     keys_pressed: FxHashSet<KeyCode>,
     keys_released: FxHashSet<KeyCode>,
+    typed: String,
 
     // mouse
     mouse_pos: Vector2<f32>,
     mouse_delta: Vector2<f32>,
+    scroll_delta: Vector2<f32>,
     mouse_held: FxHashSet<MouseButton>,
     mouse_pressed: FxHashSet<MouseButton>,
     mouse_released: FxHashSet<MouseButton>,
@@ -21,13 +24,18 @@ pub struct InputState {
 }
 
 impl InputState {
+    /// How many pixels of a pixel-based scroll (ie from a touchpad) count as one line.
+    const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
     pub fn new(cursor_locked: bool) -> Self {
         Self {
             keys_held: FxHashSet::default(),
             keys_pressed: FxHashSet::default(),
             keys_released: FxHashSet::default(),
+            typed: String::new(),
             mouse_pos: Vector2 { x: 0.0, y: 0.0 },
             mouse_delta: Vector2 { x: 0.0, y: 0.0 },
+            scroll_delta: Vector2 { x: 0.0, y: 0.0 },
             mouse_held: FxHashSet::default(),
             mouse_pressed: FxHashSet::default(),
             mouse_released: FxHashSet::default(),
@@ -40,18 +48,42 @@ impl InputState {
         self.keys_held.contains(&key)
     }
 
+    /// Whether the given key was pressed this frame.
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// The text typed this frame, ie for text entry.
+    pub fn typed_this_frame(&self) -> &str { &self.typed }
+
     /// Whether the cursor is locked; typically for FPS style cameras.
     pub fn cursor_locked(&self) -> bool {self.cursor_locked }
 
+    /// Whether the given mouse button is held at this frame.
+    pub fn mouse_held(&self, button: MouseButton) -> bool {
+        self.mouse_held.contains(&button)
+    }
+
+    /// Whether the given mouse button was pressed this frame.
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_pressed.contains(&button)
+    }
+
     /// The mouse delta for the frame.
     pub fn mouse_delta(&self) -> &Vector2<f32> { &self.mouse_delta }
 
+    /// The scroll delta for the frame, in lines. Positive `y` is scrolling up/away from the user.
+    pub fn scroll_delta(&self) -> &Vector2<f32> { &self.scroll_delta }
+
     /// Refresh the input state on a new frame.
     pub fn begin_frame(&mut self) {
         self.keys_pressed.clear();
         self.keys_released.clear();
+        self.typed.clear();
         self.mouse_pressed.clear();
+        self.mouse_released.clear();
         self.mouse_delta = Vector2::zero();
+        self.scroll_delta = Vector2::zero();
     }
 
     pub fn process_key_event(&mut self, key_code: KeyCode, key_state: ElementState) {
@@ -67,6 +99,26 @@ impl InputState {
         }
     }
 
+    pub fn process_mouse_button(&mut self, button: MouseButton, button_state: ElementState) {
+        match button_state {
+            ElementState::Pressed => {
+                self.mouse_pressed.insert(button);
+                self.mouse_held.insert(button);
+            },
+            ElementState::Released => {
+                self.mouse_released.insert(button);
+                self.mouse_held.remove(&button);
+            }
+        }
+    }
+
+    /// Process a typed character. Control characters (ie backspace) are ignored, since they're available as keys.
+    pub fn process_text(&mut self, character: char) {
+        if !character.is_control() {
+            self.typed.push(character);
+        }
+    }
+
     pub fn process_cursor_delta(&mut self, delta_x: f32, delta_y: f32) {
         self.mouse_delta += Vector2 { x: delta_x, y: delta_y };
     }
@@ -76,6 +128,12 @@ impl InputState {
     }
 
     pub fn process_mouse_scroll(&mut self, change: MouseScrollDelta) {
-        log::warn!("mouse scroll input not implemented")
+        self.scroll_delta += match change {
+            MouseScrollDelta::LineDelta(x, y) => Vector2 { x, y },
+            MouseScrollDelta::PixelDelta(pos) => Vector2 {
+                x: pos.x as f32 / Self::PIXELS_PER_SCROLL_LINE,
+                y: pos.y as f32 / Self::PIXELS_PER_SCROLL_LINE,
+            },
+        };
     }
 }
\ No newline at end of file
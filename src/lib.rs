@@ -1,7 +1,10 @@
 use crate::app::App;
+use crate::benchmark::BenchmarkConfig;
+use crate::graphics::gpu::GpuRequest;
 use winit::event_loop::EventLoop;
 
 pub mod app;
+pub mod benchmark;
 pub mod graphics;
 pub mod handler;
 pub mod resources;
@@ -12,11 +15,22 @@ pub mod systems;
 pub mod example;
 pub mod debug_menu;
 pub mod debug_state;
+pub mod console;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 pub fn run() -> anyhow::Result<()> {
+    run_with_benchmark(None)
+}
+
+/// Run the app, optionally as a benchmark that exits after its frames are rendered.
+pub fn run_with_benchmark(benchmark: Option<BenchmarkConfig>) -> anyhow::Result<()> {
+    run_with_options(benchmark, GpuRequest::new())
+}
+
+/// Run the app with the given GPU request, optionally as a benchmark.
+pub fn run_with_options(benchmark: Option<BenchmarkConfig>, gpu_request: GpuRequest) -> anyhow::Result<()> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
@@ -31,6 +45,8 @@ pub fn run() -> anyhow::Result<()> {
         #[cfg(target_arch = "wasm32")]
         &event_loop,
     );
+    app.benchmark = benchmark;
+    app.gpu_request = gpu_request;
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -1,3 +1,40 @@
+use small_engine::{benchmark::BenchmarkConfig, graphics::gpu::{self, AdapterPreference, GpuRequest}};
+
 fn main() {
-    small_engine::run().unwrap();
+    // `--benchmark <frames> [output path]` renders that many frames, then exits with the timings
+    let args = std::env::args().collect::<Vec<_>>();
+    let benchmark = args
+        .iter()
+        .position(|arg| arg == "--benchmark")
+        .map(|i| BenchmarkConfig {
+            frames: match args.get(i + 1).and_then(|frames| frames.parse().ok()) {
+                Some(frames) => frames,
+                None => {
+                    eprintln!("Usage: --benchmark <frames> [output path]");
+                    std::process::exit(2);
+                }
+            },
+            // the path is optional, so a following flag isn't one
+            output_path: args
+                .get(i + 2)
+                .filter(|path| !path.starts_with("--"))
+                .map_or("benchmark.csv".into(), Into::into),
+        });
+
+    // `--list-adapters` prints the GPUs that can be picked with `--adapter <index>`
+    if args.iter().any(|arg| arg == "--list-adapters") {
+        for (index, info) in gpu::enumerate_adapters().iter().enumerate() {
+            println!("{index}: {} ({:?}, {:?})", info.name, info.backend, info.device_type);
+        }
+        return;
+    }
+    let mut gpu_request = GpuRequest::new();
+    if let Some(i) = args.iter().position(|arg| arg == "--adapter") {
+        let index = args
+            .get(i + 1)
+            .and_then(|index| index.parse().ok())
+            .expect("--adapter needs an adapter index");
+        gpu_request.adapter_preference = AdapterPreference::Index(index);
+    }
+    small_engine::run_with_options(benchmark, gpu_request).unwrap();
 }
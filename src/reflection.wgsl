@@ -0,0 +1,51 @@
+// A reflective surface, showing the planar reflection texture rendered by `Renderer::render_reflection`.
+//
+// The texture is sampled at each fragment's screen position; it's mirrored left-to-right (see `PlanarReflection`),
+// so the u coordinate is flipped back.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    view_position: vec3<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var reflection_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var reflection_sampler: sampler;
+
+struct InstanceInput {
+    @location(7) mat_1: vec4<f32>,
+    @location(8) mat_2: vec4<f32>,
+    @location(9) mat_3: vec4<f32>,
+    @location(10) mat_4: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.mat_1,
+        instance.mat_2,
+        instance.mat_3,
+        instance.mat_4
+    );
+    return camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let screen_uv = position.xy / vec2<f32>(textureDimensions(reflection_texture));
+    return textureSample(reflection_texture, reflection_sampler, vec2(1.0 - screen_uv.x, screen_uv.y));
+}
@@ -2,8 +2,8 @@ use crate::graphics::{
     gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer, texture::GpuTexture},
     render::{
         assets::AssetStore,
-        renderable::model::{self, Material, Model, ModelVertex}, renderer::Renderer,
-    }, textures::standard::StandardTexture,
+        renderable::model::{self, Material, MaterialUniform, Model, ModelVertex}, renderer::Renderer,
+    }, textures::standard::{ColorSpace, StandardTexture},
 };
 use std::io::{BufReader, Cursor};
 
@@ -56,10 +56,10 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
 }
 
 /// Load a texture from an image.
-pub async fn load_texture(file_name: &str, gpu: &GpuContext) -> anyhow::Result<StandardTexture> {
+pub async fn load_texture(file_name: &str, color_space: ColorSpace, gpu: &GpuContext) -> anyhow::Result<StandardTexture> {
     let data = load_binary(file_name).await?;
     let img = image::load_from_memory(&data)?;
-    StandardTexture::from_image(gpu, &img, Some(file_name))
+    StandardTexture::from_image(gpu, &img, color_space, Some(file_name))
 }
 
 /// Load a model from the given file into the asset store.
@@ -67,6 +67,28 @@ pub async fn load_model(
     file_name: &str,
     gpu: &GpuContext,
     renderer: &mut Renderer<'_>
+) -> anyhow::Result<Model> {
+    load_obj_model(file_name, gpu, renderer, false).await
+}
+
+/// Load a model from the given file into the asset store, with its materials' textures decoded in the background.
+///
+/// The materials start with 1x1 placeholder textures, which are swapped for the real ones as they finish decoding;
+/// call `Renderer::poll_pending_assets` each frame for that.
+pub async fn load_model_streamed(
+    file_name: &str,
+    gpu: &GpuContext,
+    renderer: &mut Renderer<'_>
+) -> anyhow::Result<Model> {
+    load_obj_model(file_name, gpu, renderer, true).await
+}
+
+/// Load a model from the given file into the asset store, optionally streaming its textures.
+async fn load_obj_model(
+    file_name: &str,
+    gpu: &GpuContext,
+    renderer: &mut Renderer<'_>,
+    stream_textures: bool,
 ) -> anyhow::Result<Model> {
     let obj_text = load_string(file_name).await?;
     let obj_cursor = Cursor::new(obj_text);
@@ -86,80 +108,76 @@ pub async fn load_model(
     )
     .await?;
 
+    // OBJ has no lightmaps, so the materials share a white one, which has no effect
+    let white_lightmap = StandardTexture::white(gpu, Some("lightmap_texture"))?;
     let mut materials = Vec::new();
+    let mut streamed_textures = Vec::new();
     for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture, gpu).await?;
-        let normal_texture = load_texture(&m.normal_texture, gpu).await?;
-        let layout_entries =
-            StandardTexture::bind_group_entries(&diffuse_texture, &normal_texture);
+        let (diffuse_texture, normal_texture) = match stream_textures {
+            true => {
+                // only the bytes are loaded now; they're decoded once the material has an ID
+                streamed_textures.push((load_binary(&m.diffuse_texture).await?, load_binary(&m.normal_texture).await?));
+                (
+                    StandardTexture::white(gpu, Some("placeholder_diffuse_texture"))?,
+                    StandardTexture::flat_normal(gpu, Some("placeholder_normal_texture"))?,
+                )
+            }
+            // normal maps are data, so they mustn't be gamma-decoded
+            false => (
+                load_texture(&m.diffuse_texture, ColorSpace::Srgb, gpu).await?,
+                load_texture(&m.normal_texture, ColorSpace::Linear, gpu).await?,
+            ),
+        };
+        let lightmap_texture = white_lightmap.clone();
+        // OBJ materials are opaque; cutout can be enabled after loading
+        let alpha_cutoff = None;
+        let uniform_buffer = MaterialUniform::new(alpha_cutoff).create_buffer(&m.name, gpu);
+        let layout_entries = StandardTexture::bind_group_entries(
+            &diffuse_texture,
+            &normal_texture,
+            &lightmap_texture,
+            &uniform_buffer,
+        );
         let bind_group =
             GpuBindGroup::create_default(file_name, gpu, &layout_entries.0, &layout_entries.1);
         let bind_group_id = renderer.add_bind_groups(vec![bind_group])[0];
+        let double_sided = is_double_sided(&m);
         materials.push(Material {
             name: m.name,
             diffuse_texture,
             normal_texture,
-            bind_group: bind_group_id
+            lightmap_texture,
+            bind_group: bind_group_id,
+            alpha_cutoff,
+            uniform_buffer,
+            double_sided,
         })
     }
-    let material_ids = renderer
-        .get_assets_store()
-        .add_materials(materials);
+    let assets = renderer.get_assets_store();
+    let material_ids = assets.add_materials(materials);
+    for (&material, (diffuse_bytes, normal_bytes)) in material_ids.iter().zip(streamed_textures) {
+        assets.load_material_textures(material, diffuse_bytes, normal_bytes);
+    }
     let meshes = models
         .into_iter()
         .map(|mut m| {
-            let mut vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| {
-                    if m.mesh.normals.is_empty() {
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [
-                                m.mesh.texcoords[i * 2],
-                                1.0 - m.mesh.texcoords[i * 2 + 1],
-                            ],
-                            normal: [0.0, 0.0, 0.0],
-                            tangent: [0.0; 3],
-                            bitangent: [0.0; 3]
-                        }
-                    } else {
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [
-                                m.mesh.texcoords[i * 2],
-                                1.0 - m.mesh.texcoords[i * 2 + 1],
-                            ],
-                            normal: [
-                                m.mesh.normals[i * 3],
-                                m.mesh.normals[i * 3 + 1],
-                                m.mesh.normals[i * 3 + 2],
-                            ],
-                            tangent: [0.0; 3],
-                            bitangent: [0.0; 3]
-                        }
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            calculate_tangent_and_bitangents(&mut vertices, &mut m);
+            let vertices = obj_vertices(&mut m);
 
             let vertex_buffer = GpuBuffer::create_vertex(
                 &format!("{:?}_vertex_buffer", file_name),
                 gpu,
                 bytemuck::cast_slice(&vertices),
             );
-            let index_buffer = GpuBuffer::create_index(
+            // ie a point cloud without faces; the vertices are drawn in order
+            let index_buffer = (!m.mesh.indices.is_empty()).then(|| GpuBuffer::create_index(
                 &format!("{:?}_index_buffer", file_name),
                 gpu,
                 bytemuck::cast_slice(&m.mesh.indices),
-            );
+            ));
+            let num_elements = match index_buffer {
+                Some(_) => m.mesh.indices.len(),
+                None => vertices.len(),
+            } as u32;
 
             let material_index = m.mesh.material_id.unwrap_or(0);
             let material_id = material_ids[material_index];
@@ -168,8 +186,10 @@ pub async fn load_model(
                 name: file_name.to_string(),
                 vertex_buffer,
                 index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
+                num_elements,
                 material: material_id,
+                base_vertex: 0,
+                first_index: 0,
             }
         })
         .collect::<Vec<_>>();
@@ -184,6 +204,83 @@ pub async fn load_model(
     })
 }
 
+/// Create a mesh's vertices from the OBJ model, with its tangents if it has usable UVs.
+fn obj_vertices(m: &mut tobj::Model) -> Vec<ModelVertex> {
+    let mut vertices = (0..m.mesh.positions.len() / 3)
+        .map(|i| {
+            // OBJ vertex colors are RGB only
+            let color = if m.mesh.vertex_color.is_empty() {
+                [1.0; 4]
+            } else {
+                [
+                    m.mesh.vertex_color[i * 3],
+                    m.mesh.vertex_color[i * 3 + 1],
+                    m.mesh.vertex_color[i * 3 + 2],
+                    1.0,
+                ]
+            };
+            // OBJ only has one UV channel, so the second one is the same
+            let tex_coords = if m.mesh.texcoords.is_empty() {
+                [0.0; 2]
+            } else {
+                [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]]
+            };
+            if m.mesh.normals.is_empty() {
+                model::ModelVertex {
+                    position: [
+                        m.mesh.positions[i * 3],
+                        m.mesh.positions[i * 3 + 1],
+                        m.mesh.positions[i * 3 + 2],
+                    ],
+                    tex_coords,
+                    normal: [0.0, 0.0, 0.0],
+                    tangent: [0.0; 3],
+                    bitangent: [0.0; 3],
+                    color,
+                    tex_coords2: tex_coords,
+                }
+            } else {
+                model::ModelVertex {
+                    position: [
+                        m.mesh.positions[i * 3],
+                        m.mesh.positions[i * 3 + 1],
+                        m.mesh.positions[i * 3 + 2],
+                    ],
+                    tex_coords,
+                    normal: [
+                        m.mesh.normals[i * 3],
+                        m.mesh.normals[i * 3 + 1],
+                        m.mesh.normals[i * 3 + 2],
+                    ],
+                    tangent: [0.0; 3],
+                    bitangent: [0.0; 3],
+                    color,
+                    tex_coords2: tex_coords,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // without UVs there's no tangent space, so leave the tangents zeroed
+    if !m.mesh.texcoords.is_empty() {
+        calculate_tangent_and_bitangents(&mut vertices, m);
+    }
+    vertices
+}
+
+/// Whether the OBJ material has a two-sided hint.
+///
+/// MTL has no standard keyword for this, so we check the common exporter-specific ones.
+fn is_double_sided(material: &tobj::Material) -> bool {
+    ["two_sided", "double_sided", "twosided"]
+        .iter()
+        .filter_map(|key| material.unknown_param.get(*key))
+        .any(|val| matches!(val.trim(), "1" | "on" | "true"))
+}
+
+/// Calculate the vertices' tangents and bitangents from their UVs.
+///
+/// Triangles with degenerate UVs are skipped, so vertices only in such triangles keep zero tangents.
 fn calculate_tangent_and_bitangents(vertices: &mut Vec<ModelVertex>, model: &mut tobj::Model) {
     let indices = &model.mesh.indices;
     let mut triangles_included = vec![0; vertices.len()];
@@ -207,7 +304,11 @@ fn calculate_tangent_and_bitangents(vertices: &mut Vec<ModelVertex>, model: &mut
         let delta_uv1 = uv1 - uv0;
         let delta_uv2 = uv2 - uv0;
 
-        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / determinant;
         let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
 
         // use negative r to enable right-handed normal (?)
@@ -232,10 +333,63 @@ fn calculate_tangent_and_bitangents(vertices: &mut Vec<ModelVertex>, model: &mut
         triangles_included[c[2] as usize] += 1;
     }
 
-     for (i, n) in triangles_included.into_iter().enumerate() {
+    for (i, n) in triangles_included.into_iter().enumerate() {
+        if n == 0 {
+            continue;
+        }
         let denom = 1.0 / n as f32;
         let v = &mut vertices[i];
         v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
         v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load the models from OBJ text, without materials.
+    fn parse_obj(obj: &str) -> Vec<tobj::Model> {
+        let options = tobj::LoadOptions { triangulate: true, single_index: true, ..Default::default() };
+        let (models, _) = tobj::load_obj_buf(&mut BufReader::new(Cursor::new(obj)), &options, |_| {
+            Err(tobj::LoadError::OpenFileFailed)
+        })
+        .unwrap();
+        models
+    }
+
+    fn has_tangents(vertices: &[ModelVertex]) -> bool {
+        vertices.iter().any(|vertex| vertex.tangent != [0.0; 3])
+    }
+
+    fn assert_no_nans(vertices: &[ModelVertex]) {
+        for vertex in vertices {
+            assert!(vertex.tangent.iter().chain(&vertex.bitangent).all(|v| v.is_finite()), "{vertex:?}");
+        }
+    }
+
+    #[test]
+    fn model_without_uvs_has_no_tangents() {
+        let mut models = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n");
+        let vertices = obj_vertices(&mut models[0]);
+        assert!(!has_tangents(&vertices));
+        assert_eq!(vertices.len(), 3);
+        assert_no_nans(&vertices);
+    }
+
+    #[test]
+    fn degenerate_uvs_are_skipped() {
+        let mut models = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5 0.5\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n");
+        let vertices = obj_vertices(&mut models[0]);
+        assert!(!has_tangents(&vertices));
+        assert_no_nans(&vertices);
+    }
+
+    #[test]
+    fn model_with_uvs_has_tangents() {
+        let mut models = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\n");
+        let vertices = obj_vertices(&mut models[0]);
+        assert!(has_tangents(&vertices));
+        assert_no_nans(&vertices);
+    }
+}
@@ -21,14 +21,42 @@ var<storage, read> point_lights: array<PointLight>;
 @group(2) @binding(1)
 var<uniform> point_light_count: u32;
 
+struct DirectionalLight {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+}
+
+@group(2) @binding(2)
+var<storage, read> directional_lights: array<DirectionalLight>;
+
+@group(2) @binding(3)
+var<uniform> directional_light_count: u32;
+
+struct SpotLight {
+    position: vec3<f32>,
+    inner_cutoff: f32,
+    direction: vec3<f32>,
+    outer_cutoff: f32,
+    color: vec3<f32>,
+}
+
+@group(2) @binding(4)
+var<storage, read> spot_lights: array<SpotLight>;
+
+@group(2) @binding(5)
+var<uniform> spot_light_count: u32;
+
+@group(2) @binding(6)
+var<uniform> ambient_light: vec4<f32>;
+
 struct InstanceInput {
-    @location(5) mat_1: vec4<f32>,
-    @location(6) mat_2: vec4<f32>,
-    @location(7) mat_3: vec4<f32>,
-    @location(8) mat_4: vec4<f32>,
-    @location(9) mat_5: vec3<f32>,
-    @location(10) mat_6: vec3<f32>,
-    @location(11) mat_7: vec3<f32>,
+    @location(7) mat_1: vec4<f32>,
+    @location(8) mat_2: vec4<f32>,
+    @location(9) mat_3: vec4<f32>,
+    @location(10) mat_4: vec4<f32>,
+    // the instance's custom params (see `ObjectParams`)
+    @location(11) param_0: vec4<f32>,
+    @location(12) param_1: vec4<f32>,
 }
 
 struct VertexInput {
@@ -37,6 +65,8 @@ struct VertexInput {
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
     @location(4) bitangent: vec3<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) tex_coords2: vec2<f32>,
 }
 
 struct VertexOutput {
@@ -46,6 +76,33 @@ struct VertexOutput {
     @location(2) world_normal: vec3<f32>,
     @location(3) world_tangent: vec3<f32>,
     @location(4) world_bitangent: vec3<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) tex_coords2: vec2<f32>,
+    @location(7) @interpolate(flat) param_0: vec4<f32>,
+    @location(8) @interpolate(flat) param_1: vec4<f32>,
+}
+
+// Normalize the vector, leaving zero vectors as-is
+fn safe_normalize(v: vec3<f32>) -> vec3<f32> {
+    let length_squared = dot(v, v);
+    if (length_squared == 0.0) {
+        return v;
+    }
+    return v * inverseSqrt(length_squared);
+}
+
+// The inverse-transpose of the model matrix's upper 3x3, for transforming normals.
+// The cofactor matrix over the determinant is that inverse-transpose.
+fn normal_matrix_of(model: mat4x4<f32>) -> mat3x3<f32> {
+    let c0 = model[0].xyz;
+    let c1 = model[1].xyz;
+    let c2 = model[2].xyz;
+    let cofactor = mat3x3<f32>(cross(c1, c2), cross(c2, c0), cross(c0, c1));
+    let det = dot(c0, cofactor[0]);
+    if (det == 0.0) {
+        return mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    }
+    return cofactor * (1.0 / det);
 }
 
 @vertex
@@ -60,11 +117,7 @@ fn vs_main(
         instance.mat_3,
         instance.mat_4
     );
-    let normal_matrix = mat3x3<f32>(
-        instance.mat_5,
-        instance.mat_6,
-        instance.mat_7
-    );
+    let normal_matrix = normal_matrix_of(model_matrix);
 
     let world_position = model_matrix * vec4<f32>(model.position, 1.0);
     
@@ -73,8 +126,13 @@ fn vs_main(
     out.tex_coords = model.tex_coords;
     out.world_position = world_position.xyz;
     out.world_normal = normalize(normal_matrix * model.normal);
-    out.world_tangent = normalize(normal_matrix * model.tangent);
-    out.world_bitangent = normalize(normal_matrix * model.bitangent);
+    // meshes without tangents have zeroed ones, which mustn't be normalized
+    out.world_tangent = safe_normalize(normal_matrix * model.tangent);
+    out.world_bitangent = safe_normalize(normal_matrix * model.bitangent);
+    out.color = model.color;
+    out.tex_coords2 = model.tex_coords2;
+    out.param_0 = instance.param_0;
+    out.param_1 = instance.param_1;
 
     return out;
 }
@@ -91,48 +149,102 @@ var normal_texture: texture_2d<f32>;
 @group(0) @binding(3)
 var normal_sampler: sampler;
 
+@group(0) @binding(4)
+var lightmap_texture: texture_2d<f32>;
+
+@group(0) @binding(5)
+var lightmap_sampler: sampler;
+
+struct Material {
+    // fragments with a lower diffuse alpha are discarded; 0 for opaque materials
+    alpha_cutoff: f32,
+}
+
+@group(0) @binding(6)
+var<uniform> material: Material;
+
+// The diffuse + specular contribution of one light, with all directions in tangent space
+fn blinn_phong(
+    normal: vec3<f32>,
+    light_dir: vec3<f32>,
+    view_dir: vec3<f32>,
+    light_color: vec3<f32>,
+    object_color: vec3<f32>,
+) -> vec3<f32> {
+    let half_dir = normalize(view_dir + light_dir);
+
+    // Diffuse
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+    let diffuse_color = light_color * diffuse_strength;
+
+    // Specular
+    let spec_strength = pow(max(dot(normal, half_dir), 0.0), 64.0);
+    let spec_color = light_color * spec_strength;
+
+    return diffuse_color * object_color + spec_color;
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Reconstruct orthonormal TBN matrix from interpolated vectors
-    let t_vector = normalize(in.world_tangent);
-    let b_vector = normalize(in.world_bitangent);
-    let n_vector = normalize(in.world_normal);
+fn fs_main(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    // Reconstruct orthonormal TBN matrix from interpolated vectors;
+    // back faces only reach here for double-sided materials, so flip their normal
+    let n_vector = select(-1.0, 1.0, front_facing) * normalize(in.world_normal);
+    var t_vector = safe_normalize(in.world_tangent);
+    var b_vector = safe_normalize(in.world_bitangent);
+
+    // Without tangents we can't normal map, so use any basis around the vertex normal
+    let has_tangents = dot(t_vector, t_vector) > 0.0 && dot(b_vector, b_vector) > 0.0;
+    if (!has_tangents) {
+        let up = select(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), abs(n_vector.y) > 0.99);
+        t_vector = normalize(cross(up, n_vector));
+        b_vector = cross(n_vector, t_vector);
+    }
     let tangent_matrix = transpose(mat3x3<f32>(t_vector, b_vector, n_vector));
 
-    let object_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords);
+    let lightmap_color = textureSample(lightmap_texture, lightmap_sampler, in.tex_coords2);
+    let object_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords) * in.color * lightmap_color;
     let object_normal = textureSample(normal_texture, normal_sampler, in.tex_coords);
+
+    // Alpha cutout, after all the sampling so it's still in uniform control flow
+    if (object_color.a < material.alpha_cutoff) {
+        discard;
+    }
     
     // Ambient lighting
-    let ambient_strength = 0.0;
-    let ambient_color_base = vec3<f32>(1.0, 1.0, 1.0);
-    let ambient_color = ambient_strength * ambient_color_base;
-    var result = ambient_color * object_color.xyz;
+    var result = ambient_light.rgb * object_color.xyz;
 
     // Get tangent-space normal from normal map
-    let tangent_normal = normalize(object_normal.xyz * 2.0 - 1.0);
+    let tangent_normal = select(vec3(0.0, 0.0, 1.0), normalize(object_normal.xyz * 2.0 - 1.0), has_tangents);
+
+    let view_pos = camera.view_position.xyz;
+    let tangent_view_dir = normalize(tangent_matrix * (view_pos - in.world_position));
 
     // Calculate lighting for each point light
     for (var i = 0u; i < point_light_count; i++) {
         let light = point_lights[i];
-        let view_pos = camera.view_position.xyz;
-        
         let world_light_dir = light.position - in.world_position;
-        let world_view_dir = view_pos - in.world_position;
-        
         let tangent_light_dir = normalize(tangent_matrix * world_light_dir);
-        let tangent_view_dir = normalize(tangent_matrix * world_view_dir);
-        let tangent_half_dir = normalize(tangent_view_dir + tangent_light_dir);
-
-        // Diffuse
-        let diffuse_strength = max(dot(tangent_normal, tangent_light_dir), 0.0);
-        let diffuse_color = light.color * diffuse_strength;
-            
-        // Specular
-        let spec_strength = pow(max(dot(tangent_normal, tangent_half_dir), 0.0), 64.0);
-        let spec_color = light.color * spec_strength;
-
-        result += diffuse_color * object_color.xyz;
-        result += spec_color;
+        result += blinn_phong(tangent_normal, tangent_light_dir, tangent_view_dir, light.color, object_color.xyz);
+    }
+
+    // Calculate lighting for each directional light
+    for (var i = 0u; i < directional_light_count; i++) {
+        let light = directional_lights[i];
+        let tangent_light_dir = normalize(tangent_matrix * -light.direction);
+        result += blinn_phong(tangent_normal, tangent_light_dir, tangent_view_dir, light.color, object_color.xyz);
+    }
+
+    // Calculate lighting for each spot light, fading out between the inner + outer cone
+    for (var i = 0u; i < spot_light_count; i++) {
+        let light = spot_lights[i];
+        let world_light_dir = light.position - in.world_position;
+        let theta = dot(normalize(world_light_dir), normalize(-light.direction));
+        let intensity = smoothstep(light.outer_cutoff, light.inner_cutoff, theta);
+        let tangent_light_dir = normalize(tangent_matrix * world_light_dir);
+        result += intensity * blinn_phong(tangent_normal, tangent_light_dir, tangent_view_dir, light.color, object_color.xyz);
     }
 
     return vec4<f32>(result, object_color.a);
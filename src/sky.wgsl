@@ -15,6 +15,14 @@ var env_map: texture_cube<f32>;
 @binding(1)
 var env_sampler: sampler;
 
+struct Sky {
+    // multiplied into the view ray, ie to flip the cubemap's handedness
+    ray_scale: vec3<f32>,
+}
+@group(1)
+@binding(2)
+var<uniform> sky: Sky;
+
 struct VertexOutput {
     @builtin(position) frag_position: vec4<f32>,
     @location(0) clip_position: vec4<f32>,
@@ -45,6 +53,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
     // go from view space -> world space, since view space means we're always at (0, 0, 0)...
     var ray_direction = normalize((camera.inv_view * vec4(view_ray_direction, 0.0)).xyz);
+    ray_direction *= sky.ray_scale;
 
     // finally, sample from the skybox cubemap
     let sample = textureSample(env_map, env_sampler, ray_direction);
@@ -1,17 +1,16 @@
-use cgmath::{Quaternion, Rotation3, Vector3, Zero};
+use cgmath::{InnerSpace, Quaternion, Rotation3, Vector3, Zero};
 use egui::ViewportId;
 use egui_wgpu::winit::Painter;
 use egui_wgpu::{RenderState, RendererOptions, WgpuConfiguration, WgpuSetup, WgpuSetupExisting};
 use wgpu::rwh::{HasDisplayHandle, HasWindowHandle};
-use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use std::num::NonZero;
 use std::sync::Arc;
-use web_time::Instant;
-use wgpu::{Backends, PresentMode, TextureFormat};
+use web_time::{Duration, Instant};
+use wgpu::{PresentMode, TextureFormat};
 use wgpu::{
     BindGroupLayoutDescriptor, CompareFunction, DepthBiasState, DepthStencilState,
-    DeviceDescriptor, ExperimentalFeatures, Features, Instance, InstanceDescriptor, Limits,
-    PowerPreference, RequestAdapterOptions, StencilState, SurfaceConfiguration, SurfaceError,
+    DeviceDescriptor, ExperimentalFeatures, Features, Limits, StencilState, SurfaceConfiguration, SurfaceError,
     TextureUsages, Trace,
 };
 use winit::{event_loop::ActiveEventLoop, keyboard::KeyCode, window::Window};
@@ -19,33 +18,52 @@ use winit::{event_loop::ActiveEventLoop, keyboard::KeyCode, window::Window};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+use crate::benchmark::{Benchmark, BenchmarkConfig};
 use crate::core::entity::spatial_transform::SpatialTransform;
+use crate::console::Console;
 use crate::core::world::World;
-use crate::debug_menu::DebugMenu;
+use crate::debug_menu::{DebugMenu, DebugMenuFrame};
 use crate::example::{generate_one_big_entity, generated_spaced_entities};
-use crate::graphics::gpu::GpuContext;
-use crate::graphics::gpu::bind_group::GpuBindGroup;
-use crate::graphics::gpu::pipeline::GpuPipeline;
+use crate::graphics::gpu::{self, GpuContext, GpuRequest};
+use crate::graphics::gpu::pipeline::{GpuPipeline, GpuPipelineDescriptor};
 use crate::graphics::gpu::texture::GpuTexture;
+use crate::graphics::gpu::vertex::Vertex;
 use crate::graphics::render::assets::AssetStore;
 use crate::graphics::render::hdr::HdrPipeline;
-use crate::graphics::render::renderable::model::MeshInstance;
+use crate::graphics::render::renderable::model::{MeshInstance, Model};
 use crate::graphics::render::renderable::model::ModelVertex;
 use crate::graphics::render::renderable::skybox::SkyBox;
-use crate::graphics::render::renderer::Renderer;
-use crate::graphics::scene::Scene;
+use crate::graphics::render::renderer::{RenderError, Renderer};
+use crate::graphics::scene::{Scene, SceneSky};
 use crate::graphics::scene::instance_buffer::MeshInstanceData;
-use crate::graphics::scene::light::point::{PointLight, PointLightCollection};
+use crate::graphics::scene::light::Lighting;
+use crate::graphics::scene::light::directional::{DEFAULT_DIRECTIONAL_LIGHT_CAPACITY, DirectionalLightCollection};
+use crate::graphics::scene::light::point::{DEFAULT_POINT_LIGHT_CAPACITY, PointLight, PointLightCollection};
+use crate::graphics::scene::light::spot::{DEFAULT_SPOT_LIGHT_CAPACITY, SpotLightCollection};
 use crate::graphics::textures::depth::DepthTexture;
 use crate::graphics::textures::standard::DIFFUSE_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::input::recording::{InputFrame, InputPlayer, InputRecorder};
 use crate::input::state::InputState;
 use crate::resources;
 use crate::resources::hdr::HdrLoader;
 use crate::systems::camera::{Camera, CameraType, create_camera_bind_group};
 use crate::systems::camera::perspective::PerspectiveCamera;
 use crate::systems::controller::freecam::FreecamController;
+use crate::systems::controller::zoom::ZoomController;
 use crate::debug_state::DebugState;
 
+/// How far the camera can get from the origin before the world is rebased around it.
+const REBASE_DISTANCE: f32 = 1000.0;
+
+/// The key toggling whether the simulation is paused.
+const PAUSE_KEY: KeyCode = KeyCode::KeyP;
+
+/// The key advancing the paused simulation by a single step.
+const STEP_KEY: KeyCode = KeyCode::Period;
+
+/// How much time passes for the simulation in a single step while paused, ie one frame at 60 FPS.
+const STEP_DELTA_TIME: Duration = Duration::from_micros(16_667);
+
 // The state of the game.
 pub struct State<'a> {
     pub window: Arc<Window>,
@@ -56,31 +74,35 @@ pub struct State<'a> {
     scene: Scene,
     last_frame_update: Instant,
     freecam: FreecamController,
+    zoom: ZoomController,
     debug_menu: DebugMenu,
     debug_state: DebugState,
+    console: Console,
+    model: Model,
+    is_minimized: bool,
+    benchmark: Option<Benchmark>,
+    /// Whether the simulation is paused; the scene still renders, but controllers don't update.
+    paused: bool,
+    /// Whether to advance the paused simulation by a single step on the next update.
+    single_step_requested: bool,
+    /// Records each frame's input while set, ie to reproduce a bug.
+    input_recorder: Option<InputRecorder>,
+    /// Plays back recorded input while set, in place of the window's input.
+    input_player: Option<InputPlayer>,
+    /// The last recording finished from the console, for `play_input`.
+    last_recording: Vec<InputFrame>,
 }
 
 impl<'a> State<'a> {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<State<'a>> {
+    pub async fn new(window: Arc<Window>, gpu_request: GpuRequest) -> anyhow::Result<State<'a>> {
         let size = window.inner_size();
 
-        let instance = Instance::new(&InstanceDescriptor {
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: Backends::PRIMARY,
-            #[cfg(target_arch = "wasm32")]
-            backends: Backends::GL,
-            ..Default::default()
-        });
+        let instance = gpu::create_instance();
 
         let surface = instance.create_surface(window.clone()).unwrap();
 
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
+        let adapter = gpu::request_adapter(&instance, &surface, gpu_request.adapter_preference).await?;
+        log::info!("Using adapter: {}", adapter.get_info().name);
 
         let (device, queue) = adapter
             .request_device(&DeviceDescriptor {
@@ -152,35 +174,45 @@ impl<'a> State<'a> {
 
         // lighting
         let cam_light = PointLight::new(cam_entity_id, Vector3::new(1.0, 1.0, 1.0));
-        let point_light_collection = PointLightCollection::new("point_light_collection", vec![cam_light], &gpu);
-        let point_light_bind_group = point_light_collection.create_bind_group("point_light_collection_bind_group", &gpu);
-
-        // render pipeline
-        let pipeline = GpuPipeline::create_default(
-            "basic_pipeline",
+        let point_light_collection = PointLightCollection::new("point_light_collection", vec![cam_light], DEFAULT_POINT_LIGHT_CAPACITY, &gpu);
+        let directional_light_collection = DirectionalLightCollection::new("directional_light_collection", vec![], DEFAULT_DIRECTIONAL_LIGHT_CAPACITY, &gpu);
+        let spot_light_collection = SpotLightCollection::new("spot_light_collection", vec![], DEFAULT_SPOT_LIGHT_CAPACITY, &gpu);
+        let lighting = Lighting::new(&gpu, point_light_collection, directional_light_collection, spot_light_collection);
+        let lighting_bind_group = lighting.create_bind_group("lighting_bind_group", &gpu);
+
+        // render pipelines; double-sided materials use the unculled one
+        let create_mesh_pipeline = |label: &str, cull_mode: Option<wgpu::Face>| GpuPipeline::create(
+            label,
             &gpu,
             &[
                 &texture_bind_group_layout,
                 &camera_bind_group.layout(),
-                &point_light_bind_group.layout(),
+                &lighting_bind_group.layout(),
             ],
             &[ModelVertex::desc(), MeshInstanceData::desc()],
             &shader,
             &shader,
-            Some(DepthStencilState {
-                format: DepthTexture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: StencilState::default(),
-                bias: DepthBiasState::default(),
-            }),
-            wgpu::PrimitiveTopology::TriangleList,
-            HdrPipeline::COLOR_FORMAT
+            GpuPipelineDescriptor {
+                depth_stencil: Some(DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: CompareFunction::Less,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                cull_mode,
+                color_targets: vec![GpuPipeline::color_target(HdrPipeline::COLOR_FORMAT)],
+                ..Default::default()
+            },
         );
+        let pipeline = create_mesh_pipeline("basic_pipeline", Some(wgpu::Face::Back));
+        let double_sided_pipeline = create_mesh_pipeline("double_sided_pipeline", None);
 
         // renderer
         let mut renderer = Renderer::new(gpu.clone(), surface, config, AssetStore::new());
-        let pipeline_id = renderer.add_pipelines(vec![pipeline])[0];
+        let pipeline_ids = renderer.add_pipelines(vec![pipeline, double_sided_pipeline]);
+        let pipeline_id = pipeline_ids[0];
+        let double_sided_pipeline_id = pipeline_ids[1];
 
         // object
         let obj_model = resources::general::load_model("cube.obj", &gpu, &mut renderer)
@@ -191,75 +223,29 @@ impl<'a> State<'a> {
         let hdr_loader = HdrLoader::new(&gpu);
         let sky_texture_bytes = resources::general::load_binary("pure-sky.hdr").await?;
         let sky_texture = hdr_loader.from_equirect_bytes(&gpu, &sky_texture_bytes, 1080, "Sky Texture")?;
-        let sky_bind_group = GpuBindGroup::create_default(
-            "sky_bind_group", 
-            &gpu, 
-            &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                        view_dimension: wgpu::TextureViewDimension::Cube,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
-                    count: None,
-                },
-            ], 
-            &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(sky_texture.inner().view()),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(sky_texture.inner().sampler()),
-                },
-            ]
-        );
-        let shader = device.create_shader_module(wgpu::include_wgsl!("sky.wgsl")); 
-        let depth_stencil = wgpu::DepthStencilState {
-            format: DepthTexture::DEPTH_FORMAT,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::LessEqual,
-            stencil: Default::default(),
-            bias: Default::default(),
-        };
-        let sky_pipeline = GpuPipeline::create_default(
-            "skybox_pipeline",
-            &gpu,
-            &[camera_bind_group.layout(), sky_bind_group.layout()],
-            &[],
-            &shader,
-            &shader,
-            Some(depth_stencil),
-            wgpu::PrimitiveTopology::TriangleList,
-            HdrPipeline::COLOR_FORMAT,
-        );
+        let skybox = SkyBox::new(&gpu, "skybox".into(), sky_texture);
+        let sky_bind_group = skybox.create_bind_group("sky_bind_group", &gpu);
+        let sky_pipeline = skybox.create_pipeline("skybox_pipeline", &gpu, &camera_bind_group, &sky_bind_group);
         let sky_pipeline_id = renderer.add_pipelines(vec![sky_pipeline])[0];
-        let skybox = SkyBox::new("skybox".into(), sky_texture);
   
 
         // scene
-        let bind_group_ids = renderer.add_bind_groups(vec![camera_bind_group, point_light_bind_group, sky_bind_group]);
+        let bind_group_ids = renderer.add_bind_groups(vec![camera_bind_group, lighting_bind_group, sky_bind_group]);
         let camera_bind_group_id = bind_group_ids[0];
         let lighting_bind_group_id = bind_group_ids[1];
         let sky_bind_group_id = bind_group_ids[2]; 
         let mut scene = Scene::new(
             camera,
-            point_light_collection,
+            lighting,
             pipeline_id,
+            double_sided_pipeline_id,
             camera_bind_group_id,
             lighting_bind_group_id,
-            skybox,
-            sky_pipeline_id,
-            sky_bind_group_id
+            SceneSky {
+                skybox,
+                pipeline: sky_pipeline_id,
+                bind_group: sky_bind_group_id,
+            },
         );
 
         // scene nodes + mesh instances
@@ -269,7 +255,7 @@ impl<'a> State<'a> {
             .map(|&mesh| {
                 let instances = entities
                     .iter()
-                    .map(|&entity| MeshInstance { mesh, entity })
+                    .map(|&entity| MeshInstance::new(mesh, entity))
                     .collect::<Vec<_>>();
                 let instance_ids = scene.add_mesh_instances(mesh, instances);
                 (mesh, instance_ids)
@@ -282,14 +268,24 @@ impl<'a> State<'a> {
         // freecam
         let freecam = FreecamController::new(cam_entity_id);
 
+        // zoom
+        let zoom = ZoomController::new(10.0, 90.0);
+
         // debug menu
         let debug_menu = DebugMenu::new(
             &gpu, 
             &window.display_handle().unwrap(), 
-            window.inner_size()
+            window.inner_size(),
+            renderer.target_format(),
+            1,
+            None
         );
         let debug_state = DebugState::new();
 
+        // console
+        let mut console = Console::new();
+        Self::register_console_commands(&mut console);
+
         Ok(Self {
             window,
             input_state,
@@ -299,51 +295,362 @@ impl<'a> State<'a> {
             world,
             last_frame_update: Instant::now(),
             freecam,
+            zoom,
             debug_menu,
             debug_state,
+            console,
+            model: obj_model,
+            is_minimized: false,
+            benchmark: None,
+            paused: false,
+            single_step_requested: false,
+            input_recorder: None,
+            input_player: None,
+            last_recording: vec![],
         })
     }
 
     pub fn update(&mut self) {
         let now = Instant::now();
-        let delta_time = now - self.last_frame_update;
+        let frame_time = now - self.last_frame_update;
         self.last_frame_update = now;
+
+        if let Some(player) = &mut self.input_player {
+            match player.next_frame().cloned() {
+                Some(frame) => {
+                    self.input_state = frame.input;
+                    self.advance(frame.dt);
+                    return;
+                }
+                None => {
+                    // don't leave the recording's last held keys stuck down
+                    self.input_state = InputState::new(self.input_state.cursor_locked());
+                    self.input_player = None;
+                }
+            }
+        }
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(frame_time, &self.input_state);
+        }
+        self.advance(frame_time);
+    }
+
+    /// Update with an externally supplied frame time and input, instead of the wall clock and window events.
+    ///
+    /// Feeding the same `dt`s + inputs from the same starting state reproduces the same world state, ie for replays.
+    pub fn update_with_dt(&mut self, dt: Duration, input: &InputState) {
+        self.input_state.clone_from(input);
+        // so switching back to `update` doesn't count the time spent here as one long frame
+        self.last_frame_update = Instant::now();
+        self.advance(dt);
+    }
+
+    /// Advance everything by a frame taking `frame_time`.
+    fn advance(&mut self, frame_time: Duration) {
+        // while paused, time doesn't pass for the simulation, except for a fixed amount on a single step
+        let stepping = std::mem::take(&mut self.single_step_requested);
+        let delta_time = match (self.paused, stepping) {
+            (false, _) => frame_time,
+            (true, true) => STEP_DELTA_TIME,
+            (true, false) => Duration::ZERO,
+        };
+        // keep the camera near the origin, so nearby coordinates stay precise
+        let cam_pos = self.freecam.pos(&self.world);
+        if cam_pos.magnitude() > REBASE_DISTANCE {
+            self.world.rebase(cam_pos);
+        }
+        self.world.update_graph();
         self.scene.update_and_write_buffers(&self.world, &self.gpu);
-        self.freecam.update(&self.input_state, &mut self.world, delta_time.as_secs_f32()).unwrap();
+
+        // the console takes the keyboard while it's open
+        if self.input_state.key_pressed(Console::TOGGLE_KEY) {
+            self.console.toggle();
+            // IME composition (ie for CJK text) is only wanted while typing into the console
+            self.window.set_ime_allowed(self.console.is_open());
+        }
+        if let Some(benchmark) = &mut self.benchmark {
+            // the benchmark drives the camera, so the frames are the same every run
+            benchmark.record(frame_time.as_secs_f32());
+            let camera_transform = benchmark.camera_transform();
+            if let Some(entity) = self.world.entity_mut(self.freecam.entity()) {
+                entity.update_local_transform(|transform| *transform = camera_transform);
+            }
+        } else if self.console.is_open() {
+            if let Some(line) = self.console.handle_input(&self.input_state) {
+                self.run_console_line(&line);
+            }
+        } else {
+            if self.input_state.key_pressed(PAUSE_KEY) {
+                self.paused = !self.paused;
+            }
+            if self.paused && self.input_state.key_pressed(STEP_KEY) {
+                // applies from the next update, like the console's `step`
+                self.request_single_step();
+            }
+            if !self.paused || stepping {
+                self.freecam.update(&self.input_state, &mut self.world, delta_time.as_secs_f32()).unwrap();
+                self.zoom.update(&self.input_state, self.scene.camera_mut(), delta_time.as_secs_f32());
+            }
+        }
         
         let cam_pos = self.freecam.pos(&self.world);
-        self.debug_state.update(cam_pos);
+        self.debug_state.update(cam_pos, self.renderer.frame_stats());
+    }
+
+    /// Swap in a new scene, ie for a level transition, dropping the old one.
+    ///
+    /// The old scene's pipelines + bind groups are removed from the renderer, unless the new scene shares them.
+    /// The freecam moves the new scene's camera from then on.
+    pub fn set_scene(&mut self, scene: Scene) {
+        let old_scene = std::mem::replace(&mut self.scene, scene);
+        let pipelines = self.scene.pipeline_ids();
+        for id in old_scene.pipeline_ids() {
+            if !pipelines.contains(&id) {
+                self.renderer.remove_pipeline(id);
+            }
+        }
+        let bind_groups = self.scene.bind_group_ids();
+        for id in old_scene.bind_group_ids() {
+            if !bind_groups.contains(&id) {
+                self.renderer.remove_bind_group(id);
+            }
+        }
+        self.freecam = FreecamController::new(self.scene.camera().entity());
+    }
+
+    /// Whether the simulation is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause or resume the simulation. While paused, the scene keeps rendering but time doesn't pass for it.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Advance the simulation by a single fixed step on the next update, then stay paused.
+    ///
+    /// This pauses the simulation if it isn't already.
+    pub fn request_single_step(&mut self) {
+        self.paused = true;
+        self.single_step_requested = true;
+    }
+
+    /// Start recording each frame's input, replacing any recording in progress.
+    pub fn start_input_recording(&mut self) {
+        self.input_recorder = Some(InputRecorder::new());
+    }
+
+    /// Stop recording input, returning the recording if there was one.
+    pub fn stop_input_recording(&mut self) -> Option<InputRecorder> {
+        self.input_recorder.take()
+    }
+
+    /// Play back a recording, one frame per update, in place of the window's input.
+    pub fn play_input(&mut self, player: InputPlayer) {
+        self.input_player = Some(player);
+    }
+
+    /// Whether a recording is being played back.
+    pub fn playing_input(&self) -> bool {
+        self.input_player.is_some()
+    }
+
+    /// Start a benchmark, which takes over the camera until its frames are recorded.
+    pub fn start_benchmark(&mut self, config: BenchmarkConfig) {
+        self.benchmark = Some(Benchmark::new(config));
+    }
+
+    /// Whether a benchmark is running and has recorded all its frames.
+    pub fn benchmark_finished(&self) -> bool {
+        self.benchmark.as_ref().is_some_and(Benchmark::is_finished)
+    }
+
+    /// End the benchmark (if any), writing its results.
+    pub fn finish_benchmark(&mut self) -> std::io::Result<()> {
+        match self.benchmark.take() {
+            Some(benchmark) => benchmark.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Run a line entered into the console, printing its output.
+    fn run_console_line(&mut self, line: &str) {
+        self.console.print(format!("> {line}"));
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return;
+        };
+        let args = words.collect::<Vec<_>>();
+        let output = match self.console.command(name) {
+            Some(command) => command(&args, self),
+            None => format!("Unknown command: {name}"),
+        };
+        if !output.is_empty() {
+            self.console.print(output);
+        }
+    }
+
+    /// Register the built-in console commands.
+    fn register_console_commands(console: &mut Console) {
+        console.register("spawn", |args, state| {
+            let position = match parse_vector3(args) {
+                Ok(Some(position)) => position,
+                Ok(None) => state.freecam.pos(&state.world),
+                Err(err) => return err,
+            };
+            let transform = SpatialTransform {
+                position,
+                ..SpatialTransform::identity()
+            };
+            let entity = state.world.add_entity(None, vec![], transform);
+            for &mesh in &state.model.meshes {
+                let instance = MeshInstance::new(mesh, entity);
+                state.scene.add_mesh_instances(mesh, vec![instance]);
+            }
+            format!("Spawned at {:?}", position)
+        });
+        console.register("teleport", |args, state| {
+            let position = match parse_vector3(args) {
+                Ok(Some(position)) => position,
+                Ok(None) => return "Usage: teleport <x> <y> <z>".into(),
+                Err(err) => return err,
+            };
+            match state.world.entity_mut(state.freecam.entity()) {
+                Some(entity) => {
+                    entity.update_local_transform(|transform| transform.position = position);
+                    format!("Teleported to {:?}", position)
+                }
+                None => "The camera's entity doesn't exist".into(),
+            }
+        });
+        console.register("set_fov", |args, state| {
+            match args.first().map(|arg| arg.parse::<f32>()) {
+                Some(Ok(fov)) => {
+                    let fov = state.zoom.set_fov(state.scene.camera_mut(), fov);
+                    format!("Set FOV to {fov}")
+                }
+                _ => "Usage: set_fov <degrees>".into(),
+            }
+        });
+        console.register("set_frame_latency", |args, state| {
+            match args.first().map(|arg| arg.parse::<u32>()) {
+                Some(Ok(latency)) => match state.renderer.set_frame_latency(latency) {
+                    Ok(()) => format!("Set frame latency to {latency} ({:?})", state.renderer.present_mode()),
+                    Err(err) => err.to_string(),
+                },
+                _ => "Usage: set_frame_latency <frames>".into(),
+            }
+        });
+        console.register("set_render_scale", |args, state| {
+            match args.first().map(|arg| arg.parse::<f32>()) {
+                Some(Ok(scale)) => match state.renderer.set_render_scale(scale) {
+                    Ok(()) => format!("Set render scale to {scale} ({:?})", state.renderer.render_size()),
+                    Err(err) => err.to_string(),
+                },
+                _ => "Usage: set_render_scale <scale>".into(),
+            }
+        });
+        console.register("pause", |_, state| {
+            state.set_paused(!state.paused());
+            if state.paused() { "Paused".into() } else { "Resumed".into() }
+        });
+        console.register("step", |_, state| {
+            state.request_single_step();
+            "Stepped a frame".into()
+        });
+        console.register("record_input", |_, state| {
+            state.start_input_recording();
+            "Recording input".into()
+        });
+        console.register("stop_recording", |_, state| {
+            match state.stop_input_recording() {
+                Some(recorder) => {
+                    let frames = recorder.len();
+                    state.last_recording = recorder.finish();
+                    format!("Recorded {frames} frames")
+                }
+                None => "Not recording".into(),
+            }
+        });
+        console.register("play_input", |_, state| {
+            if state.last_recording.is_empty() {
+                return "Nothing recorded".into();
+            }
+            let frames = state.last_recording.clone();
+            state.play_input(InputPlayer::new(frames));
+            "Playing input".into()
+        });
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
+        // a minimized window has a zero size; there's nothing to render to until it's restored
+        self.is_minimized = width == 0 || height == 0;
+        if self.is_minimized {
+            return;
+        }
         self.renderer.resize(width, height);
         self.debug_menu.resize(width, height);
     }
 
+    /// Reconfigure the surface, ie after it was lost or outdated.
+    pub fn reconfigure_surface(&mut self) {
+        self.renderer.reconfigure();
+    }
+
     pub fn render(&mut self) -> Result<(), SurfaceError> {
         self.window.request_redraw();
+        // the surface may not be configured until the first resize
+        if self.is_minimized || !self.renderer.is_ready() {
+            return Ok(());
+        }
 
-        self.renderer
-            .begin_frame()
-            .unwrap();
-        
-        self.renderer
-            .render_scene_for_frame(&self.scene, &self.world)
-            .unwrap();
+        // swap in any textures that finished loading in the background
+        self.renderer.poll_pending_assets();
 
-        let mut primitives = vec![];
-        self.renderer
-            .encode_commands(|encoder| primitives = self.debug_menu.setup_render(&self.window, encoder, &mut self.debug_state, &self.gpu))
-            .unwrap();
-        self.renderer
-            .render_with_render_pass(|pass| self.debug_menu.render(&primitives, pass), false)
-            .unwrap();
+        match self.renderer.begin_frame() {
+            Ok(()) => {}
+            Err(RenderError::Surface(err)) => return Err(err),
+            Err(err) => {
+                log::error!("Unable to begin frame: {err}");
+                return Ok(());
+            }
+        }
 
-        self.renderer
-            .end_frame()
-            .unwrap();
+        // a lost/outdated surface is handled by the caller; anything else only costs this frame
+        if let Err(err) = self.render_frame_contents() {
+            self.renderer.discard_frame();
+            return match err {
+                RenderError::Surface(err) => Err(err),
+                err => {
+                    log::error!("Unable to render frame: {err}");
+                    Ok(())
+                }
+            };
+        }
+        match self.renderer.end_frame() {
+            Ok(()) => Ok(()),
+            Err(RenderError::Surface(err)) => Err(err),
+            Err(err) => {
+                log::error!("Unable to end frame: {err}");
+                Ok(())
+            }
+        }
+    }
 
-        Ok(())
+    /// Render the scene, then the debug UI over it, into the frame in progress.
+    fn render_frame_contents(&mut self) -> Result<(), RenderError> {
+        self.renderer.render_scene_for_frame(&self.scene, &self.world)?;
+
+        let labels = self.renderer.take_screen_labels(&self.scene);
+        self.renderer.render_with_render_pass(|pass| {
+            let frame = DebugMenuFrame {
+                panels: &mut [&mut self.debug_state],
+                labels: &labels,
+                console: &self.console,
+            };
+            self.debug_menu.render_frame(&self.window, pass, frame, &self.gpu)
+        }, None)
     }
 
     pub fn reset_for_frame(&mut self) {
@@ -362,6 +669,12 @@ impl<'a> State<'a> {
         }
     }
 
+    pub fn handle_text(&mut self, text: &str) {
+        for character in text.chars() {
+            self.input_state.process_text(character);
+        }
+    }
+
     pub fn handle_cursor_delta(&mut self, delta_x: f64, delta_y: f64) {
         self.input_state.process_cursor_delta(delta_x as f32, delta_y as f32);
     }
@@ -370,7 +683,23 @@ impl<'a> State<'a> {
         self.input_state.process_cursor_movement(x as f32, y as f32);
     }
 
+    pub fn handle_mouse_button(&mut self, button: MouseButton, button_state: ElementState) {
+        self.input_state.process_mouse_button(button, button_state);
+    }
+
     pub fn handle_mouse_wheel(&mut self, change: MouseScrollDelta) {
         self.input_state.process_mouse_scroll(change)
     }
 }
+
+/// Parse `x y z` console arguments, returning `None` if there aren't any.
+fn parse_vector3(args: &[&str]) -> Result<Option<Vector3<f32>>, String> {
+    match args {
+        [] => Ok(None),
+        [x, y, z] => {
+            let parse = |arg: &str| arg.parse::<f32>().map_err(|_| format!("Not a number: {arg}"));
+            Ok(Some(Vector3::new(parse(x)?, parse(y)?, parse(z)?)))
+        }
+        _ => Err("Expected 3 coordinates".into()),
+    }
+}
@@ -0,0 +1,82 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Sun {
+    // points towards the sun
+    direction: vec3<f32>,
+}
+@group(1) @binding(0)
+var<uniform> sun: Sun;
+
+struct VertexOutput {
+    @builtin(position) frag_position: vec4<f32>,
+    @location(0) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(vec2<u32>(
+        id & 1u,
+        (id >> 1u) & 1u,
+    ));
+    var out: VertexOutput;
+    out.clip_position = vec4(uv * 4.0 - 1.0, 1.0, 1.0);
+    out.frag_position = vec4(uv * 4.0 - 1.0, 1.0, 1.0);
+    return out;
+}
+
+const DAY_ZENITH: vec3<f32> = vec3(0.15, 0.35, 0.8);
+const DAY_HORIZON: vec3<f32> = vec3(0.6, 0.75, 0.95);
+const NIGHT_ZENITH: vec3<f32> = vec3(0.002, 0.004, 0.015);
+const NIGHT_HORIZON: vec3<f32> = vec3(0.01, 0.015, 0.04);
+const SUNSET_TINT: vec3<f32> = vec3(1.0, 0.45, 0.15);
+const GROUND: vec3<f32> = vec3(0.2, 0.18, 0.16);
+const SUN_COLOR: vec3<f32> = vec3(20.0, 18.0, 15.0);
+
+// the cosine of the sun disk's angular radius (~0.5 degrees)
+const SUN_DISK_COS: f32 = 0.99996;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // same as the cubemap sky; clip space -> view space -> world space ray
+    let view_pos_homogeneous = camera.inv_proj * in.clip_position;
+    let view_ray_direction = view_pos_homogeneous.xyz / view_pos_homogeneous.w;
+    let ray_direction = normalize((camera.inv_view * vec4(view_ray_direction, 0.0)).xyz);
+    let sun_direction = normalize(sun.direction);
+
+    // fade from night to day as the sun rises, and tint the horizon around sunrise/sunset
+    let elevation = sun_direction.y;
+    let daylight = smoothstep(-0.2, 0.2, elevation);
+    let sunset = (1.0 - smoothstep(0.0, 0.35, abs(elevation))) * daylight;
+    // the tint is strongest on the sun's side of the sky
+    let towards_sun = max(dot(normalize(vec2(ray_direction.x, ray_direction.z) + vec2(1e-5)), normalize(vec2(sun_direction.x, sun_direction.z) + vec2(1e-5))), 0.0);
+
+    let zenith = mix(NIGHT_ZENITH, DAY_ZENITH, daylight);
+    var horizon = mix(NIGHT_HORIZON, DAY_HORIZON, daylight);
+    horizon = mix(horizon, SUNSET_TINT, sunset * (0.3 + 0.7 * towards_sun));
+
+    let height = max(ray_direction.y, 0.0);
+    var color = mix(horizon, zenith, pow(height, 0.5));
+    if ray_direction.y < 0.0 {
+        color = mix(horizon, GROUND * (0.05 + 0.95 * daylight), smoothstep(0.0, 0.1, -ray_direction.y));
+    }
+
+    // the sun disk + a glow around it; hidden below the horizon
+    let cos_angle = dot(ray_direction, sun_direction);
+    let disk = smoothstep(SUN_DISK_COS - 0.00002, SUN_DISK_COS, cos_angle);
+    let glow = pow(max(cos_angle, 0.0), 256.0) * 0.5 + pow(max(cos_angle, 0.0), 8.0) * 0.1 * daylight;
+    let above_horizon = smoothstep(-0.01, 0.01, ray_direction.y);
+    let sun_tint = mix(SUNSET_TINT, vec3(1.0), 1.0 - sunset);
+    color += SUN_COLOR * sun_tint * disk * above_horizon + sun_tint * glow * above_horizon;
+
+    return vec4(color, 1.0);
+}
@@ -0,0 +1,174 @@
+use cgmath::{Vector3, VectorSpace};
+
+use crate::core::entity::spatial_transform::SpatialTransform;
+
+/// A curve remapping an animation's progress, ie to ease in/out of movement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseInQuad,
+    EaseOutQuad,
+    EaseInOutQuad,
+    EaseInCubic,
+    EaseOutCubic,
+    EaseInOutCubic,
+    SmoothStep,
+}
+
+impl Easing {
+    /// Remap the progress `t`, which is clamped to `[0, 1]`. Every curve maps 0 to 0 and 1 to 1.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t,
+            Easing::EaseOutQuad => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::EaseInCubic => t * t * t,
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A value that can be tweened, by interpolating between two of them.
+pub trait Tweenable: Copy {
+    /// Interpolate between this and the other value, where `t` is 0 for this one and 1 for the other.
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector3<f32> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Tweenable for SpatialTransform {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+/// Animates a value from a start to an end over a duration, ie for scripted camera moves.
+///
+/// Advance it with `update` each frame, and apply its `value` (ie to an entity's local transform).
+pub struct Tween<T: Tweenable> {
+    start: T,
+    end: T,
+    duration: f32,
+    easing: Easing,
+    elapsed: f32,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Create a tween lasting `duration` seconds, starting at `start`.
+    pub fn new(start: T, end: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            start,
+            end,
+            duration,
+            easing,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Advance the tween by `dt` seconds, returning the value at its new time.
+    pub fn update(&mut self, dt: f32) -> T {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.value()
+    }
+
+    /// Get the value at the tween's current time.
+    pub fn value(&self) -> T {
+        self.start.interpolate(&self.end, self.easing.apply(self.progress()))
+    }
+
+    /// Get how far through the tween is, from 0 to 1. A tween with no duration is always finished.
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether the tween has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Restart the tween from its start.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 8] = [
+        Easing::Linear,
+        Easing::EaseInQuad,
+        Easing::EaseOutQuad,
+        Easing::EaseInOutQuad,
+        Easing::EaseInCubic,
+        Easing::EaseOutCubic,
+        Easing::EaseInOutCubic,
+        Easing::SmoothStep,
+    ];
+
+    #[test]
+    fn easings_map_the_ends_to_themselves() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?} at 0");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing:?} at 1");
+            // out of range progress is clamped
+            assert_eq!(easing.apply(-1.0), 0.0, "{easing:?} below 0");
+            assert_eq!(easing.apply(2.0), 1.0, "{easing:?} above 1");
+        }
+    }
+
+    #[test]
+    fn zero_duration_tween_is_finished_at_its_end() {
+        let tween = Tween::new(1.0, 5.0, 0.0, Easing::EaseInOutCubic);
+        assert!(tween.is_finished());
+        assert_eq!(tween.progress(), 1.0);
+        assert_eq!(tween.value(), 5.0);
+    }
+
+    #[test]
+    fn tween_stops_at_its_end() {
+        let mut tween = Tween::new(0.0, 10.0, 2.0, Easing::Linear);
+        assert_eq!(tween.update(1.0), 5.0);
+        assert!(!tween.is_finished());
+
+        // overshooting the duration stays at the end
+        assert_eq!(tween.update(5.0), 10.0);
+        assert!(tween.is_finished());
+        assert_eq!(tween.progress(), 1.0);
+
+        tween.reset();
+        assert_eq!(tween.value(), 0.0);
+        assert!(!tween.is_finished());
+    }
+}
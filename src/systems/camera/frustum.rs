@@ -0,0 +1,115 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+use crate::core::collision::Aabb;
+
+/// A view frustum as 6 planes facing inwards, ie for testing whether things are in view.
+///
+/// The tests are conservative; shapes near the frustum's corners may be counted as intersecting when they aren't.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near + far planes, as `(normal, d)` where `dot(normal, p) + d >= 0` inside.
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extract the frustum from a view-projection matrix (see `Camera::view_projection`), with wgpu's 0..1 clip depth.
+    ///
+    /// The planes are in the space the matrix transforms from, ie world space for a camera's view-projection.
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Self {
+        let m = view_projection.transpose();
+        let (x, y, z, w) = (m.x, m.y, m.z, m.w);
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            // normalize, so distances to the planes are in world units
+            plane / plane.truncate().magnitude()
+        });
+        Self { planes }
+    }
+
+    /// Get the signed distance from each plane to the point, where positive is inside.
+    fn distances(&self, point: Vector3<f32>) -> impl Iterator<Item = f32> + '_ {
+        self.planes.iter().map(move |plane| plane.truncate().dot(point) + plane.w)
+    }
+
+    /// Whether the point is inside the frustum (or on its boundary).
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        self.distances(point).all(|distance| distance >= 0.0)
+    }
+
+    /// Whether the sphere is at least partially inside the frustum.
+    pub fn intersects_sphere(&self, centre: Vector3<f32>, radius: f32) -> bool {
+        self.distances(centre).all(|distance| distance >= -radius)
+    }
+
+    /// Whether the capsule between the two points is at least partially inside the frustum.
+    pub fn intersects_capsule(&self, start: Vector3<f32>, end: Vector3<f32>, radius: f32) -> bool {
+        self.distances(start)
+            .zip(self.distances(end))
+            .all(|(start, end)| start.max(end) >= -radius)
+    }
+
+    /// Whether the box is at least partially inside the frustum.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the box's corner furthest along the plane's normal
+            let corner = Vector3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Point3, perspective};
+    use crate::systems::camera::OPENGL_TO_WGPU_MATRIX;
+    use super::*;
+
+    /// A frustum at the origin looking down -z, with a 90 degree FOV, so its half-width at a depth is that depth.
+    fn frustum() -> Frustum {
+        let view = Matrix4::look_at_rh(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+        let projection = OPENGL_TO_WGPU_MATRIX * perspective(Deg(90.0), 1.0, 0.1, 100.0);
+        Frustum::from_view_projection(projection * view)
+    }
+
+    #[test]
+    fn points_in_front_are_inside() {
+        let frustum = frustum();
+        assert!(frustum.contains_point(Vector3::new(0.0, 0.0, -5.0)));
+        assert!(frustum.contains_point(Vector3::new(4.0, -4.0, -5.0)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 5.0)));
+        assert!(!frustum.contains_point(Vector3::new(6.0, 0.0, -5.0)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -150.0)));
+    }
+
+    #[test]
+    fn spheres_overlapping_a_plane_intersect() {
+        let frustum = frustum();
+        // ~0.7 outside the right plane
+        let centre = Vector3::new(6.0, 0.0, -5.0);
+        assert!(frustum.intersects_sphere(centre, 2.0));
+        assert!(!frustum.intersects_sphere(centre, 0.5));
+        assert!(!frustum.intersects_sphere(Vector3::new(0.0, 0.0, 10.0), 1.0));
+    }
+
+    #[test]
+    fn capsules_reaching_into_view_intersect() {
+        let frustum = frustum();
+        assert!(frustum.intersects_capsule(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -5.0), 0.1));
+        assert!(!frustum.intersects_capsule(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 10.0), 0.1));
+    }
+
+    #[test]
+    fn boxes_overlapping_the_frustum_intersect() {
+        let frustum = frustum();
+        let straddling = Aabb { min: Vector3::new(-1.0, -1.0, -6.0), max: Vector3::new(20.0, 1.0, -4.0) };
+        assert!(frustum.intersects_aabb(&straddling));
+        let behind = Aabb { min: Vector3::new(-1.0, -1.0, 1.0), max: Vector3::new(1.0, 1.0, 3.0) };
+        assert!(!frustum.intersects_aabb(&behind));
+        let beside = Aabb { min: Vector3::new(10.0, -1.0, -6.0), max: Vector3::new(12.0, 1.0, -4.0) };
+        assert!(!frustum.intersects_aabb(&beside));
+    }
+}
@@ -1,12 +1,14 @@
 use bytemuck::NoUninit;
-use cgmath::{Matrix4, SquareMatrix, Vector3, Zero};
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3, Vector4, Zero};
 use wgpu::{BindGroupEntry, BindGroupLayoutEntry, BindingType, BufferBindingType, ShaderStages};
 use crate::{core::{entity::WorldEntity, world::{World, WorldEntityId}}, systems::camera::{
+        frustum::Frustum,
         ortho::{OrthoCameraData, OrthographicCamera},
         perspective::{PerspectiveCamera, PerspectiveCameraData},
     }};
 use crate::graphics::gpu::{GpuContext, bind_group::GpuBindGroup, buffer::GpuBuffer};
 
+pub mod frustum;
 pub mod ortho;
 pub mod perspective;
 
@@ -41,6 +43,11 @@ impl Camera {
         }
     }
 
+    /// Get the entity the camera is positioned by.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
     /// Update the camera's data and write it to the uniform data.
     pub fn update_and_write_uniform_buffer(&mut self, world: &World, gpu: &GpuContext) {
         let entity = world
@@ -52,6 +59,121 @@ impl Camera {
         }
     }
 
+    /// Get the vertical FOV (in degrees), if this is a perspective camera.
+    pub fn fov(&self) -> Option<f32> {
+        match &self.cam_type {
+            CameraType::Perspective(camera) => Some(camera.data().fovy),
+            CameraType::Ortho(_) => None,
+        }
+    }
+
+    /// Set the vertical FOV (in degrees). This is written to the GPU on the next update.
+    ///
+    /// Does nothing for orthographic cameras, since they don't have a FOV.
+    pub fn set_fov(&mut self, fov: f32) {
+        if let CameraType::Perspective(camera) = &mut self.cam_type {
+            camera.data_mut().fovy = fov;
+        }
+    }
+
+    /// Get the orthographic camera's data, if this is one.
+    pub fn ortho_data(&self) -> Option<&OrthoCameraData> {
+        match &self.cam_type {
+            CameraType::Perspective(_) => None,
+            CameraType::Ortho(camera) => Some(camera.data()),
+        }
+    }
+
+    /// Set the zoom, where higher values zoom in. This is written to the GPU on the next update.
+    ///
+    /// Does nothing for perspective cameras; use the FOV instead.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        if let CameraType::Ortho(camera) = &mut self.cam_type {
+            camera.data_mut().zoom = zoom;
+        }
+    }
+
+    /// Set the near/far clip planes. This is written to the GPU on the next update.
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        match &mut self.cam_type {
+            CameraType::Perspective(camera) => {
+                let data = camera.data_mut();
+                data.znear = near;
+                data.zfar = far;
+            }
+            CameraType::Ortho(camera) => {
+                let data = camera.data_mut();
+                data.near = near;
+                data.far = far;
+            }
+        }
+    }
+
+    /// Compute the camera's view matrix from its current data and entity.
+    pub fn view_matrix(&self, world: &World) -> Matrix4<f32> {
+        let entity = world
+            .entity(self.entity)
+            .expect("Camera's entity must exist");
+        match &self.cam_type {
+            CameraType::Perspective(camera) => camera.data().build_view_matrix(entity),
+            CameraType::Ortho(camera) => camera.data().build_view_matrix(entity),
+        }
+    }
+
+    /// Compute the camera's projection matrix from its current data.
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        match &self.cam_type {
+            CameraType::Perspective(camera) => camera.data().build_projection_matrix(),
+            CameraType::Ortho(camera) => camera.data().build_projection_matrix(),
+        }
+    }
+
+    /// Compute the camera's view-projection matrix from its current data and entity.
+    ///
+    /// Unlike `uniform_view_proj`, this doesn't need the camera to have been updated since its data or entity changed.
+    pub fn view_projection(&self, world: &World) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix(world)
+    }
+
+    /// Compute the camera's view frustum in world space from its current data and entity.
+    pub fn frustum(&self, world: &World) -> Frustum {
+        Frustum::from_view_projection(self.view_projection(world))
+    }
+
+    /// Project a world position to pixel coordinates in a viewport of the given `(width, height)`, ie to place UI over it.
+    ///
+    /// The origin is the viewport's top-left. Returns `None` if the position is behind the camera;
+    /// positions off to the side still project, outside the viewport.
+    pub fn world_to_screen(&self, world: &World, world_pos: Vector3<f32>, viewport_size: (u32, u32)) -> Option<Vector2<f32>> {
+        Self::project_to_screen(self.view_projection(world), world_pos, viewport_size)
+    }
+
+    /// Project a world position to pixel coordinates like `world_to_screen`, through the given view-projection matrix.
+    pub fn project_to_screen(view_projection: Matrix4<f32>, world_pos: Vector3<f32>, viewport_size: (u32, u32)) -> Option<Vector2<f32>> {
+        let clip = view_projection * Vector4::new(world_pos.x, world_pos.y, world_pos.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        let (width, height) = viewport_size;
+        Some(Vector2::new(
+            (ndc.x + 1.0) * 0.5 * width as f32,
+            (1.0 - ndc.y) * 0.5 * height as f32,
+        ))
+    }
+
+    /// Get the view-projection matrix last written to the camera's uniform buffer, ie what the GPU is rendering with.
+    ///
+    /// This goes stale as soon as the camera's data or entity changes; prefer `view_projection` unless
+    /// you need to match exactly what was rendered.
+    pub fn uniform_view_proj(&self) -> Matrix4<f32> {
+        let uniform = match &self.cam_type {
+            CameraType::Perspective(c) => c.uniform(),
+            CameraType::Ortho(c) => c.uniform(),
+        };
+        uniform.view_proj.into()
+    }
+
     /// Get the camera's buffer.
     pub fn buffer(&self) -> &GpuBuffer {
         match &self.cam_type {
@@ -86,6 +208,18 @@ impl CameraUniform {
         }
     }
 
+    /// Create a uniform from the given matrices, ie for a camera derived from another (such as a mirrored one).
+    pub fn from_matrices(view: Matrix4<f32>, proj: Matrix4<f32>, view_position: Vector3<f32>) -> Self {
+        Self {
+            view_proj: (proj * view).into(),
+            view: view.into(),
+            view_position: view_position.into(),
+            inverse_proj: proj.invert().unwrap().into(),
+            inverse_view: view.invert().unwrap().into(),
+            _padding: 0.0
+        }
+    }
+
     /// Update the uniform for a perspective camera.
     pub fn update_perspective(&mut self, data: &PerspectiveCameraData, entity: &WorldEntity) {
         let view = data.build_view_matrix(entity);
@@ -96,6 +230,7 @@ impl CameraUniform {
         self.view = view.into();
         self.view_proj = view_proj.into();
         self.inverse_proj = proj.invert().unwrap().into();
+        self.inverse_view = view.invert().unwrap().into();
     }
 
     /// Update the uniform for an ortho camera.
@@ -108,21 +243,24 @@ impl CameraUniform {
         self.view = view.into();
         self.view_proj = view_proj.into();
         self.inverse_proj = proj.invert().unwrap().into();
+        self.inverse_view = view.invert().unwrap().into();
     }
 }
 
+/// The bind group layout entries for a camera.
+pub const CAMERA_BIND_GROUP_LAYOUT_ENTRIES: [BindGroupLayoutEntry; 1] = [BindGroupLayoutEntry {
+    binding: 0,
+    visibility: ShaderStages::VERTEX.union(ShaderStages::FRAGMENT).union(ShaderStages::COMPUTE),
+    ty: BindingType::Buffer {
+        ty: BufferBindingType::Uniform,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    },
+    count: None,
+}];
+
 /// Create the bind group for a camera.
 pub fn create_camera_bind_group(gpu: &GpuContext, camera_buffer: &GpuBuffer) -> GpuBindGroup {
-    let layout_entries = [BindGroupLayoutEntry {
-        binding: 0,
-        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-        ty: BindingType::Buffer {
-            ty: BufferBindingType::Uniform,
-            has_dynamic_offset: false,
-            min_binding_size: None,
-        },
-        count: None,
-    }];
     let entries = [BindGroupEntry {
         binding: 0,
         resource: camera_buffer.handle().as_entire_binding(),
@@ -130,7 +268,37 @@ pub fn create_camera_bind_group(gpu: &GpuContext, camera_buffer: &GpuBuffer) ->
     GpuBindGroup::create_default(
         "perspective_camera_bind_group",
         gpu,
-        &layout_entries,
+        &CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
         &entries,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Quaternion, Rotation3, assert_abs_diff_eq};
+    use crate::core::entity::spatial_transform::SpatialTransform;
+    use super::*;
+
+    #[test]
+    fn forward_focus_projects_to_the_screen_centre() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform {
+            position: Vector3::new(1.0, 2.0, 3.0),
+            rotation: Quaternion::from_angle_y(Deg(30.0)) * Quaternion::from_angle_x(Deg(-20.0)),
+            ..SpatialTransform::identity()
+        });
+        world.update_graph();
+        let entity = world.entity(entity).unwrap();
+
+        let data = PerspectiveCameraData::new(16.0 / 9.0, 60.0, 0.1, 100.0);
+        let view_projection = data.build_projection_matrix() * data.build_view_matrix(entity);
+        let transform = entity.transform();
+
+        let focus = transform.position + transform.forward() * 10.0;
+        let screen = Camera::project_to_screen(view_projection, focus, (1920, 1080)).unwrap();
+        assert_abs_diff_eq!(screen, Vector2::new(960.0, 540.0), epsilon = 1e-2);
+
+        let behind = transform.position - transform.forward() * 10.0;
+        assert!(Camera::project_to_screen(view_projection, behind, (1920, 1080)).is_none());
+    }
+}
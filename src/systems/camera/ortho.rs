@@ -22,29 +22,8 @@ impl OrthographicCamera {
         gpu: &GpuContext,
         camera_entity: &WorldEntity,
         label: &str,
-        origin_at_top_left: bool,
-        invert_y: bool,
-        width: f32,
-        height: f32,
-        yaw: f32,
-        pitch: f32,
-        zoom: f32,
-        near: f32,
-        far: f32,
+        data: OrthoCameraData,
     ) -> Self {
-        let data = {
-            OrthoCameraData::new(
-                origin_at_top_left,
-                invert_y,
-                width,
-                height,
-                yaw,
-                pitch,
-                zoom,
-                near,
-                far,
-            )
-        };
         let mut uniform = CameraUniform::new();
         uniform.update_ortho(&data, camera_entity);
         let buffer =
@@ -56,6 +35,24 @@ impl OrthographicCamera {
         }
     }
 
+    /// Create a camera in pixel coordinates, ie for typical 2D scenes.
+    ///
+    /// The origin is at the top-left with y pointing down, at zoom 1 and a depth range of -1..1.
+    /// The entity should be positioned at the world origin for world coordinates to match screen pixels.
+    pub fn screen_space(
+        gpu: &GpuContext,
+        camera_entity: &WorldEntity,
+        width: f32,
+        height: f32,
+    ) -> Self {
+        Self::new(
+            gpu,
+            camera_entity,
+            "screen_space_camera",
+            OrthoCameraData::screen_space(width, height),
+        )
+    }
+
     /// Write the camera's uniform buffer to the GPU.
     pub(super) fn update_and_write_uniform_buffer(&mut self, entity: &WorldEntity, gpu: &GpuContext) {
         self.uniform.update_ortho(&self.data, entity);
@@ -66,11 +63,21 @@ impl OrthographicCamera {
         );
     }
 
+    /// Get the uniform, as of the last update.
+    pub(super) fn uniform(&self) -> &CameraUniform {
+        &self.uniform
+    }
+
     /// Get the buffer.
     pub fn buffer(&self) -> &GpuBuffer {
         &self.buffer
     }
 
+    /// Get the camera data.
+    pub fn data(&self) -> &OrthoCameraData {
+        &self.data
+    }
+
     /// Get the camera data mutably.
     pub fn data_mut(&mut self) -> &mut OrthoCameraData {
         &mut self.data
@@ -91,27 +98,18 @@ pub struct OrthoCameraData {
 }
 
 impl OrthoCameraData {
-    pub fn new(
-        origin_at_top_left: bool,
-        invert_y: bool,
-        width: f32,
-        height: f32,
-        yaw: f32,
-        pitch: f32,
-        zoom: f32,
-        near: f32,
-        far: f32,
-    ) -> Self {
+    /// Data for a camera in pixel coordinates; see `OrthographicCamera::screen_space`.
+    pub fn screen_space(width: f32, height: f32) -> Self {
         Self {
-            origin_at_top_left,
-            invert_y,
+            origin_at_top_left: true,
+            invert_y: false,
             width,
             height,
-            yaw,
-            pitch,
-            zoom,
-            near,
-            far,
+            yaw: 0.0,
+            pitch: 0.0,
+            zoom: 1.0,
+            near: -1.0,
+            far: 1.0,
         }
     }
 
@@ -153,3 +151,26 @@ impl OrthoCameraData {
         ortho(left, right, bottom, top, self.near, self.far)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector4, assert_abs_diff_eq};
+    use super::*;
+
+    /// Project a point on the z = 0 plane to NDC through the data's projection.
+    fn to_ndc(data: &OrthoCameraData, x: f32, y: f32) -> (f32, f32) {
+        let clip = data.build_projection_matrix() * Vector4::new(x, y, 0.0, 1.0);
+        (clip.x / clip.w, clip.y / clip.w)
+    }
+
+    #[test]
+    fn screen_space_origin_is_the_top_left_corner() {
+        let data = OrthoCameraData::screen_space(800.0, 600.0);
+        let (x, y) = to_ndc(&data, 0.0, 0.0);
+        assert_abs_diff_eq!(x, -1.0);
+        assert_abs_diff_eq!(y, 1.0);
+        let (x, y) = to_ndc(&data, 800.0, 600.0);
+        assert_abs_diff_eq!(x, 1.0);
+        assert_abs_diff_eq!(y, -1.0);
+    }
+}
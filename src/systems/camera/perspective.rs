@@ -52,11 +52,21 @@ impl PerspectiveCamera {
         );
     }
 
+    /// Get the uniform, as of the last update.
+    pub(super) fn uniform(&self) -> &CameraUniform {
+        &self.uniform
+    }
+
     /// Get the buffer.
     pub fn buffer(&self) -> &GpuBuffer {
         &self.buffer
     }
 
+    /// Get the camera data.
+    pub fn data(&self) -> &PerspectiveCameraData {
+        &self.data
+    }
+
     /// Get the camera data mutably.
     pub fn data_mut(&mut self) -> &mut PerspectiveCameraData {
         &mut self.data
@@ -32,12 +32,18 @@ impl FreecamController {
             .position
     }
 
+    /// Get the entity this controller moves.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
     /// Update the entity for this freecam controller.
     pub fn update(&self, input: &InputState, world: &mut World, delta_time: f32) -> Result<(), &'static str> {
         if !self.enabled {
             return Ok(());
         }
 
+        let up_axis = world.up_axis();
         let entity = world
             .entity_mut(self.entity)
             .ok_or("Freecam controller couldn't find the entity")?;
@@ -69,7 +75,8 @@ impl FreecamController {
             let yaw = -mouse_delta.x * LOOK_SENS * delta_time;
             let pitch = mouse_delta.y * LOOK_SENS * delta_time;
 
-            let yaw_q = Quaternion::from_angle_y(Deg(yaw));
+            // yaw around the world's up axis, since the entity's rotation is relative to it
+            let yaw_q = Quaternion::from_axis_angle(up_axis.up(), Deg(yaw));
             let pitch_q = Quaternion::from_angle_x(Deg(pitch));
 
             entity.update_local_transform(|transform| transform.rotation = (yaw_q * transform.rotation * pitch_q).normalize());
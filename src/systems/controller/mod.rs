@@ -1 +1,2 @@
-pub mod freecam;
\ No newline at end of file
+pub mod freecam;
+pub mod zoom;pub mod ortho2d;
@@ -0,0 +1,87 @@
+use cgmath::{Vector3, Zero};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{core::world::{World, WorldEntityId}, input::state::InputState, systems::camera::Camera};
+
+static PAN_SPEED: f32 = 500.0;
+static ZOOM_STEP: f32 = 1.1;
+
+/// Pans and zooms an orthographic camera, ie for 2D scenes.
+///
+/// Panning moves the camera's entity with WASD/arrows or by dragging with the middle mouse button,
+/// and scrolling zooms in/out. Pan distances are in camera units (ie pixels for a screen-space camera),
+/// scaled by the zoom so panning feels the same at any zoom level.
+pub struct Ortho2DController {
+    entity: WorldEntityId,
+    min_zoom: f32,
+    max_zoom: f32,
+    enabled: bool
+}
+
+impl Ortho2DController {
+    /// Create a controller for the camera's entity, with the zoom clamped to `[min_zoom, max_zoom]`.
+    pub fn new(entity: WorldEntityId, min_zoom: f32, max_zoom: f32) -> Self {
+        Self {
+            entity,
+            min_zoom,
+            max_zoom,
+            enabled: true
+        }
+    }
+
+    /// Get the entity this controller moves.
+    pub fn entity(&self) -> WorldEntityId {
+        self.entity
+    }
+
+    /// Update the camera's entity and zoom for this controller.
+    ///
+    /// Does nothing for perspective cameras.
+    pub fn update(&self, input: &InputState, world: &mut World, camera: &mut Camera, delta_time: f32) -> Result<(), &'static str> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let Some(data) = camera.ortho_data() else {
+            return Ok(());
+        };
+        let zoom = data.zoom;
+        // the direction of "down" on the screen, in camera units
+        let down = if data.invert_y { -1.0 } else { 1.0 };
+
+        let entity = world
+            .entity_mut(self.entity)
+            .ok_or("Ortho 2D controller couldn't find the entity")?;
+
+        let mut movement: Vector3<f32> = Vector3::zero();
+        let scaled_pan_speed = PAN_SPEED * delta_time / zoom;
+        if input.key_held(KeyCode::KeyW) || input.key_held(KeyCode::ArrowUp) {
+            movement.y -= scaled_pan_speed * down;
+        }
+        if input.key_held(KeyCode::KeyS) || input.key_held(KeyCode::ArrowDown) {
+            movement.y += scaled_pan_speed * down;
+        }
+        if input.key_held(KeyCode::KeyA) || input.key_held(KeyCode::ArrowLeft) {
+            movement.x -= scaled_pan_speed;
+        }
+        if input.key_held(KeyCode::KeyD) || input.key_held(KeyCode::ArrowRight) {
+            movement.x += scaled_pan_speed;
+        }
+
+        // dragging moves the scene with the cursor, ie the camera against it
+        if input.mouse_held(MouseButton::Middle) {
+            let mouse_delta = input.mouse_delta();
+            movement.x -= mouse_delta.x / zoom;
+            movement.y -= mouse_delta.y / zoom * down;
+        }
+        entity.update_local_transform(|transform| transform.position += movement);
+
+        // scrolling up zooms in, by a constant factor per line
+        let scroll = input.scroll_delta().y;
+        if scroll != 0.0 {
+            let zoom = (zoom * ZOOM_STEP.powf(scroll)).clamp(self.min_zoom, self.max_zoom);
+            camera.set_zoom(zoom);
+        }
+
+        Ok(())
+    }
+}
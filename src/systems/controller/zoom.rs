@@ -0,0 +1,75 @@
+use crate::{input::state::InputState, systems::camera::Camera};
+
+static ZOOM_SENS: f32 = 5.0;
+static ZOOM_SMOOTHING: f32 = 10.0;
+
+/// Zooms a perspective camera by mapping scroll input to a target FOV, and smoothly moving the FOV towards it.
+pub struct ZoomController {
+    target_fov: Option<f32>,
+    min_fov: f32,
+    max_fov: f32,
+}
+
+impl ZoomController {
+    /// Create a controller, with the FOV (in degrees) clamped to `[min_fov, max_fov]`.
+    pub fn new(min_fov: f32, max_fov: f32) -> Self {
+        Self {
+            target_fov: None,
+            min_fov,
+            max_fov,
+        }
+    }
+
+    /// Set the camera's FOV (in degrees) straight away, clamped to the controller's range, and get the clamped FOV.
+    /// 
+    /// The FOV is also kept as the target, so later updates don't move away from it.
+    pub fn set_fov(&mut self, camera: &mut Camera, fov: f32) -> f32 {
+        let fov = fov.clamp(self.min_fov, self.max_fov);
+        self.target_fov = Some(fov);
+        camera.set_fov(fov);
+        fov
+    }
+
+    /// Update the camera's FOV for this zoom controller.
+    /// 
+    /// Does nothing for orthographic cameras.
+    pub fn update(&mut self, input: &InputState, camera: &mut Camera, delta_time: f32) {
+        let Some(fov) = camera.fov() else {
+            return;
+        };
+        camera.set_fov(self.next_fov(fov, input, delta_time));
+    }
+
+    /// Move the target FOV by the scroll input, and get the FOV to use this frame, moved from `fov` towards it.
+    fn next_fov(&mut self, fov: f32, input: &InputState, delta_time: f32) -> f32 {
+        // scrolling up zooms in, ie narrows the FOV
+        let target_fov = self.target_fov.unwrap_or(fov) - input.scroll_delta().y * ZOOM_SENS;
+        let target_fov = target_fov.clamp(self.min_fov, self.max_fov);
+        self.target_fov = Some(target_fov);
+
+        // framerate-independent exponential smoothing
+        let t = 1.0 - (-ZOOM_SMOOTHING * delta_time).exp();
+        fov + (target_fov - fov) * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::MouseScrollDelta;
+    use super::*;
+
+    #[test]
+    fn scrolling_up_zooms_in_towards_the_min_fov() {
+        let mut controller = ZoomController::new(20.0, 90.0);
+        let mut input = InputState::new(false);
+        let mut fov = 45.0;
+        for _ in 0..120 {
+            input.begin_frame();
+            input.process_mouse_scroll(MouseScrollDelta::LineDelta(0.0, 1.0));
+            let next = controller.next_fov(fov, &input, 1.0 / 60.0);
+            assert!(next <= fov && next >= 20.0);
+            fov = next;
+        }
+        assert!((fov - 20.0).abs() < 0.01, "fov {fov} didn't reach the min");
+    }
+}
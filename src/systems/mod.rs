@@ -1,2 +1,3 @@
 pub mod camera;
-pub mod controller;
\ No newline at end of file
+pub mod controller;
+pub mod physics;pub mod animation;
@@ -0,0 +1,76 @@
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation3, Vector3};
+use crate::core::{components::ComponentStore, world::World};
+
+/// A velocity component, in the entity's parent space.
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity {
+    /// Units per second.
+    pub linear: Vector3<f32>,
+    /// The axis of rotation, with its length as the speed in radians per second.
+    pub angular: Vector3<f32>,
+}
+
+/// Move each entity with a velocity by it over `dt` seconds, by updating its local transform.
+///
+/// This should be called with a fixed `dt` for deterministic results. Entities that no longer exist are skipped.
+pub fn integrate_velocities(world: &mut World, velocities: &ComponentStore<Velocity>, dt: f32) {
+    for (id, velocity) in velocities.iter() {
+        let Some(entity) = world.entity_mut(id) else {
+            continue;
+        };
+        let angle = velocity.angular.magnitude() * dt;
+        entity.update_local_transform(|transform| {
+            transform.position += velocity.linear * dt;
+            if angle > 0.0 {
+                let spin = Quaternion::from_axis_angle(velocity.angular.normalize(), Rad(angle));
+                transform.rotation = (spin * transform.rotation).normalize();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Rotation, assert_abs_diff_eq};
+    use crate::core::entity::spatial_transform::SpatialTransform;
+    use super::*;
+
+    #[test]
+    fn fixed_steps_move_by_velocity_times_time() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        let mut velocities = ComponentStore::new();
+        velocities.insert(entity, Velocity {
+            linear: Vector3::new(2.0, 0.0, -1.0),
+            angular: Vector3::new(0.0, 90f32.to_radians(), 0.0),
+        });
+
+        // 60 steps of 1/60s is one second
+        for _ in 0..60 {
+            integrate_velocities(&mut world, &velocities, 1.0 / 60.0);
+        }
+
+        let transform = world.entity(entity).unwrap().local_transform();
+        assert_abs_diff_eq!(transform.position, Vector3::new(2.0, 0.0, -1.0), epsilon = 1e-4);
+        let expected = Quaternion::from_angle_y(Deg(90.0));
+        assert_abs_diff_eq!(
+            transform.rotation.rotate_vector(Vector3::unit_x()),
+            expected.rotate_vector(Vector3::unit_x()),
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn missing_entities_are_skipped() {
+        let mut world = World::new();
+        let entity = world.add_entity(None, vec![], SpatialTransform::identity());
+        let mut velocities = ComponentStore::new();
+        velocities.insert(entity, Velocity { linear: Vector3::unit_x(), angular: Vector3::new(0.0, 0.0, 0.0) });
+
+        // a different world, without the entity
+        let mut other_world = World::new();
+        integrate_velocities(&mut other_world, &velocities, 1.0);
+        integrate_velocities(&mut world, &velocities, 1.0);
+        assert_abs_diff_eq!(world.entity(entity).unwrap().local_transform().position, Vector3::unit_x());
+    }
+}